@@ -1,12 +1,19 @@
-#[cfg(target_os = "windows")]
 use log::debug;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
 #[cfg(target_os = "windows")]
 use shared_memory::*;
+use std::mem::{offset_of, size_of};
+
+/// Size of the game table in BWAPI's shared memory, which is also what `game_table` writes
+pub const GAME_TABLE_SIZE: usize = size_of::<GameTable>();
+
+/// How often the shared memory is read until two consecutive reads match
 #[cfg(target_os = "windows")]
-use std::mem::size_of;
+const MAX_READS: usize = 5;
 
 #[repr(C)]
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct GameInstance {
     pub server_process_id: u32,
     pub is_connected: bool,
@@ -14,11 +21,162 @@ pub struct GameInstance {
 }
 
 #[repr(C)]
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct GameTable {
     pub game_instances: [GameInstance; 8],
 }
 
+impl GameInstance {
+    /// Why the row looks like it was read while BWAPI was updating it, if it does. `previous` is
+    /// the same row in the previous snapshot.
+    pub fn implausibility(&self, previous: Option<&GameInstance>) -> Option<&'static str> {
+        if self.server_process_id == 0 {
+            return (self.is_connected || self.last_keep_alive_time != 0)
+                .then_some("empty slot with connection or keep alive");
+        }
+        // Windows (and wine) use multiples of 4 as process ids
+        if !self.server_process_id.is_multiple_of(4) {
+            return Some("invalid process id");
+        }
+        match previous {
+            // Tick counts wrap around, a "decrease" by more than half the range is an increase
+            Some(previous)
+                if previous.server_process_id == self.server_process_id
+                    && previous
+                        .last_keep_alive_time
+                        .wrapping_sub(self.last_keep_alive_time)
+                        .wrapping_sub(1)
+                        < u32::MAX / 2 =>
+            {
+                Some("keep alive went backwards")
+            }
+            _ => None,
+        }
+    }
+}
+
+/// What BWAPI servers (ie. StarCraft instances) are waiting for
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GameTableState {
+    /// No server registered
+    Empty,
+    /// At least one server waits for a client to connect
+    FreeSlot,
+    /// All registered servers have a client connected
+    SlotsBusy,
+}
+
+impl GameTable {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("GameTable is always serializable")
+    }
+
+    /// The slots as JSON array, each with its slot index
+    pub fn slots_to_json(&self, pretty: bool) -> String {
+        let slots: Vec<_> = self
+            .game_instances
+            .iter()
+            .enumerate()
+            .map(|(slot, instance)| {
+                json!({
+                    "slot": slot,
+                    "server_process_id": instance.server_process_id,
+                    "is_connected": instance.is_connected,
+                    "last_keep_alive_time": instance.last_keep_alive_time,
+                })
+            })
+            .collect();
+        if pretty {
+            serde_json::to_string_pretty(&slots)
+        } else {
+            serde_json::to_string(&slots)
+        }
+        .expect("Slots are always serializable")
+    }
+
+    pub fn state(&self) -> GameTableState {
+        let servers = || {
+            self.game_instances
+                .iter()
+                .filter(|it| it.server_process_id != 0)
+        };
+        if servers().next().is_none() {
+            GameTableState::Empty
+        } else if servers().any(|it| !it.is_connected) {
+            GameTableState::FreeSlot
+        } else {
+            GameTableState::SlotsBusy
+        }
+    }
+
+    /// True if all registered servers have a client connected. Implausible rows (see
+    /// `GameInstance::implausibility`) count as a server waiting for its client, a row read while
+    /// BWAPI updates it must not let the table look filled early.
+    pub fn all_slots_filled(&self, previous: Option<&GameTable>) -> bool {
+        self.game_instances
+            .iter()
+            .enumerate()
+            .all(|(slot, instance)| {
+                let previous = previous.map(|it| &it.game_instances[slot]);
+                instance.implausibility(previous).is_none()
+                    && (instance.server_process_id == 0 || instance.is_connected)
+            })
+    }
+
+    /// Copy of the table with implausible rows (see `GameInstance::implausibility`) cleared
+    pub fn without_implausible_rows(&self, previous: Option<&GameTable>) -> GameTable {
+        let mut game_table = *self;
+        for (slot, instance) in game_table.game_instances.iter_mut().enumerate() {
+            let previous = previous.map(|it| &it.game_instances[slot]);
+            if let Some(reason) = instance.implausibility(previous) {
+                debug!("Ignoring game table slot {slot} ({reason}): {instance:?}");
+                *instance = GameInstance::default();
+            }
+        }
+        game_table
+    }
+
+    /// The table in the memory layout of BWAPI, padding is zeroed
+    pub fn to_bytes(&self) -> [u8; GAME_TABLE_SIZE] {
+        let mut bytes = [0; GAME_TABLE_SIZE];
+        for (instance, chunk) in self
+            .game_instances
+            .iter()
+            .zip(bytes.chunks_exact_mut(size_of::<GameInstance>()))
+        {
+            chunk[offset_of!(GameInstance, server_process_id)..][..4]
+                .copy_from_slice(&instance.server_process_id.to_ne_bytes());
+            chunk[offset_of!(GameInstance, is_connected)] = instance.is_connected as u8;
+            chunk[offset_of!(GameInstance, last_keep_alive_time)..][..4]
+                .copy_from_slice(&instance.last_keep_alive_time.to_ne_bytes());
+        }
+        bytes
+    }
+
+    /// Reads a table written by `to_bytes` (or copied from BWAPI's shared memory), None if the
+    /// size doesn't match
+    pub fn from_bytes(bytes: &[u8]) -> Option<GameTable> {
+        if bytes.len() != GAME_TABLE_SIZE {
+            return None;
+        }
+        let u32_at = |chunk: &[u8], offset: usize| {
+            u32::from_ne_bytes(chunk[offset..][..4].try_into().expect("4 bytes"))
+        };
+        let mut game_instances = [GameInstance::default(); 8];
+        for (instance, chunk) in game_instances
+            .iter_mut()
+            .zip(bytes.chunks_exact(size_of::<GameInstance>()))
+        {
+            *instance = GameInstance {
+                server_process_id: u32_at(chunk, offset_of!(GameInstance, server_process_id)),
+                is_connected: chunk[offset_of!(GameInstance, is_connected)] != 0,
+                last_keep_alive_time: u32_at(chunk, offset_of!(GameInstance, last_keep_alive_time)),
+            };
+        }
+        Some(GameTable { game_instances })
+    }
+}
+
 #[cfg(target_os = "windows")]
 pub struct GameTableAccess {
     game_table: Option<Shmem>,
@@ -42,8 +200,138 @@ impl GameTableAccess {
                 Err(err) => debug!("{}", err),
             }
         }
-        self.game_table
-            .as_ref()
-            .map(|shmem| unsafe { *(shmem.as_ptr() as *const GameTable) })
+        let shmem = self.game_table.as_ref()?;
+        // BWAPI updates the table without synchronization, read until it doesn't change
+        let read =
+            || unsafe { std::slice::from_raw_parts(shmem.as_ptr(), GAME_TABLE_SIZE) }.to_vec();
+        let mut snapshot = read();
+        for _ in 1..MAX_READS {
+            let next = read();
+            if next == snapshot {
+                break;
+            }
+            snapshot = next;
+        }
+        GameTable::from_bytes(&snapshot)
+    }
+
+    /// The current game table as JSON, if BWAPI's shared memory is available
+    pub fn dump_to_json(&mut self) -> Option<String> {
+        self.get_game_table().map(|table| table.to_json())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::game_table::{GameInstance, GameTable, GameTableState, GAME_TABLE_SIZE};
+
+    fn game_table() -> GameTable {
+        let mut game_instances = [GameInstance::default(); 8];
+        game_instances[2] = GameInstance {
+            server_process_id: 4712,
+            is_connected: true,
+            last_keep_alive_time: 123456,
+        };
+        GameTable { game_instances }
+    }
+
+    #[test]
+    fn test_bytes_round_trip() {
+        let game_table = game_table();
+        let bytes = game_table.to_bytes();
+        assert_eq!(bytes.len(), 96);
+        assert_eq!(GameTable::from_bytes(&bytes), Some(game_table));
+        assert_eq!(GameTable::from_bytes(&bytes[1..]), None);
+        // Other tools might write any non-zero byte for true
+        let mut bytes = bytes;
+        bytes[2 * 12 + 4] = 0xff;
+        assert_eq!(GameTable::from_bytes(&bytes), Some(game_table));
+        assert_eq!(GAME_TABLE_SIZE, 96);
+    }
+
+    #[test]
+    fn test_implausible_rows() {
+        let previous = game_table();
+        let mut current = previous;
+        // Half written rows
+        current.game_instances[0].is_connected = true;
+        current.game_instances[1] = GameInstance {
+            server_process_id: 0x7a3f_11c1,
+            is_connected: true,
+            last_keep_alive_time: 17,
+        };
+        // Keep alive went backwards
+        current.game_instances[2].last_keep_alive_time -= 1000;
+        current.game_instances[3] = GameInstance {
+            server_process_id: 1236,
+            is_connected: false,
+            last_keep_alive_time: 100,
+        };
+        assert!(!current.all_slots_filled(Some(&previous)));
+        let validated = current.without_implausible_rows(Some(&previous));
+        // Only the plausible row 3 waits for a client
+        assert!(!validated.all_slots_filled(Some(&previous)));
+        current.game_instances[3].is_connected = true;
+        assert!(current
+            .without_implausible_rows(Some(&previous))
+            .all_slots_filled(Some(&previous)));
+        // But the implausible rows might be servers waiting for their client as well
+        assert!(!current.all_slots_filled(Some(&previous)));
+        current.game_instances[3].is_connected = false;
+        assert_eq!(validated.game_instances[0], GameInstance::default());
+        assert_eq!(validated.game_instances[1], GameInstance::default());
+        assert_eq!(validated.game_instances[2], GameInstance::default());
+        assert_eq!(validated.game_instances[3], current.game_instances[3]);
+        // Without a previous snapshot, keep alive can't be checked
+        assert_eq!(
+            current.without_implausible_rows(None).game_instances[2],
+            current.game_instances[2]
+        );
+
+        // Keep alive wrapping around or staying the same is fine
+        let mut wrapped = previous;
+        wrapped.game_instances[2].last_keep_alive_time = u32::MAX - 10;
+        let mut current = wrapped;
+        current.game_instances[2].last_keep_alive_time = 5;
+        assert_eq!(current.without_implausible_rows(Some(&wrapped)), current);
+        assert_eq!(wrapped.without_implausible_rows(Some(&wrapped)), wrapped);
+    }
+
+    #[test]
+    fn test_state_and_slots() {
+        let mut game_table = game_table();
+        assert_eq!(game_table.state(), GameTableState::SlotsBusy);
+        game_table.game_instances[0].server_process_id = 42;
+        assert_eq!(game_table.state(), GameTableState::FreeSlot);
+        assert_eq!(
+            GameTable::from_bytes(&[0; GAME_TABLE_SIZE])
+                .unwrap()
+                .state(),
+            GameTableState::Empty
+        );
+        assert!(game_table.slots_to_json(false).starts_with(
+            r#"[{"is_connected":false,"last_keep_alive_time":0,"server_process_id":42,"slot":0}"#
+        ));
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let mut game_instances = [GameInstance {
+            server_process_id: 0,
+            is_connected: false,
+            last_keep_alive_time: 0,
+        }; 8];
+        game_instances[0] = GameInstance {
+            server_process_id: 4711,
+            is_connected: true,
+            last_keep_alive_time: 123456,
+        };
+        let game_table = GameTable { game_instances };
+
+        let json = game_table.to_json();
+        assert!(json.contains("\"server_process_id\": 4711"));
+
+        let deserialized: GameTable = serde_json::from_str(&json).unwrap();
+        assert_eq!(deserialized, game_table);
     }
 }
@@ -1,5 +1,5 @@
 mod game_table;
 
-pub use crate::game_table::GameTable;
 #[cfg(target_os = "windows")]
 pub use crate::game_table::GameTableAccess;
+pub use crate::game_table::{GameInstance, GameTable, GameTableState, GAME_TABLE_SIZE};
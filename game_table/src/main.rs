@@ -1,12 +1,60 @@
-mod game_table;
-use crate::game_table::*;
+use game_table::{GameTable, GameTableState};
 use std::io::Write;
+use std::process::ExitCode;
 
-fn main() {
+const USAGE: &str = "Usage: game_table [--json] [--pretty] [--exit-code]
+  Without arguments, the raw game table is written to stdout (nothing if there is none)
+  --json       Print the slots of the game table as JSON ('null' if there is none)
+  --pretty     Pretty print the JSON, implies --json
+  --exit-code  Exit with 0 if a server waits for a client, 2 if there is no game table,
+               3 if the game table is empty or 4 if all slots are busy";
+
+/// Exit codes of `--exit-code`, 1 is used for invalid arguments
+const NO_TABLE: u8 = 2;
+const TABLE_EMPTY: u8 = 3;
+const SLOTS_BUSY: u8 = 4;
+
+fn read_game_table() -> Option<GameTable> {
     #[cfg(target_os = "windows")]
-    GameTableAccess::new().get_game_table().map(|out| {
-        std::io::stdout().write(unsafe {
-            &std::mem::transmute::<GameTable, [u8; std::mem::size_of::<GameTable>()]>(out)[..]
-        })
-    });
+    {
+        game_table::GameTableAccess::new().get_game_table()
+    }
+    #[cfg(not(target_os = "windows"))]
+    {
+        None
+    }
+}
+
+fn main() -> ExitCode {
+    let (mut json, mut pretty, mut exit_code) = (false, false, false);
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--json" => json = true,
+            "--pretty" => pretty = true,
+            "--exit-code" => exit_code = true,
+            _ => {
+                eprintln!("Unknown argument '{arg}'\n{USAGE}");
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+    let game_table = read_game_table();
+    if json || pretty {
+        match game_table {
+            Some(game_table) => println!("{}", game_table.slots_to_json(pretty)),
+            None => println!("null"),
+        }
+    } else if let Some(game_table) = game_table {
+        // Read by bwaishotgun on Linux via wine, see src/bwapi.rs
+        std::io::stdout().write_all(&game_table.to_bytes()).ok();
+    }
+    if !exit_code {
+        return ExitCode::SUCCESS;
+    }
+    ExitCode::from(match game_table.map(|it| it.state()) {
+        None => NO_TABLE,
+        Some(GameTableState::Empty) => TABLE_EMPTY,
+        Some(GameTableState::FreeSlot) => 0,
+        Some(GameTableState::SlotsBusy) => SLOTS_BUSY,
+    })
 }
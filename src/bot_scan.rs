@@ -0,0 +1,168 @@
+//! Finds files in bot folders, which StarCraft or BWAPI might load instead of the intended ones
+//! (ie. a second BWAPI.dll or an SNP shipped with the bot). These cause version mismatches which
+//! are hard to track down.
+use std::fs::{create_dir_all, rename};
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+
+/// Folder inside the bot folder, suspicious files are moved to with `auto_quarantine`
+pub const QUARANTINE_FOLDER: &str = "quarantine";
+/// Files nested deeper are not checked
+const MAX_DEPTH: usize = 5;
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct SuspiciousFile {
+    /// Relative to the bot folder
+    pub path: PathBuf,
+    pub reason: &'static str,
+    pub action: &'static str,
+    /// Moved by `quarantine`. Runtimes might be needed by the bot, they are only reported.
+    pub quarantine: bool,
+}
+
+/// Checks a single file, `path` is relative to the bot folder
+fn check_file(path: &Path) -> Option<SuspiciousFile> {
+    let file_name = path.file_name()?.to_string_lossy().to_ascii_lowercase();
+    let in_root = path.parent().is_some_and(|it| it.as_os_str().is_empty());
+    let (reason, action, quarantine) = if file_name.starts_with("snp_")
+        && file_name.ends_with(".snp")
+    {
+        (
+            "StarCraft might load it instead of the SNP of the StarCraft installation",
+            "remove it",
+            true,
+        )
+    } else if file_name == "bwapi.dll" && path.parent() != Some(Path::new("bwapi-data")) {
+        (
+            "it might be loaded instead of 'bwapi-data/BWAPI.dll'",
+            "move it to 'bwapi-data' if it is the intended one, remove it otherwise",
+            true,
+        )
+    } else if file_name.starts_with("chaoslauncher") || file_name.ends_with(".bwl") {
+        (
+            "Chaoslauncher and its plugins are not used by BWAIShotgun",
+            "remove it",
+            true,
+        )
+    } else if in_root
+        && file_name.ends_with(".dll")
+        && ["msvcr", "msvcp", "vcruntime", "libgmp", "gmp"]
+            .iter()
+            .any(|prefix| file_name.starts_with(prefix))
+    {
+        (
+            "StarCraft is started in the bot folder and might load this runtime instead of the installed one",
+            "move it next to the bot binary in 'bwapi-data/AI', remove it if the bot is a DLL",
+            false,
+        )
+    } else {
+        return None;
+    };
+    Some(SuspiciousFile {
+        path: path.to_path_buf(),
+        reason,
+        action,
+        quarantine,
+    })
+}
+
+/// Suspicious files in the bot folder, the quarantine folder is skipped
+pub fn scan_bot_folder(bot_folder: &Path) -> Vec<SuspiciousFile> {
+    let mut found = vec![];
+    scan(bot_folder, Path::new(""), 0, &mut found);
+    found.sort_by(|a, b| a.path.cmp(&b.path));
+    found
+}
+
+fn scan(bot_folder: &Path, relative: &Path, depth: usize, found: &mut Vec<SuspiciousFile>) {
+    if depth > MAX_DEPTH {
+        return;
+    }
+    for entry in bot_folder
+        .join(relative)
+        .read_dir()
+        .into_iter()
+        .flatten()
+        .flatten()
+    {
+        let path = relative.join(entry.file_name());
+        match entry.file_type() {
+            Ok(file_type) if file_type.is_dir() => {
+                if path != Path::new(QUARANTINE_FOLDER) {
+                    scan(bot_folder, &path, depth + 1, found);
+                }
+            }
+            Ok(_) => found.extend(check_file(&path)),
+            Err(_) => (),
+        }
+    }
+}
+
+/// Moves the files into the quarantine folder of the bot, keeping their relative path. Files
+/// without `SuspiciousFile::quarantine` are left in place.
+pub fn quarantine(bot_folder: &Path, files: &[SuspiciousFile]) -> anyhow::Result<()> {
+    for file in files.iter().filter(|file| file.quarantine) {
+        let target = bot_folder.join(QUARANTINE_FOLDER).join(&file.path);
+        if let Some(folder) = target.parent() {
+            create_dir_all(folder)?;
+        }
+        rename(bot_folder.join(&file.path), &target)
+            .with_context(|| format!("Could not quarantine '{}'", file.path.display()))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::bot_scan::{quarantine, scan_bot_folder, QUARANTINE_FOLDER};
+    use crate::test_util::TempDir;
+    use std::fs::{create_dir_all, write};
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn test_scan_bot_folder() {
+        let bot = TempDir::new("bot-scan");
+        for file in [
+            "bwapi-data/BWAPI.dll",
+            "bwapi-data/AI/BWAPI.dll",
+            "bwapi-data/AI/msvcp140.dll",
+            "bwapi-data/AI/Bot.exe",
+            "SNP_DirectIP.snp",
+            "Chaoslauncher/Chaoslauncher.exe",
+            "vcruntime140.dll",
+        ] {
+            let path = bot.join(file);
+            create_dir_all(path.parent().unwrap()).unwrap();
+            write(path, "").unwrap();
+        }
+
+        let found: Vec<_> = scan_bot_folder(&bot)
+            .into_iter()
+            .map(|it| it.path)
+            .collect();
+        assert_eq!(
+            found,
+            [
+                "Chaoslauncher/Chaoslauncher.exe",
+                "SNP_DirectIP.snp",
+                "bwapi-data/AI/BWAPI.dll",
+                "vcruntime140.dll"
+            ]
+            .map(PathBuf::from)
+        );
+
+        quarantine(&bot, &scan_bot_folder(&bot)).unwrap();
+        let left: Vec<_> = scan_bot_folder(&bot)
+            .into_iter()
+            .map(|it| it.path)
+            .collect();
+        // Runtimes are only reported
+        assert_eq!(left, [PathBuf::from("vcruntime140.dll")]);
+        assert!(bot
+            .join(QUARANTINE_FOLDER)
+            .join(Path::new("bwapi-data/AI/BWAPI.dll"))
+            .exists());
+        assert!(bot.join("bwapi-data/BWAPI.dll").exists());
+    }
+}
@@ -1,12 +1,23 @@
+use crate::bwapi::{BwapiConfig, BwapiVersion, IniSections};
 use crate::{ExecutionWrapper, GameConfig, Race};
 use anyhow::{bail, Context};
-use log::debug;
+use log::{debug, warn};
+use std::collections::HashSet;
 use std::fs::read_dir;
 use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::time::{Duration, SystemTime};
+
+/// Upper limit of `bot_binary_scan_depth`, in case of deeply nested folders
+const MAX_SCAN_DEPTH: usize = 5;
 
 pub trait LaunchBuilder {
     fn build_command(&self, game_config: &GameConfig) -> anyhow::Result<Command>;
+
+    /// How long a client bot might take to connect to BWAPI after being launched
+    fn estimated_startup_time(&self) -> Duration {
+        Duration::from_secs(10)
+    }
 }
 
 #[derive(Debug)]
@@ -20,6 +31,71 @@ pub struct BotSetup {
     pub race: Race,
     pub wrapper: ExecutionWrapper,
     pub replay_path: Option<String>,
+    pub bwapi_config: BwapiConfig,
+    pub bwapi_version: Option<BwapiVersion>,
+    pub tools_dir: PathBuf,
+    /// See `BwapiIni::path`
+    pub bwapi_ini: PathBuf,
+    /// See `BwapiIni::validate`
+    pub validate_bwapi_ini: bool,
+    /// Parsed `extra_bwapi_ini` of the bot
+    pub extra_bwapi_ini: IniSections,
+}
+
+impl BotSetup {
+    #[cfg(test)]
+    pub fn for_test() -> Self {
+        Self {
+            starcraft_exe: PathBuf::from("StarCraft/StarCraft.exe"),
+            starcraft_path: PathBuf::from("StarCraft"),
+            player_name: "Bot".to_string(),
+            bot_binary: Binary::Dll(PathBuf::from("Bot.dll")),
+            bot_base_path: PathBuf::from("bots/Bot"),
+            tournament_module: None,
+            race: Race::Terran,
+            wrapper: ExecutionWrapper::NoWrapper,
+            replay_path: None,
+            bwapi_config: Default::default(),
+            bwapi_version: None,
+            tools_dir: PathBuf::from("tools"),
+            bwapi_ini: PathBuf::from("bots/Bot/bwapi-data/bwapi.ini"),
+            validate_bwapi_ini: true,
+            extra_bwapi_ini: vec![],
+        }
+    }
+
+    /// Helper dlls next to a DLL bot are only found by Windows, if their folder is on the search path
+    pub fn add_dll_search_path(&self, cmd: &mut Command) -> anyhow::Result<()> {
+        let Binary::Dll(dll) = &self.bot_binary else {
+            return Ok(());
+        };
+        let Some(dll_folder) = dll.parent() else {
+            return Ok(());
+        };
+        let dll_folder = self.bot_base_path.join(dll_folder);
+        if matches!(self.wrapper, ExecutionWrapper::Wine) {
+            // Wine expects Windows paths separated by ';', not the paths of the host
+            let mut wine_paths = wine_path(&std::path::absolute(&dll_folder)?);
+            if let Some(existing) = std::env::var_os("WINEPATH") {
+                wine_paths.push(';');
+                wine_paths.push_str(&existing.to_string_lossy());
+            }
+            cmd.env("WINEPATH", wine_paths);
+            return Ok(());
+        }
+        let paths = std::iter::once(dll_folder).chain(
+            std::env::var_os("PATH")
+                .map(|it| std::env::split_paths(&it).collect::<Vec<_>>())
+                .unwrap_or_default(),
+        );
+        cmd.env("PATH", std::env::join_paths(paths)?);
+        Ok(())
+    }
+}
+
+/// The path of an absolute host path inside Wine, which maps '/' to the drive 'Z:'
+fn wine_path(path: &Path) -> String {
+    format!("Z:{}", path.to_string_lossy().replace('/', "\\"))
 }
 
 #[derive(Clone, Debug)]
@@ -29,6 +105,13 @@ pub enum Binary {
     Exe(PathBuf),
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BinaryKind {
+    Dll,
+    Jar,
+    Exe,
+}
+
 impl Binary {
     pub(crate) fn from_path(path: &Path) -> Option<Self> {
         path.extension()
@@ -46,32 +129,263 @@ impl Binary {
             })
     }
 
-    pub(crate) fn search(search_path: &Path) -> anyhow::Result<Self> {
-        let mut executable = None;
+    pub fn kind(&self) -> BinaryKind {
+        match self {
+            Binary::Dll(_) => BinaryKind::Dll,
+            Binary::Jar(_) => BinaryKind::Jar,
+            Binary::Exe(_) => BinaryKind::Exe,
+        }
+    }
+
+    /// The order in which `search` considers the binary types, if multiple types are found: A
+    /// later type replaces an earlier one, except for a jar found next to a DLL (the jar might just
+    /// be a library of the bot). So an exe always wins and a DLL wins over a jar.
+    pub fn preferred_order() -> &'static [BinaryKind] {
+        &[BinaryKind::Dll, BinaryKind::Jar, BinaryKind::Exe]
+    }
+
+    pub fn path(&self) -> &Path {
+        match self {
+            Binary::Dll(path) | Binary::Jar(path) | Binary::Exe(path) => path,
+        }
+    }
+
+    /// Searches for the bot binary, see `preferred_order`. Multiple candidates of the same type are
+    /// an error, unless `prefer_newest` is set: The most recently modified one is used then (or the
+    /// first by name, if they were modified at the same time).
+    /// Files matching any of the `ignore` patterns (ie. helper dlls) are skipped.
+    /// Subfolders are searched up to `depth` levels (1 = only `search_path`), breadth-first: Only
+    /// if a level contains no binary at all, the next one is searched.
+    pub(crate) fn search(
+        search_path: &Path,
+        prefer_newest: bool,
+        ignore: &[String],
+        depth: usize,
+    ) -> anyhow::Result<Self> {
         debug!("Searching for bot in '{}'", search_path.display());
-        for file in read_dir(search_path)
-            .with_context(|| format!("Could not search in {}", search_path.display()))?
-            .flatten()
-        {
-            let path = file.path();
-            if let Some(detected_binary) = Binary::from_path(&path) {
-                executable = Some(match (executable, detected_binary) {
-                    (None, dll @ Binary::Dll(_)) | (Some(dll @ Binary::Dll(_)), Binary::Jar(_)) => {
-                        dll
+        let mut folders = vec![search_path.to_path_buf()];
+        // Symlinks might form loops
+        let mut visited = HashSet::new();
+        for _ in 0..depth.clamp(1, MAX_SCAN_DEPTH) {
+            let mut candidates = vec![];
+            let mut subfolders = vec![];
+            for folder in folders {
+                if !visited.insert(folder.canonicalize().unwrap_or_else(|_| folder.clone())) {
+                    continue;
+                }
+                for file in read_dir(&folder)
+                    .with_context(|| format!("Could not search in {}", folder.display()))?
+                    .flatten()
+                {
+                    let file_name = file.file_name().to_string_lossy().to_string();
+                    if file.path().is_dir() {
+                        subfolders.push(file.path());
+                        continue;
+                    }
+                    if ignore
+                        .iter()
+                        .any(|pattern| matches_pattern(pattern, &file_name))
+                    {
+                        debug!("Ignoring '{file_name}'");
+                        continue;
                     }
-                    (None, jar @ Binary::Jar(_)) => jar,
-                    (None, exe @ Binary::Exe(_))
-                    | (Some(Binary::Dll(_) | Binary::Jar(_)), exe @ Binary::Exe(_)) => exe,
-                    _ => bail!(
-                        "Found multiple binary candidates in '{}', please select one in 'bot.toml'",
-                        search_path.to_string_lossy()
-                    ),
-                })
+                    if let Some(detected_binary) = Binary::from_path(&file.path()) {
+                        let modified = file
+                            .metadata()
+                            .and_then(|it| it.modified())
+                            .unwrap_or(SystemTime::UNIX_EPOCH);
+                        candidates.push((detected_binary, modified));
+                    }
+                }
+            }
+            if !candidates.is_empty() {
+                return Self::select(search_path, prefer_newest, candidates);
+            }
+            subfolders.sort();
+            folders = subfolders;
+        }
+        bail!("No binary found in '{}'", search_path.to_string_lossy())
+    }
+
+    fn select(
+        search_path: &Path,
+        prefer_newest: bool,
+        candidates: Vec<(Binary, SystemTime)>,
+    ) -> anyhow::Result<Self> {
+        let mut selected = None;
+        for kind in Binary::preferred_order() {
+            if candidates.iter().any(|(binary, _)| binary.kind() == *kind) {
+                selected = match (selected, kind) {
+                    (Some(BinaryKind::Dll), BinaryKind::Jar) => selected,
+                    _ => Some(*kind),
+                };
             }
         }
-        match executable {
-            None => bail!("No binary found in '{}'", search_path.to_string_lossy()),
-            Some(x) => Ok(x),
+        let mut of_kind: Vec<_> = candidates
+            .iter()
+            .filter(|(binary, _)| Some(binary.kind()) == selected)
+            .collect();
+        of_kind.sort_by(|(a, a_modified), (b, b_modified)| {
+            b_modified
+                .cmp(a_modified)
+                .then_with(|| a.path().cmp(b.path()))
+        });
+        match of_kind.as_slice() {
+            [] => bail!("No binary found in '{}'", search_path.to_string_lossy()),
+            [(binary, _)] => Ok(binary.clone()),
+            [(newest, _), ..] => {
+                let candidate_list = of_kind
+                    .iter()
+                    .map(|(binary, modified)| {
+                        format!(
+                            "  {} (modified {} ago)",
+                            binary.path().display(),
+                            format_age(*modified)
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                if prefer_newest {
+                    warn!(
+                        "Found multiple binary candidates in '{}', using '{}':\n{}",
+                        search_path.to_string_lossy(),
+                        newest.path().display(),
+                        candidate_list
+                    );
+                    return Ok(newest.clone());
+                }
+                bail!(
+                    "Found multiple binary candidates in '{}', please select one in 'bot.toml' or set 'prefer_newest = true':\n{}",
+                    search_path.to_string_lossy(),
+                    candidate_list
+                );
+            }
         }
     }
 }
+
+/// Case insensitive file name matching, supporting '*' and '?' wildcards
+pub(crate) fn matches_pattern(pattern: &str, file_name: &str) -> bool {
+    fn matches(pattern: &[char], name: &[char]) -> bool {
+        match (pattern.first(), name.first()) {
+            (None, None) => true,
+            (Some('*'), _) => {
+                matches(&pattern[1..], name) || (!name.is_empty() && matches(pattern, &name[1..]))
+            }
+            (Some('?'), Some(_)) => matches(&pattern[1..], &name[1..]),
+            (Some(p), Some(n)) if p == n => matches(&pattern[1..], &name[1..]),
+            _ => false,
+        }
+    }
+    let pattern: Vec<_> = pattern.to_lowercase().chars().collect();
+    let name: Vec<_> = file_name.to_lowercase().chars().collect();
+    matches(&pattern, &name)
+}
+
+fn format_age(modified: SystemTime) -> String {
+    let minutes = modified.elapsed().unwrap_or_default().as_secs() / 60;
+    match (minutes / (60 * 24), minutes / 60 % 24, minutes % 60) {
+        (0, 0, minutes) => format!("{minutes}m"),
+        (0, hours, minutes) => format!("{hours}h {minutes}m"),
+        (days, hours, _) => format!("{days}d {hours}h"),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::botsetup::{matches_pattern, wine_path, Binary, BinaryKind};
+    use crate::test_util::TempDir;
+    use std::fs::{create_dir_all, File};
+    use std::path::Path;
+    use std::time::{Duration, SystemTime};
+
+    fn search_folder(name: &str, files: &[(&str, u64)]) -> TempDir {
+        let folder = TempDir::new(name);
+        for (file, age_secs) in files {
+            File::create(folder.join(file))
+                .unwrap()
+                .set_modified(SystemTime::now() - Duration::from_secs(*age_secs))
+                .unwrap();
+        }
+        folder
+    }
+
+    #[test]
+    fn test_wine_path() {
+        assert_eq!(
+            wine_path(Path::new("/home/shotgun/bots/Bot/AI")),
+            r"Z:\home\shotgun\bots\Bot\AI"
+        );
+    }
+
+    #[test]
+    fn test_search_prefers_exe() {
+        let binary = Binary::search(Path::new("test-resources/binaries"), false, &[], 1).unwrap();
+        assert_eq!(binary.kind(), BinaryKind::Exe);
+    }
+
+    #[test]
+    fn test_search_prefers_dll_over_jar() {
+        let folder = search_folder("dll-jar", &[("Bot.jar", 0), ("Bot.dll", 0)]);
+        let binary = Binary::search(&folder, false, &[], 1).unwrap();
+        assert_eq!(binary.kind(), BinaryKind::Dll);
+    }
+
+    #[test]
+    fn test_search_multiple_candidates() {
+        let folder = search_folder("multiple", &[("old.dll", 3600), ("new.dll", 60)]);
+        let err = Binary::search(&folder, false, &[], 1)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("old.dll"));
+        assert!(err.contains("new.dll"));
+    }
+
+    #[test]
+    fn test_search_prefer_newest() {
+        let folder = search_folder("newest", &[("old.dll", 3600), ("new.dll", 60)]);
+        let binary = Binary::search(&folder, true, &[], 1).unwrap();
+        assert_eq!(binary.path(), folder.join("new.dll"));
+    }
+
+    #[test]
+    fn test_search_prefer_newest_tie() {
+        let folder = search_folder("tie", &[("b.dll", 60), ("a.dll", 60)]);
+        let files: Vec<_> = ["a.dll", "b.dll"].map(|it| folder.join(it)).into();
+        let modified = files[0].metadata().unwrap().modified().unwrap();
+        File::options()
+            .write(true)
+            .open(&files[1])
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+        let binary = Binary::search(&folder, true, &[], 1).unwrap();
+        assert_eq!(binary.path(), files[0]);
+    }
+
+    #[test]
+    fn test_search_ignore() {
+        let folder = search_folder("ignore", &[("MyBot.dll", 60), ("opencv_world.dll", 60)]);
+        let binary = Binary::search(&folder, false, &["opencv*.dll".to_string()], 1).unwrap();
+        assert_eq!(binary.path(), folder.join("MyBot.dll"));
+    }
+
+    #[test]
+    fn test_search_depth() {
+        let folder = search_folder("depth", &[]);
+        create_dir_all(folder.join("bin").join("x86")).unwrap();
+        File::create(folder.join("bin").join("MyBot.exe")).unwrap();
+        File::create(folder.join("bin").join("x86").join("Helper.exe")).unwrap();
+        assert!(Binary::search(&folder, false, &[], 1).is_err());
+        let binary = Binary::search(&folder, false, &[], 3).unwrap();
+        assert_eq!(binary.path(), folder.join("bin").join("MyBot.exe"));
+    }
+
+    #[test]
+    fn test_matches_pattern() {
+        assert!(matches_pattern("*.dll", "torch.DLL"));
+        assert!(matches_pattern("opencv_?.dll", "opencv_1.dll"));
+        assert!(!matches_pattern("opencv*.dll", "MyBot.dll"));
+        assert!(!matches_pattern("torch.dll", "torch.dll.bak"));
+    }
+}
@@ -1,38 +1,155 @@
 use crate::botsetup::BotSetup;
-#[cfg(not(target_os = "windows"))]
-use crate::tools_folder;
 use crate::{Binary, Race};
-#[cfg(not(target_os = "windows"))]
-use anyhow::Context;
+use anyhow::{anyhow, bail, ensure, Context};
 use game_table::GameTable;
 #[cfg(not(target_os = "windows"))]
-use log::{debug, trace};
+use log::trace;
+use log::{debug, error, info, warn};
+use retry::delay::Fixed;
+use retry::{retry, OperationResult};
+use serde::Deserialize;
+use std::collections::{HashSet, VecDeque};
+use std::fmt::{Display, Formatter};
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::process::Child;
 #[cfg(not(target_os = "windows"))]
 use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+/// Maximum number of game table snapshots kept in the history
+const GAME_TABLE_HISTORY_LEN: usize = 50;
+
+/// Bounded history of game table changes, to analyze failures to fill the game table.
+/// Keep alive times are ignored when checking for changes.
+pub struct GameTableHistory {
+    started: Instant,
+    snapshots: VecDeque<(Duration, Option<GameTable>)>,
+}
+
+impl GameTableHistory {
+    fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            snapshots: VecDeque::new(),
+        }
+    }
+
+    fn record(&mut self, game_table: Option<&GameTable>) {
+        let unchanged = self
+            .snapshots
+            .back()
+            .is_some_and(|(_, last)| match (last, game_table) {
+                (Some(last), Some(current)) => last
+                    .game_instances
+                    .iter()
+                    .zip(current.game_instances.iter())
+                    .all(|(a, b)| {
+                        a.server_process_id == b.server_process_id
+                            && a.is_connected == b.is_connected
+                    }),
+                (None, None) => true,
+                _ => false,
+            });
+        if unchanged {
+            return;
+        }
+        if self.snapshots.len() == GAME_TABLE_HISTORY_LEN {
+            self.snapshots.pop_front();
+        }
+        self.snapshots
+            .push_back((self.started.elapsed(), game_table.copied()));
+    }
+}
+
+impl Display for GameTableHistory {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (at, game_table) in self.snapshots.iter() {
+            write!(f, "+{:.1}s:", at.as_secs_f64())?;
+            match game_table {
+                Some(game_table) => {
+                    for (slot, instance) in game_table
+                        .game_instances
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, it)| it.server_process_id != 0)
+                    {
+                        write!(
+                            f,
+                            " [{slot}: pid {} {} (keep alive {})]",
+                            instance.server_process_id,
+                            if instance.is_connected {
+                                "connected"
+                            } else {
+                                "free"
+                            },
+                            instance.last_keep_alive_time
+                        )?;
+                    }
+                    writeln!(f)?;
+                }
+                None => writeln!(f, " no game table")?,
+            }
+        }
+        Ok(())
+    }
+}
 
 pub struct GameTableAccess {
     #[cfg(target_os = "windows")]
     delegate: game_table::GameTableAccess,
+    /// Reads the game table from within wine
+    #[cfg(not(target_os = "windows"))]
+    game_table_exe: PathBuf,
+    history: GameTableHistory,
+    /// Last validated snapshot, to check the keep alive of the next one
+    last_game_table: Option<GameTable>,
 }
 
 impl GameTableAccess {
-    pub fn new() -> Self {
+    pub fn new(tools_dir: &Path) -> Self {
+        #[cfg(target_os = "windows")]
+        let _ = tools_dir;
         Self {
             #[cfg(target_os = "windows")]
             delegate: game_table::GameTableAccess::new(),
+            #[cfg(not(target_os = "windows"))]
+            game_table_exe: tools_dir.join("game_table.exe"),
+            history: GameTableHistory::new(),
+            last_game_table: None,
         }
     }
 
+    /// All changes of the game table observed by this accessor
+    pub fn history(&self) -> &GameTableHistory {
+        &self.history
+    }
+
+    /// The game table without implausible rows (see `GameTable::without_implausible_rows`)
     pub fn get_game_table(&mut self) -> Option<GameTable> {
+        self.read_checked().map(|(_, game_table)| game_table)
+    }
+
+    /// The game table as read and without implausible rows, only the latter is recorded
+    fn read_checked(&mut self) -> Option<(GameTable, GameTable)> {
+        let game_table = self.read_game_table();
+        let validated =
+            game_table.map(|it| it.without_implausible_rows(self.last_game_table.as_ref()));
+        if validated.is_some() {
+            self.last_game_table = validated;
+        }
+        self.history.record(validated.as_ref());
+        game_table.zip(validated)
+    }
+
+    fn read_game_table(&mut self) -> Option<GameTable> {
         #[cfg(target_os = "windows")]
         {
             self.delegate.get_game_table()
         }
         #[cfg(not(target_os = "windows"))]
         {
-            let game_table_path = tools_folder().join("game_table.exe");
+            let game_table_path = &self.game_table_exe;
             if !game_table_path.exists() {
                 panic!("Missing '{}'", game_table_path.display());
             }
@@ -43,31 +160,84 @@ impl GameTableAccess {
                 .output()
                 .context("Executing game_table.exe with wine")
                 .expect("Unable to execute game_table.exe with wine");
-            if output.stdout.len() == std::mem::size_of::<GameTable>() {
-                let res: GameTable =
-                    unsafe { std::ptr::read_unaligned(output.stdout.as_slice().as_ptr().cast()) };
-                trace!("{res:?}");
-                Some(res)
-            } else {
-                trace!(
+            let res = GameTable::from_bytes(&output.stdout);
+            match res {
+                Some(res) => trace!("{res:?}"),
+                None => trace!(
                     "Expected game table, got: {} ",
                     String::from_utf8_lossy(&output.stdout)
-                );
-                None
+                ),
             }
+            res
         }
     }
 
     pub fn all_slots_filled(&mut self) -> bool {
-        self.get_game_table()
-            .map(|table| {
-                // eprintln!("{:#?}", table);
-                !table
-                    .game_instances
-                    .iter()
-                    .any(|it| it.server_process_id != 0 && !it.is_connected)
-            })
-            .unwrap_or(false)
+        let previous = self.last_game_table;
+        self.read_checked()
+            .is_some_and(|(game_table, _)| game_table.all_slots_filled(previous.as_ref()))
+    }
+
+    /// Waits up to `startup_time` for the client bot `bot_name` to connect, ie. for all slots to be
+    /// filled. Fails early, if StarCraft or the bot exit in the meantime.
+    pub fn wait_for_all_slots_filled(
+        &mut self,
+        bot_name: &str,
+        startup_time: Duration,
+        bwapi_child: &mut Child,
+        child: &mut Child,
+    ) -> anyhow::Result<()> {
+        debug!(
+            "Waiting up to {}s for bot to take up slot...",
+            startup_time.as_secs()
+        );
+        let mut attempts = 0;
+        retry(Fixed::from_millis(100).take((startup_time.as_millis() / 100) as usize), || {
+            attempts += 1;
+            if attempts % 50 == 0 {
+                info!(
+                    "Waiting for '{}' to connect ({}s of {}s connect timeout)",
+                    bot_name,
+                    attempts / 10,
+                    startup_time.as_secs()
+                );
+            }
+            let slots_filled = self.all_slots_filled();
+            match bwapi_child.try_wait() {
+                Ok(None) => {
+                    // Ok, continue
+                },
+                Ok(Some(code)) => {
+                    error!("Starcraft died with: {}", code);
+                    return OperationResult::Err("BWAPI process died");
+                }
+                Err(e) => {
+                    error!("Error waiting for BWAPI process: {e}");
+                    return OperationResult::Err("Could not wait for BWAPI process");
+                }
+            }
+            match child.try_wait() {
+                Ok(None) => {
+                    // Ok, continue
+                },
+                Ok(Some(code)) => {
+                    error!("Bot process died with: {}", code);
+                    return OperationResult::Err("Bot process died")
+                }
+                Err(e) => {
+                    error!("Error waiting for bot process: {e}");
+                    return OperationResult::Err("Could not wait for bot process");
+                }
+            }
+            if slots_filled {
+                OperationResult::Ok(())
+            } else {
+                OperationResult::Retry(
+                    "Bot client executable did not connect to BWAPI server (did you try to run a human hosted game without hosting it?)",
+                )
+            }
+        })
+        .map_err(|err| anyhow!("{err}\nGame table history:\n{}", self.history()))
     }
 
     pub fn has_free_slot(&mut self) -> bool {
@@ -85,7 +255,11 @@ impl GameTableAccess {
     }
 }
 
-#[derive(Debug, Eq, PartialEq)]
+/// Replay path used if none is configured, relative to the bot folder
+pub const DEFAULT_REPLAY_PATH: &str =
+    "replays/$Y $b $d/%MAP%_%BOTRACE%%ALLYRACES%vs%ENEMYRACES%_$H$M$S.rep";
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
 pub enum BwapiVersion {
     Bwapi375,
     Bwapi412,
@@ -105,6 +279,11 @@ impl BwapiVersion {
         }
     }
 
+    /// BWAPI 3.7.x has no `seed_override`
+    pub fn supports_seed(&self) -> bool {
+        !matches!(self, Self::Bwapi375)
+    }
+
     pub fn version_short(&self) -> &'static str {
         match self {
             Self::Bwapi375 => "375",
@@ -118,7 +297,14 @@ impl BwapiVersion {
 pub enum BwapiConnectMode {
     Host {
         map: Option<String>,
-        player_count: usize,
+        min_players: usize,
+        max_players: usize,
+        /// How long to wait for more than `min_players`
+        lobby_grace: Duration,
+        /// Computer players, the host then plays a single player game against them
+        computer_races: Vec<Race>,
+        /// Players of the top and bottom force of a 'Top vs Bottom' game, melee otherwise
+        force_sizes: Option<(usize, usize)>,
     },
     Join,
 }
@@ -141,40 +327,239 @@ impl Default for AutoMenu {
     }
 }
 
+/// Typed `[config]` section of the bwapi.ini, configured per bot in 'bot.toml'
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct BwapiConfig {
+    /// Seasonal graphics (ie. santa hats)
+    pub holiday: bool,
+    /// Client bots (exe/jar) connect to BWAPI using shared memory
+    pub shared_memory: bool,
+    /// Let BWAPI restart automatically, client bots are also relaunched if they crash mid-game
+    pub auto_restart: bool,
+}
+
+impl Default for BwapiConfig {
+    fn default() -> Self {
+        Self {
+            holiday: false,
+            shared_memory: true,
+            auto_restart: false,
+        }
+    }
+}
+
+/// BWAPI reads the bwapi.ini with the ANSI API of Windows, which can't represent all characters.
+/// Paths with non-ASCII characters (ie. StarCraft in 'C:\Spiele\Übungen') are replaced by their
+/// 8.3 short path, which is pure ASCII - if short names are enabled on the drive.
+pub fn ini_path(path: &Path) -> String {
+    #[cfg(target_os = "windows")]
+    if !path.as_os_str().is_ascii() {
+        use std::os::windows::ffi::{OsStrExt, OsStringExt};
+        use windows_sys::Win32::Storage::FileSystem::GetShortPathNameW;
+
+        let long_path: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+        let mut short_path = vec![0u16; 1024];
+        let len = unsafe {
+            GetShortPathNameW(
+                long_path.as_ptr(),
+                short_path.as_mut_ptr(),
+                short_path.len() as u32,
+            )
+        } as usize;
+        if len > 0 && len < short_path.len() {
+            let short_path = std::ffi::OsString::from_wide(&short_path[..len]);
+            if short_path.is_ascii() {
+                debug!(
+                    "Using '{}' for '{}' in bwapi.ini",
+                    short_path.to_string_lossy(),
+                    path.display()
+                );
+                return short_path.to_string_lossy().to_string();
+            }
+        }
+        log::warn!(
+            "'{}' contains non-ASCII characters and might not be found by BWAPI, consider moving it",
+            path.display()
+        );
+    }
+    path.to_string_lossy().to_string()
+}
+
+/// Quotes paths with spaces (ie. 'C:\Program Files (x86)\StarCraft'). Windows strips the quotes
+/// again when BWAPI reads the ini, but would trim leading and trailing spaces without them.
+fn ini_quoted(value: &str) -> String {
+    if value.contains(' ') && !value.starts_with('"') {
+        format!("\"{value}\"")
+    } else {
+        value.to_string()
+    }
+}
+
+/// Fails early for paths which would only break once BWAPI (or wine) is running: Paths which are
+/// not valid Unicode can't be passed on without mangling them, and BWAPI 3.7.x reads its install
+/// path with the ANSI API.
+pub fn check_bwapi_path(
+    path: &Path,
+    what: &str,
+    bwapi_version: Option<BwapiVersion>,
+) -> anyhow::Result<()> {
+    let Some(path_str) = path.to_str() else {
+        bail!(
+            "The {what} '{}' is not valid Unicode and can't be passed to BWAPI, please rename it",
+            path.display()
+        );
+    };
+    ensure!(
+        bwapi_version != Some(BwapiVersion::Bwapi375) || path_str.is_ascii(),
+        "BWAPI 3.7.x can't handle the non-ASCII characters of the {what} '{path_str}', please move it to a path with only ASCII characters"
+    );
+    Ok(())
+}
+
+fn on_off(value: bool) -> &'static str {
+    if value {
+        "ON"
+    } else {
+        "OFF"
+    }
+}
+
+/// Sections of an ini file with their keys and values, in file order
+pub type IniSections = Vec<(String, Vec<(String, String)>)>;
+
+/// Simple ini parser: Empty lines and comments (';' or '#') are skipped, keys before the first
+/// section are not allowed
+pub fn parse_ini(content: &str) -> anyhow::Result<IniSections> {
+    let mut sections: IniSections = vec![];
+    for (number, line) in content
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.trim()))
+    {
+        if line.is_empty() || line.starts_with([';', '#']) {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[').and_then(|it| it.strip_suffix(']')) {
+            sections.push((name.trim().to_string(), vec![]));
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            bail!("Line {number} is neither a section nor a key: '{line}'");
+        };
+        let (_, keys) = sections
+            .last_mut()
+            .with_context(|| format!("Line {number} is outside of a section: '{line}'"))?;
+        keys.push((key.trim().to_string(), value.trim().to_string()));
+    }
+    Ok(sections)
+}
+
+/// Keys decided by BWAIShotgun, even if it doesn't write them (ie. `map` for joining bots)
+const MANAGED_KEYS: [&str; 5] = ["ai", "character_name", "race", "game", "map"];
+
+/// Adds the sections and keys of `extra`, keys already in `ini` and `MANAGED_KEYS` take precedence
+fn merge_ini(ini: &mut IniSections, extra: &IniSections) {
+    for (section, extra_keys) in extra {
+        let index = match ini.iter().position(|(name, _)| name == section) {
+            Some(index) => index,
+            None => {
+                ini.push((section.clone(), vec![]));
+                ini.len() - 1
+            }
+        };
+        let keys = &mut ini[index].1;
+        for (key, value) in extra_keys {
+            if MANAGED_KEYS.contains(&key.as_str())
+                || keys.iter().any(|(existing, _)| existing == key)
+            {
+                log::warn!("Ignoring '{key}' in [{section}] of 'extra_bwapi_ini', it is set by BWAIShotgun");
+            } else {
+                keys.push((key.clone(), value.clone()));
+            }
+        }
+    }
+}
+
 /// Although BWAPI can manage multiple bots with one BWAPI.ini, we'll be using one per bot
 #[derive(Default)]
 pub struct BwapiIni {
     pub ai_module: String,
     pub tm_module: Option<PathBuf>,
-    // default: 0 - full throttle
+    /// `speed_override`, 0 is full throttle (the fastest BWAPI allows), -1 uses the game's speed
     pub game_speed: i32,
     pub replay_path: Option<String>,
     pub sound: bool,
+    /// Drop players not responding in the lobby/game
+    pub drop_players: bool,
+    /// Fixed seed of StarCraft's RNG, not supported by BWAPI 3.7.x
+    pub seed: Option<u32>,
+    pub config: BwapiConfig,
     pub auto_menu: AutoMenu,
+    /// Additional sections and keys, see `extra_bwapi_ini`
+    pub extra: IniSections,
 }
 
 impl BwapiIni {
+    /// BWAPI 4.x reads the ini set in `BWAPI_CONFIG_INI`, so each game gets its own to not clobber
+    /// other runs. Older or unknown versions might only read 'bwapi-data/bwapi.ini'.
+    pub fn path(
+        bot_base_path: &Path,
+        bwapi_version: Option<BwapiVersion>,
+        game_id: &str,
+    ) -> PathBuf {
+        let file_name = match bwapi_version {
+            Some(BwapiVersion::Bwapi375) | None => "bwapi.ini".to_string(),
+            Some(_) => format!("bwapi_{game_id}.ini"),
+        };
+        bot_base_path.join("bwapi-data").join(file_name)
+    }
+
     pub fn from(bot_setup: &BotSetup) -> Self {
         Self {
             ai_module: match &bot_setup.bot_binary {
-                Binary::Dll(x) => x.to_string_lossy().to_string(),
+                Binary::Dll(x) => ini_path(x),
                 Binary::Exe(_) | Binary::Jar(_) => "".to_string(),
             },
             tm_module: bot_setup.tournament_module.clone(),
             replay_path: bot_setup.replay_path.clone(),
+            drop_players: true,
+            config: bot_setup.bwapi_config.clone(),
+            extra: bot_setup.extra_bwapi_ini.clone(),
             ..Default::default()
         }
     }
     pub fn write(&self, out: &mut impl Write) -> std::io::Result<()> {
+        if self.extra.is_empty() {
+            return self.write_managed(out);
+        }
+        let mut managed = vec![];
+        self.write_managed(&mut managed)?;
+        let mut sections = parse_ini(&String::from_utf8_lossy(&managed))
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err.to_string()))?;
+        merge_ini(&mut sections, &self.extra);
+        for (section, keys) in sections {
+            writeln!(out, "[{section}]")?;
+            for (key, value) in keys {
+                writeln!(out, "{key} = {value}")?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the keys set by BWAIShotgun
+    fn write_managed(&self, out: &mut impl Write) -> std::io::Result<()> {
         writeln!(out, "[ai]")?;
-        writeln!(out, "ai = {}", self.ai_module)?;
+        writeln!(out, "ai = {}", ini_quoted(&self.ai_module))?;
         if let Some(tm) = &self.tm_module {
-            writeln!(out, "tournament = {}", tm.to_string_lossy())?;
+            writeln!(out, "tournament = {}", ini_quoted(&ini_path(tm)))?;
         }
         writeln!(out, "[config]")?;
-        writeln!(out, "holiday = OFF")?;
+        writeln!(out, "holiday = {}", on_off(self.config.holiday))?;
+        writeln!(out, "shared_memory = {}", on_off(self.config.shared_memory))?;
 
         writeln!(out, "[auto_menu]")?;
+        writeln!(out, "auto_restart = {}", on_off(self.config.auto_restart))?;
         match &self.auto_menu {
             AutoMenu::Unused => (),
             AutoMenu::AutoMenu {
@@ -183,17 +568,44 @@ impl BwapiIni {
                 game_name,
                 connect_mode,
             } => {
-                writeln!(out, "auto_menu=LAN")?;
-                writeln!(out, "lan_mode=Local PC")?;
+                // BWAPI only adds computer players ('enemy_*') in single player games
+                let single_player = matches!(connect_mode, BwapiConnectMode::Host { computer_races, .. } if !computer_races.is_empty());
+                if single_player {
+                    writeln!(out, "auto_menu=SINGLE_PLAYER")?;
+                } else {
+                    writeln!(out, "auto_menu=LAN")?;
+                    writeln!(out, "lan_mode=Local PC")?;
+                }
                 writeln!(out, "character_name={name}")?;
                 writeln!(out, "race={race}")?;
                 match connect_mode {
-                    BwapiConnectMode::Host { map, player_count } => {
+                    BwapiConnectMode::Host {
+                        map,
+                        min_players,
+                        max_players,
+                        lobby_grace,
+                        computer_races,
+                        force_sizes,
+                    } => {
                         if let Some(map_name) = map {
                             writeln!(out, "map={map_name}")?;
                         }
-                        writeln!(out, "wait_for_min_players={player_count}")?;
-                        writeln!(out, "wait_for_max_players={player_count}")?;
+                        if let Some((top, bottom)) = force_sizes {
+                            writeln!(out, "game_type=TOP_VS_BOTTOM")?;
+                            writeln!(out, "game_type_extra={top} vs {bottom}")?;
+                        }
+                        if single_player {
+                            writeln!(out, "enemy_count={}", computer_races.len())?;
+                            for (slot, race) in computer_races.iter().enumerate() {
+                                writeln!(out, "enemy_race_{}={race}", slot + 1)?;
+                            }
+                        } else {
+                            writeln!(out, "wait_for_min_players={min_players}")?;
+                            writeln!(out, "wait_for_max_players={max_players}")?;
+                            if min_players < max_players {
+                                writeln!(out, "wait_for_time={}", lobby_grace.as_millis())?;
+                            }
+                        }
                     }
                     BwapiConnectMode::Join => {
                         writeln!(out, "game={game_name}")?;
@@ -207,21 +619,339 @@ impl BwapiIni {
             self.replay_path
                 .as_ref()
                 .map(|s| s.as_str())
-                .unwrap_or("replays/$Y $b $d/%MAP%_%BOTRACE%%ALLYRACES%vs%ENEMYRACES%_$H$M$S.rep")
+                .unwrap_or(DEFAULT_REPLAY_PATH)
         )?;
         writeln!(out, "[starcraft]")?;
         writeln!(out, "speed_override = {}", self.game_speed)?;
-        let sound = if self.sound { "ON" } else { "OFF" };
-        writeln!(out, "sound = {sound}")?;
-        writeln!(out, "drop_players = ON")
+        writeln!(out, "sound = {}", on_off(self.sound))?;
+        if let Some(seed) = self.seed {
+            writeln!(out, "seed_override = {seed}")?;
+        }
+        writeln!(out, "drop_players = {}", on_off(self.drop_players))
+    }
+
+    /// Writes the ini into memory and parses it again. Line breaks (ie. in a player name) would
+    /// produce broken or additional entries, which BWAPI silently ignores or misinterprets.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        let mut values = vec![
+            ("AI module", self.ai_module.clone()),
+            ("replay path", self.replay_path.clone().unwrap_or_default()),
+        ];
+        if let Some(tm) = &self.tm_module {
+            values.push(("tournament module", ini_path(tm)));
+        }
+        if let AutoMenu::AutoMenu {
+            name,
+            game_name,
+            connect_mode,
+            ..
+        } = &self.auto_menu
+        {
+            values.push(("player name", name.clone()));
+            values.push(("game name", game_name.clone()));
+            if let BwapiConnectMode::Host { map: Some(map), .. } = connect_mode {
+                values.push(("map", map.clone()));
+            }
+        }
+        for (what, value) in values {
+            ensure!(
+                !value.contains(['\n', '\r', '\0']),
+                "The {what} {value:?} contains characters which can't be written to bwapi.ini"
+            );
+        }
+
+        let mut out = vec![];
+        self.write(&mut out)?;
+        let ini = String::from_utf8(out).context("bwapi.ini is not valid UTF-8")?;
+        let mut sections = HashSet::new();
+        let mut keys = HashSet::new();
+        let mut section = None;
+        for (number, line) in ini.lines().enumerate().map(|(i, line)| (i + 1, line)) {
+            if let Some(name) = line.strip_prefix('[').and_then(|it| it.strip_suffix(']')) {
+                ensure!(
+                    sections.insert(name),
+                    "Line {number} of bwapi.ini repeats section [{name}]"
+                );
+                section = Some(name);
+                continue;
+            }
+            let Some((key, _)) = line.split_once('=') else {
+                bail!("Line {number} of bwapi.ini is neither a section nor a key: '{line}'");
+            };
+            let key = key.trim();
+            ensure!(
+                !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'),
+                "Line {number} of bwapi.ini has an invalid key: '{line}'"
+            );
+            let section = section
+                .with_context(|| format!("Line {number} of bwapi.ini is outside of a section"))?;
+            ensure!(
+                keys.insert((section, key)),
+                "Line {number} of bwapi.ini repeats '{key}' in [{section}]"
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Keeps copies of the bwapi.ini of each bot (see `copy_bwapi_ini_*`) and moves per game inis
+/// into the log folder of the bot when dropped, so it also happens if the game failed to launch.
+pub struct BwapiIniCleanup {
+    /// The ini and the log folder of each bot
+    inis: Vec<(PathBuf, PathBuf)>,
+    copy_always: bool,
+    copy_on_success: bool,
+    success: bool,
+}
+
+impl BwapiIniCleanup {
+    pub fn new(copy_always: bool, copy_on_success: bool) -> Self {
+        Self {
+            inis: vec![],
+            copy_always,
+            copy_on_success,
+            success: false,
+        }
+    }
+
+    /// Registers the ini before it is written, it is ignored if it doesn't exist when dropped
+    pub fn add(&mut self, bwapi_ini: PathBuf, log_dir: PathBuf) {
+        self.inis.push((bwapi_ini, log_dir));
+    }
+
+    /// The game ended without failures, unless this is called the game counts as failed
+    pub fn set_success(&mut self, success: bool) {
+        self.success = success;
+    }
+}
+
+impl Drop for BwapiIniCleanup {
+    fn drop(&mut self) {
+        for (bwapi_ini, log_dir) in self.inis.iter() {
+            // Bots added multiple times share their ini, it might be moved already
+            if !bwapi_ini.exists() {
+                continue;
+            }
+            let copies = [
+                (self.copy_always, "bwapi_last.ini"),
+                (
+                    self.copy_on_success && self.success,
+                    "bwapi_last_success.ini",
+                ),
+            ];
+            for (_, file_name) in copies.iter().filter(|(enabled, _)| *enabled) {
+                if let Err(err) = std::fs::copy(bwapi_ini, bwapi_ini.with_file_name(file_name)) {
+                    warn!("Could not copy '{}': {err}", bwapi_ini.display());
+                }
+            }
+            // The per game ini is only kept in the log folder
+            if bwapi_ini.file_name().is_some_and(|it| it != "bwapi.ini") {
+                if let Err(err) = std::fs::copy(bwapi_ini, log_dir.join("bwapi.ini"))
+                    .and_then(|_| std::fs::remove_file(bwapi_ini))
+                {
+                    warn!(
+                        "Could not move '{}' to the logs: {err}",
+                        bwapi_ini.display()
+                    );
+                }
+            }
+        }
     }
 }
 
 #[cfg(test)]
 mod test {
-    use crate::bwapi::BwapiVersion;
+    use crate::botsetup::BotSetup;
+    #[cfg(target_os = "windows")]
+    use crate::bwapi::ini_path;
     use crate::bwapi::BwapiVersion::{Bwapi375, Bwapi412, Bwapi420, Bwapi440};
+    use crate::bwapi::{
+        check_bwapi_path, parse_ini, AutoMenu, BwapiConnectMode, BwapiIni, BwapiIniCleanup,
+        BwapiVersion,
+    };
+    use crate::test_util::TempDir;
+    use crate::{Binary, Race};
     use crc::{Crc, CRC_32_ISO_HDLC};
+    use std::fs::{create_dir_all, write};
+    use std::path::Path;
+    use std::time::Duration;
+
+    #[test]
+    fn test_path() {
+        let base = Path::new("bots/Bot");
+        assert_eq!(
+            BwapiIni::path(base, Some(Bwapi440), "123"),
+            base.join("bwapi-data").join("bwapi_123.ini")
+        );
+        assert_eq!(
+            BwapiIni::path(base, Some(Bwapi375), "123"),
+            base.join("bwapi-data").join("bwapi.ini")
+        );
+        assert_eq!(
+            BwapiIni::path(base, None, "123"),
+            base.join("bwapi-data").join("bwapi.ini")
+        );
+    }
+
+    #[test]
+    fn test_seed() {
+        let mut out = vec![];
+        BwapiIni {
+            seed: Some(42),
+            ..Default::default()
+        }
+        .write(&mut out)
+        .unwrap();
+        assert!(String::from_utf8(out)
+            .unwrap()
+            .contains("seed_override = 42\n"));
+    }
+
+    #[test]
+    fn test_validate() {
+        let ini = |name: &str| BwapiIni {
+            auto_menu: AutoMenu::AutoMenu {
+                name: name.to_string(),
+                race: Race::Zerg,
+                game_name: "shotgun".to_string(),
+                connect_mode: BwapiConnectMode::Host {
+                    map: Some("maps/(2)Destination.scx".to_string()),
+                    min_players: 2,
+                    max_players: 2,
+                    lobby_grace: Duration::from_secs(30),
+                    computer_races: vec![],
+                    force_sizes: None,
+                },
+            },
+            seed: Some(42),
+            tm_module: Some("bots/Bot/TM_440.dll".into()),
+            ..Default::default()
+        };
+        ini("[Bot] = 1").validate().unwrap();
+        assert!(ini("Bot\nai = Evil.dll").validate().is_err());
+        assert!(BwapiIni::default().validate().is_ok());
+    }
+
+    #[test]
+    fn test_non_ascii_path() {
+        let bot_path = Path::new("bots").join("Bötchen_ß");
+        assert_eq!(
+            BwapiIni::path(&bot_path, Some(Bwapi440), "1"),
+            bot_path.join("bwapi-data").join("bwapi_1.ini")
+        );
+    }
+
+    /// Without the ANSI API limitation (ie. Wine on Linux), paths are written as they are
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    fn test_non_ascii_ini_path() {
+        let dll = Path::new("bots").join("Bötchen_ß").join("Bötchen.dll");
+        let mut bot_setup = BotSetup::for_test();
+        bot_setup.bot_binary = Binary::Dll(dll.clone());
+        let mut out = vec![];
+        BwapiIni::from(&bot_setup).write(&mut out).unwrap();
+        let ai = format!("ai = {}\n", dll.display());
+        assert!(String::from_utf8(out).unwrap().contains(&ai));
+    }
+
+    #[cfg(target_os = "windows")]
+    #[test]
+    fn test_non_ascii_ini_path() {
+        let folder = TempDir::new("Bötchen_ß");
+        let dll = folder.join("Bötchen.dll");
+        write(&dll, "dll").unwrap();
+
+        let path = ini_path(&dll);
+        // Short names can be disabled per drive, then the path is kept
+        if path != dll.to_string_lossy() {
+            assert!(path.is_ascii());
+            assert_eq!(
+                Path::new(&path).canonicalize().unwrap(),
+                dll.canonicalize().unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_paths_with_spaces() {
+        let home = TempDir::new("paths");
+        let starcraft = home.join("Program Files (x86)").join("StarCraft");
+        let bot_path = home.join("bots").join("Bötchen Zwei");
+        let ai = bot_path.join("bwapi-data").join("AI");
+        create_dir_all(&starcraft).unwrap();
+        create_dir_all(&ai).unwrap();
+        write(ai.join("Bötchen.dll"), "").unwrap();
+
+        let mut bot_setup = BotSetup::for_test();
+        bot_setup.bot_binary = Binary::Dll(ai.join("Bötchen.dll"));
+        bot_setup.tournament_module = Some(bot_path.join("TM 440.dll"));
+        let ini = BwapiIni::from(&bot_setup);
+        ini.validate().unwrap();
+        let mut out = vec![];
+        ini.write(&mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains(&format!("ai = \"{}\"\n", ai.join("Bötchen.dll").display())));
+        assert!(out.contains(&format!(
+            "tournament = \"{}\"\n",
+            bot_path.join("TM 440.dll").display()
+        )));
+
+        check_bwapi_path(&starcraft, "StarCraft folder", Some(Bwapi375)).unwrap();
+        check_bwapi_path(&bot_path, "bot folder", Some(Bwapi440)).unwrap();
+        check_bwapi_path(&bot_path, "bot folder", None).unwrap();
+        assert!(check_bwapi_path(&bot_path, "bot folder", Some(Bwapi375)).is_err());
+        #[cfg(unix)]
+        {
+            use std::os::unix::ffi::OsStrExt;
+            let not_unicode = Path::new(std::ffi::OsStr::from_bytes(b"bots/B\xf6tchen"));
+            assert!(check_bwapi_path(not_unicode, "bot folder", Some(Bwapi440)).is_err());
+        }
+    }
+
+    #[test]
+    fn test_extra_ini() {
+        let extra = parse_ini(
+            "; Custom settings
+[ai]
+ai = Evil.dll
+debug = ON
+
+[debug]
+log_level = 3
+[auto_menu]
+character_name = Evil
+map = evil.scx",
+        )
+        .unwrap();
+        let mut out = vec![];
+        BwapiIni {
+            ai_module: "bots/Bot/Bot.dll".to_string(),
+            auto_menu: AutoMenu::AutoMenu {
+                name: "Bot".to_string(),
+                race: Race::Protoss,
+                game_name: "shotgun".to_string(),
+                connect_mode: BwapiConnectMode::Join,
+            },
+            extra,
+            ..Default::default()
+        }
+        .write(&mut out)
+        .unwrap();
+        let ini = parse_ini(&String::from_utf8(out).unwrap()).unwrap();
+        let value = |section: &str, key: &str| {
+            ini.iter()
+                .find(|(name, _)| name == section)
+                .and_then(|(_, keys)| keys.iter().find(|(name, _)| name == key))
+                .map(|(_, value)| value.as_str())
+        };
+        assert_eq!(value("ai", "ai"), Some("bots/Bot/Bot.dll"));
+        assert_eq!(value("ai", "debug"), Some("ON"));
+        assert_eq!(value("debug", "log_level"), Some("3"));
+        assert_eq!(value("auto_menu", "character_name"), Some("Bot"));
+        // Would make a joining bot host
+        assert_eq!(value("auto_menu", "map"), None);
+        assert!(parse_ini("key = value").is_err());
+    }
 
     #[test]
     fn test_crc() {
@@ -258,4 +988,31 @@ mod test {
         );
         assert_eq!(BwapiVersion::from_u32(chksum), Some(Bwapi375));
     }
+
+    #[test]
+    fn test_bwapi_ini_cleanup() {
+        let folder = TempDir::new("bwapi-ini-cleanup");
+        create_dir_all(folder.join("bwapi-data")).unwrap();
+        create_dir_all(folder.join("logs")).unwrap();
+        let bwapi_ini = BwapiIni::path(&folder, Some(Bwapi440), "game");
+        write(&bwapi_ini, "[ai]").unwrap();
+
+        let mut cleanup = BwapiIniCleanup::new(true, true);
+        cleanup.add(bwapi_ini.clone(), folder.join("logs"));
+        // Registered, but never written
+        cleanup.add(
+            folder.join("bwapi-data").join("bwapi_other.ini"),
+            folder.join("logs"),
+        );
+        // Without `set_success`, ie. the launch failed
+        drop(cleanup);
+
+        assert!(!bwapi_ini.exists());
+        assert!(folder.join("logs").join("bwapi.ini").exists());
+        assert!(folder.join("bwapi-data").join("bwapi_last.ini").exists());
+        assert!(!folder
+            .join("bwapi-data")
+            .join("bwapi_last_success.ini")
+            .exists());
+    }
 }
@@ -0,0 +1,116 @@
+//! BWAPI 3.7.x reads its install path (and thereby the location of `bwapi-data`) from the
+//! registry. Stray values left behind by other launchers (ie. Chaoslauncher) can make it load
+//! a different bwapi.ini/AI module than the one we configured.
+#[cfg(target_os = "windows")]
+use anyhow::Context;
+#[cfg(target_os = "windows")]
+use log::{debug, warn};
+#[cfg(target_os = "windows")]
+use registry::{Data, Hive, Security};
+use std::path::{Path, PathBuf};
+
+#[cfg(target_os = "windows")]
+const STARCRAFT_KEY: &str = r"SOFTWARE\Blizzard Entertainment\Starcraft";
+#[cfg(target_os = "windows")]
+const INSTALL_PATH: &str = "InstallPath";
+
+/// A registry value BWAPI will read its install path from
+pub struct InstallPathEntry {
+    pub key: String,
+    pub value: PathBuf,
+}
+
+/// Lists all `InstallPath` values BWAPI 3.7.x might pick up
+pub fn install_path_entries() -> Vec<InstallPathEntry> {
+    #[cfg(target_os = "windows")]
+    {
+        [
+            (Hive::CurrentUser, "HKEY_CURRENT_USER"),
+            (Hive::LocalMachine, "HKEY_LOCAL_MACHINE"),
+        ]
+        .into_iter()
+        .filter_map(|(hive, hive_name)| {
+            let value = hive
+                // BWAPI is 32-bit, so it sees the 32-bit registry view
+                .open(STARCRAFT_KEY, Security::Read | Security::Wow6432Key)
+                .ok()?
+                .value(INSTALL_PATH)
+                .ok()?;
+            match value {
+                Data::String(value) | Data::ExpandString(value) => Some(InstallPathEntry {
+                    key: format!(r"{hive_name}\{STARCRAFT_KEY}\{INSTALL_PATH}"),
+                    value: PathBuf::from(value.to_os_string()),
+                }),
+                _ => None,
+            }
+        })
+        .collect()
+    }
+    #[cfg(not(target_os = "windows"))]
+    vec![]
+}
+
+/// Returns the entries not pointing to `expected`
+pub fn conflicting_install_paths(expected: &Path) -> Vec<InstallPathEntry> {
+    install_path_entries()
+        .into_iter()
+        .filter(|entry| entry.value != expected)
+        .collect()
+}
+
+/// Overrides the current users `InstallPath` as long as it is alive, the original value is
+/// restored when dropped.
+pub struct InstallPathOverride {
+    #[cfg(target_os = "windows")]
+    original: Option<Data>,
+}
+
+impl InstallPathOverride {
+    pub fn new(install_path: &Path) -> anyhow::Result<Self> {
+        #[cfg(target_os = "windows")]
+        {
+            let key = Hive::CurrentUser
+                .create(
+                    STARCRAFT_KEY,
+                    Security::Read | Security::Write | Security::Wow6432Key,
+                )
+                .context("Could not open StarCraft registry key")?;
+            let original = key.value(INSTALL_PATH).ok();
+            let value = utfx::U16CString::from_os_str(install_path.as_os_str())
+                .context("Invalid install path")?;
+            key.set_value(INSTALL_PATH, &Data::String(value))
+                .context("Could not override InstallPath")?;
+            debug!(
+                r"Set HKEY_CURRENT_USER\{STARCRAFT_KEY}\{INSTALL_PATH} to '{}'",
+                install_path.display()
+            );
+            Ok(Self { original })
+        }
+        #[cfg(not(target_os = "windows"))]
+        {
+            let _ = install_path;
+            anyhow::bail!("Only supported in Windows")
+        }
+    }
+}
+
+impl Drop for InstallPathOverride {
+    fn drop(&mut self) {
+        #[cfg(target_os = "windows")]
+        {
+            let restored = Hive::CurrentUser
+                .open(STARCRAFT_KEY, Security::Write | Security::Wow6432Key)
+                .map_err(anyhow::Error::from)
+                .and_then(|key| {
+                    match &self.original {
+                        Some(original) => key.set_value(INSTALL_PATH, original)?,
+                        None => key.delete_value(INSTALL_PATH)?,
+                    }
+                    Ok(())
+                });
+            if let Err(err) = restored {
+                warn!(r"Could not restore HKEY_CURRENT_USER\{STARCRAFT_KEY}\{INSTALL_PATH}: {err}");
+            }
+        }
+    }
+}
@@ -1,21 +1,130 @@
-use crate::botsetup::{BotSetup, LaunchBuilder};
-use crate::{tools_folder, BwapiIni, GameConfig};
-use anyhow::ensure;
+use crate::botsetup::{Binary, BotSetup, LaunchBuilder};
+use crate::probe::{probe, ProbeOutcome};
+use crate::role::BotRole;
+use crate::wrapper::ExecutionWrapper;
+use crate::{BwapiIni, GameConfig};
+use anyhow::{anyhow, ensure};
+use log::debug;
+use std::ffi::OsString;
 use std::fs::File;
+use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::time::Duration;
 
 pub enum BwHeadlessConnectMode {
-    Host { map: String, player_count: usize },
-    Join,
+    /// Host or join a game
+    Role(BotRole),
+    /// Play back the replay (relative to StarCraft) instead of a game
+    Replay { replay: PathBuf },
+}
+
+/// Argument conventions of the installed bwheadless, detected once per run from its `--help`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct BwHeadlessCapabilities {
+    /// Takes the BWAPI.dll with `--dll-path` instead of `-l`
+    pub dll_path: bool,
+}
+
+impl BwHeadlessCapabilities {
+    pub fn from_help(help: &str) -> Self {
+        Self {
+            dll_path: help.contains("--dll-path"),
+        }
+    }
+
+    /// Runs `bwheadless --help`, falls back to the conventions of the bundled bwheadless if that
+    /// fails
+    pub fn detect(bwheadless_exe: &Path, wrapper: &ExecutionWrapper) -> Self {
+        if !bwheadless_exe.exists() {
+            return Self::default();
+        }
+        let mut cmd = wrapper.wrap_executable(bwheadless_exe);
+        cmd.arg("--help");
+        let capabilities = match probe(cmd, Duration::from_secs(10), usize::MAX) {
+            Ok(result) if result.outcome != ProbeOutcome::Running => {
+                Self::from_help(&result.output.join("\n"))
+            }
+            Ok(_) => {
+                debug!("'bwheadless --help' did not exit, using the default arguments");
+                Self::default()
+            }
+            Err(err) => {
+                debug!("Could not run 'bwheadless --help': {err:?}");
+                Self::default()
+            }
+        };
+        debug!("bwheadless capabilities: {capabilities:?}");
+        capabilities
+    }
+
+    fn dll_arg(&self) -> &'static str {
+        if self.dll_path {
+            "--dll-path"
+        } else {
+            "-l"
+        }
+    }
 }
 
 pub struct BwHeadless {
     pub bot_setup: BotSetup,
-    pub game_name: Option<String>,
+    /// 'tools/bwheadless.exe', unless replaced by 'bwheadless_exe'
+    pub bwheadless_exe: PathBuf,
+    pub capabilities: BwHeadlessCapabilities,
     pub connect_mode: BwHeadlessConnectMode,
 }
 
+impl BwHeadless {
+    /// Arguments to host or join a game, or to play back a replay
+    fn connect_args(&self) -> anyhow::Result<Vec<OsString>> {
+        let role = match &self.connect_mode {
+            BwHeadlessConnectMode::Role(role) => role,
+            BwHeadlessConnectMode::Replay { replay } => {
+                return Ok(vec![
+                    "--replay".into(),
+                    self.bot_setup.starcraft_path.join(replay).into(),
+                ])
+            }
+        };
+        Ok(match role {
+            BotRole::Host {
+                game_name,
+                map,
+                max_players,
+                ..
+            } => {
+                let map = map
+                    .as_ref()
+                    .ok_or_else(|| anyhow!("bwheadless cannot host without a map"))?;
+                vec![
+                    "-g".into(),
+                    game_name.into(),
+                    "-m".into(),
+                    self.bot_setup.starcraft_path.join(map).into(),
+                    "-h".into(),
+                    // bwheadless can only wait for an exact number of players
+                    max_players.to_string().into(),
+                ]
+            }
+            BotRole::Join {
+                game_name: Some(game_name),
+            } => vec!["-g".into(), game_name.into()],
+            // Without a game name, bwheadless joins the first game
+            BotRole::Join { game_name: None } => vec![],
+        })
+    }
+}
+
 impl LaunchBuilder for BwHeadless {
+    fn estimated_startup_time(&self) -> Duration {
+        match self.bot_setup.bot_binary {
+            Binary::Dll(_) => Duration::from_secs(5),
+            Binary::Exe(_) => Duration::from_secs(10),
+            // The JVM needs some time to start up and JIT
+            Binary::Jar(_) => Duration::from_secs(30),
+        }
+    }
+
     fn build_command(&self, game_config: &GameConfig) -> anyhow::Result<Command> {
         ensure!(
             self.bot_setup.starcraft_exe.exists(),
@@ -34,36 +143,106 @@ impl LaunchBuilder for BwHeadless {
             bwapi_dll.to_string_lossy()
         );
 
-        let bwheadless = tools_folder().join("bwheadless.exe");
+        let bwheadless = &self.bwheadless_exe;
         ensure!(
             bwheadless.exists(),
             r"Could not find '{}'. Please make sure to extract all files, or check your antivirus software.",
-            tools_folder().to_string_lossy()
+            bwheadless.to_string_lossy()
         );
-        let bwapi_ini = bwapi_data.join("bwapi.ini");
-        let mut bwapi_ini_file = File::create(&bwapi_ini)?;
-        BwapiIni::from(&self.bot_setup).write(&mut bwapi_ini_file)?;
+        let bwapi_ini = &self.bot_setup.bwapi_ini;
+        let ini = BwapiIni {
+            drop_players: game_config.drop_players,
+            seed: game_config.random_seed,
+            ..BwapiIni::from(&self.bot_setup)
+        };
+        if self.bot_setup.validate_bwapi_ini {
+            ini.validate()?;
+        }
+        ini.write(&mut File::create(bwapi_ini)?)?;
 
         let mut cmd = self.bot_setup.wrapper.wrap_executable(bwheadless);
         cmd.arg("-e").arg(&self.bot_setup.starcraft_exe);
-        if let Some(game_name) = &self.game_name {
-            cmd.arg("-g").arg(game_name);
-        }
         cmd.arg("-r").arg(&self.bot_setup.race.to_string());
-        cmd.arg("-l").arg(bwapi_dll);
+        cmd.arg(self.capabilities.dll_arg()).arg(bwapi_dll);
         cmd.arg("--installpath").arg(&self.bot_setup.bot_base_path);
         cmd.arg("-n").arg(&self.bot_setup.player_name);
-        cmd.arg("-gs").arg(game_config.latency_frames.to_string());
+        cmd.arg("-gs")
+            .arg(game_config.effective_latency_frames().to_string());
         // Newer versions of BWAPI no longer use the registry key (aka installpath) - but allow overriding the bwapi_ini location.
-        cmd.env("BWAPI_CONFIG_INI", &*bwapi_ini.to_string_lossy());
+        cmd.env("BWAPI_CONFIG_INI", bwapi_ini);
         cmd.current_dir(&self.bot_setup.bot_base_path);
-        match &self.connect_mode {
-            BwHeadlessConnectMode::Host { map, player_count } => {
-                cmd.arg("-m").arg(self.bot_setup.starcraft_path.join(map));
-                cmd.arg("-h").arg(player_count.to_string());
-            }
-            BwHeadlessConnectMode::Join => {}
-        }
+        self.bot_setup.add_dll_search_path(&mut cmd)?;
+        cmd.args(self.connect_args()?);
         Ok(cmd)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::botsetup::BotSetup;
+    use crate::bwheadless::{BwHeadless, BwHeadlessCapabilities, BwHeadlessConnectMode};
+    use crate::role::BotRole;
+    use std::ffi::OsString;
+    use std::path::PathBuf;
+
+    fn connect_args(connect_mode: BwHeadlessConnectMode) -> Vec<OsString> {
+        BwHeadless {
+            bot_setup: BotSetup::for_test(),
+            bwheadless_exe: PathBuf::from("tools/bwheadless.exe"),
+            capabilities: BwHeadlessCapabilities::default(),
+            connect_mode,
+        }
+        .connect_args()
+        .unwrap()
+    }
+
+    #[test]
+    fn test_connect_args() {
+        let host = connect_args(BwHeadlessConnectMode::Role(BotRole::Host {
+            game_name: "shotgun".to_string(),
+            map: Some("maps/map.scx".to_string()),
+            min_players: 2,
+            max_players: 3,
+        }));
+        assert_eq!(host[..2], ["-g", "shotgun"]);
+        assert_eq!(host[4..], ["-h", "3"]);
+        assert_eq!(
+            connect_args(BwHeadlessConnectMode::Role(BotRole::Join {
+                game_name: Some("shotgun".to_string())
+            })),
+            ["-g", "shotgun"]
+        );
+        assert!(connect_args(BwHeadlessConnectMode::Role(BotRole::Join {
+            game_name: None
+        }))
+        .is_empty());
+        let replay = connect_args(BwHeadlessConnectMode::Replay {
+            replay: PathBuf::from("maps/replays/game.rep"),
+        });
+        assert_eq!(replay[0], "--replay");
+        assert!(PathBuf::from(&replay[1]).ends_with("maps/replays/game.rep"));
+    }
+
+    #[test]
+    fn test_capabilities() {
+        // Excerpt of the '--help' of 'tools/bwheadless.exe'
+        let help = "Usage: bwheadless.exe [option]...
+  -e,  --exe         The exe file to launch. Default 'StarCraft.exe'.
+  -h,  --host <n>    Host a game with the given number of players instead of joining.
+  -j,  --join        Join instead of hosting. The first game that is found
+                    will be joined.
+  -l,  --dll DLL     Load DLL into StarCraft. This option can be
+                    specified multiple times to load multiple dlls.
+       --lan         Sets the network provider to LAN (UDP).
+       --installpath PATH  Overrides the InstallPath value that would usually
+                          be read from the registry. This is used by BWAPI to
+                          locate bwapi-data/bwapi.ini.";
+        let bundled = BwHeadlessCapabilities::from_help(help);
+        assert_eq!(bundled, BwHeadlessCapabilities::default());
+        assert_eq!(bundled.dll_arg(), "-l");
+        let newer = BwHeadlessCapabilities::from_help(
+            &help.replace("-l,  --dll DLL  ", "     --dll-path PATH"),
+        );
+        assert_eq!(newer.dll_arg(), "--dll-path");
+    }
+}
@@ -1,5 +1,7 @@
-use crate::{BotLaunchConfig, GameConfig, HeadfulMode};
+use crate::tournament_module::TournamentModule;
+use crate::{BotLaunchConfig, GameConfig};
 use clap::{error::ErrorKind, Parser, Subcommand};
+use std::path::PathBuf;
 
 #[derive(Subcommand, Debug)]
 enum GameType {
@@ -13,10 +15,42 @@ enum GameType {
         /// Names of bots to play
         bots: Vec<String>,
     },
+    /// Show the detected StarCraft installation and configuration, without running a game
+    Info,
+    /// Print the environment fingerprint added to results (ie. for bug reports)
+    Env,
+    /// Start exe/jar bots outside of a game, to check that they can run at all
+    Probe {
+        /// Names of bots to probe
+        #[arg(required = true)]
+        bots: Vec<String>,
+    },
+    /// Write a commented 'game.toml' and optionally a 'bot.toml' for a new bot
+    Init {
+        /// Also create 'bots/<BOT>/bot.toml'
+        bot: Option<String>,
+        /// Race of the new bot
+        #[arg(long, requires = "bot")]
+        race: Option<String>,
+        /// Overwrite existing files
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+/// Arguments of the 'init' command
+pub struct InitArgs<'a> {
+    pub bot: Option<&'a str>,
+    pub race: Option<&'a str>,
+    pub force: bool,
 }
 
 #[derive(Parser, Debug)]
 pub struct Cli {
+    /// BWAIShotgun folder with the configuration, bots and tools (default: BWAISHOTGUN_HOME or
+    /// the folder of the executable)
+    #[arg(long)]
+    pub home: Option<PathBuf>,
     /// Absolute path of map to host
     #[arg(short, long)]
     map: Option<String>,
@@ -27,6 +61,55 @@ pub struct Cli {
     /// Folder/File name to use for replays
     #[arg(long)]
     replay_path: Option<String>,
+    /// Temporarily override the StarCraft 'InstallPath' registry value for old (3.7.x) headful bots
+    #[arg(long)]
+    pub fix_registry: bool,
+    /// Don't print the summary table at the end of a game
+    #[arg(short, long)]
+    pub quiet: bool,
+    /// Print the game results as JSON instead of the summary table
+    #[arg(long)]
+    pub json: bool,
+    /// Don't wait for confirmation of pre-flight warnings, see 'confirm_warnings' in 'shotgun.toml'
+    #[arg(short, long)]
+    pub yes: bool,
+    /// Tournament module for all bots of this game: "none", "default" or a custom prefix
+    #[arg(long = "tm", value_name = "TM", conflicts_with = "no_tm")]
+    tournament_module: Option<String>,
+    /// Run all bots without tournament module, same as '--tm none'
+    #[arg(long)]
+    no_tm: bool,
+    /// After the game, zip the logs and TM results of all bots into this folder
+    #[arg(long, value_name = "FOLDER")]
+    pub archive_logs: Option<PathBuf>,
+    /// After the game, append a row with the result to this CSV file (created with a header). Overrides
+    /// 'result_file' and 'result_format' in 'shotgun.toml'
+    #[arg(long, value_name = "FILE")]
+    pub results_csv: Option<PathBuf>,
+    /// Use this bwheadless.exe instead of the one in 'tools', see 'bwheadless_exe' in 'shotgun.toml'
+    #[arg(long, value_name = "FILE")]
+    pub bwheadless_path: Option<PathBuf>,
+    /// Use this injectory_x86.exe instead of the one in 'tools', see 'injectory_exe' in 'shotgun.toml'
+    #[arg(long, value_name = "FILE")]
+    pub injectory_path: Option<PathBuf>,
+    /// Additional command line argument for an exe/jar bot, can be repeated
+    #[arg(long = "bot-arg", value_name = "BOT:ARG")]
+    bot_args: Vec<String>,
+    /// Additional command line arguments for an exe/jar bot, split at whitespace (without quoting,
+    /// use '--bot-arg' for arguments containing spaces), can be repeated
+    #[arg(long = "bot-args", value_name = "'BOT ARGS...'")]
+    bot_arg_lists: Vec<String>,
+    /// Run the games listed in this file ('.toml' with '[[games]]' or '.jsonl' with one game per
+    /// line) instead of 'game.toml', one after the other
+    #[arg(long, value_name = "FILE", conflicts_with = "map")]
+    pub manifest: Option<PathBuf>,
+    /// Milliseconds to wait after starting StarCraft for a bot, see 'bot_launch_pause_ms' in
+    /// 'game.toml'
+    #[arg(long, value_name = "MS")]
+    launch_pause: Option<u32>,
+    /// Continue with the next game of the '--manifest' if a game fails
+    #[arg(long, requires = "manifest")]
+    pub continue_on_error: bool,
 }
 
 pub enum Error {
@@ -34,38 +117,161 @@ pub enum Error {
 }
 
 impl Cli {
-    pub fn merge_into(self, mut config: GameConfig) -> Result<GameConfig, Error> {
+    pub fn is_info(&self) -> bool {
+        matches!(self.game_type, Some(GameType::Info))
+    }
+
+    pub fn is_env(&self) -> bool {
+        matches!(self.game_type, Some(GameType::Env))
+    }
+
+    pub fn probe_bots(&self) -> Option<&[String]> {
+        match &self.game_type {
+            Some(GameType::Probe { bots }) => Some(bots),
+            _ => None,
+        }
+    }
+
+    pub fn init_args(&self) -> Option<InitArgs<'_>> {
+        match &self.game_type {
+            Some(GameType::Init { bot, race, force }) => Some(InitArgs {
+                bot: bot.as_deref(),
+                race: race.as_deref(),
+                force: *force,
+            }),
+            _ => None,
+        }
+    }
+
+    pub fn merge_into(
+        &self,
+        mut config: GameConfig<crate::GameType>,
+    ) -> Result<GameConfig<crate::GameType>, Error> {
         if self.map.is_some() != self.game_type.is_some() {
             Err(Error::ClapError(clap::Error::raw(
                 ErrorKind::DisplayHelpOnMissingArgumentOrSubcommand,
                 "Map and game type must either be both set, or not at all. Use '-h' to get help.\n",
             )))
         } else {
-            if let Some(game_type) = self.game_type {
+            if let Some(game_type) = &self.game_type {
                 config.human_host = matches!(game_type, GameType::Human { .. });
                 config.game_type = match game_type {
                     GameType::Melee { bots } | GameType::Human { bots } => crate::GameType::Melee(
                         bots.iter()
                             .map(|name| BotLaunchConfig {
                                 name: name.to_string(),
-                                player_name: None,
-                                race: None,
-                                headful: HeadfulMode::Off,
+                                ..Default::default()
                             })
                             .collect(),
                     ),
+                    GameType::Info
+                    | GameType::Env
+                    | GameType::Probe { .. }
+                    | GameType::Init { .. } => {
+                        unreachable!("'info', 'env', 'probe' and 'init' don't run a game")
+                    }
                 };
             }
-            if let Some(map) = self.map {
-                config.map = Some(map);
+            if let Some(map) = &self.map {
+                config.map = Some(map.clone());
             }
             if let Some(human_speed) = self.human_speed {
                 config.human_speed = human_speed;
             }
-            if let Some(replay_path) = self.replay_path {
-                config.replay_path = Some(replay_path);
+            if let Some(launch_pause) = self.launch_pause {
+                config.bot_launch_pause_ms = launch_pause;
+            }
+            if let Some(replay_path) = &self.replay_path {
+                config.replay_path = Some(replay_path.clone());
+            }
+            if self.no_tm {
+                config.tournament_module = Some(TournamentModule::None);
+            } else if let Some(tournament_module) = &self.tournament_module {
+                config.tournament_module = Some(TournamentModule::from_name(tournament_module));
+            }
+            let mut bot_args = vec![];
+            for bot_arg in &self.bot_args {
+                let Some((bot, arg)) = bot_arg.split_once(':') else {
+                    return Err(Error::ClapError(clap::Error::raw(
+                        ErrorKind::InvalidValue,
+                        format!("'--bot-arg {bot_arg}' must be in the form 'BOT:ARG'\n"),
+                    )));
+                };
+                bot_args.push((format!("--bot-arg {bot_arg}"), bot, vec![arg]));
+            }
+            for bot_arg_list in &self.bot_arg_lists {
+                let mut words = bot_arg_list.split_whitespace();
+                let (Some(bot), args) = (words.next(), words.collect::<Vec<_>>()) else {
+                    return Err(Error::ClapError(clap::Error::raw(
+                        ErrorKind::InvalidValue,
+                        "'--bot-args' must be in the form 'BOT ARGS...'\n",
+                    )));
+                };
+                bot_args.push((format!("--bot-args {bot_arg_list}"), bot, args));
+            }
+            for (option, bot, args) in bot_args {
+                let bots = config.game_type.bots_mut();
+                let mut bots = bots.into_iter().filter(|it| it.name == bot).peekable();
+                if bots.peek().is_none() {
+                    return Err(Error::ClapError(clap::Error::raw(
+                        ErrorKind::InvalidValue,
+                        format!("'{option}': '{bot}' is not part of the game\n"),
+                    )));
+                }
+                for launch_config in bots {
+                    launch_config
+                        .args
+                        .extend(args.iter().map(|it| it.to_string()));
+                }
             }
             Ok(config)
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::cli::Cli;
+    use crate::{GameConfig, GameType};
+    use clap::{CommandFactory, Parser};
+
+    #[test]
+    fn test_cli() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn test_bot_args() {
+        let merged = |args: &[&str]| {
+            let config: GameConfig<GameType> =
+                toml::from_str("game_type = { Melee = [{ name = 'A' }, { name = 'B' }] }").unwrap();
+            Cli::try_parse_from(["bwaishotgun"].iter().chain(args))
+                .unwrap()
+                .merge_into(config)
+                .ok()
+        };
+        let config = merged(&[
+            "--bot-args",
+            "A --speed  fast",
+            "--bot-arg",
+            "A:--name=My Bot",
+            "--bot-arg",
+            "B:--debug",
+        ])
+        .unwrap();
+        let args: Vec<_> = config
+            .game_type
+            .bots()
+            .iter()
+            .map(|it| it.args.clone())
+            .collect();
+        // '--bot-arg' comes first, it keeps spaces
+        assert_eq!(
+            args,
+            [vec!["--name=My Bot", "--speed", "fast"], vec!["--debug"]]
+        );
+        assert!(merged(&["--bot-args", " "]).is_none());
+        assert!(merged(&["--bot-args", "C --debug"]).is_none());
+        assert!(merged(&["--bot-arg", "A"]).is_none());
+    }
+}
@@ -0,0 +1,156 @@
+//! Failed BWAPI assertions and crashes of StarCraft can show a modal dialog, which blocks the game
+//! until someone clicks it away. Headless games would hang until a timeout catches them.
+
+/// Titles of crash dialogs: The C runtime (failed assertions, `abort`), Windows itself (unhandled
+/// exceptions) and StarCraft's own fatal error dialog
+#[cfg_attr(not(target_os = "windows"), allow(dead_code))]
+const CRASH_TITLES: [&str; 3] = [
+    "Microsoft Visual C++ Runtime Library",
+    "Application Error",
+    "Fatal Error",
+];
+
+/// Texts of crash dialogs, BWAPI and some bots show their assertion failures with other titles
+#[cfg_attr(not(target_os = "windows"), allow(dead_code))]
+const CRASH_TEXTS: [&str; 6] = [
+    "Assertion failed",
+    "Debug Assertion Failed",
+    "Runtime Error",
+    "Debug Error",
+    "This application has encountered a critical error",
+    "Unhandled exception",
+];
+
+/// A dialog blocking StarCraft
+#[derive(Debug)]
+pub struct CrashDialog {
+    pub process_id: u32,
+    pub title: String,
+}
+
+/// Other dialogs (ie. StarCraft's own message boxes) must not end the game
+#[cfg_attr(not(target_os = "windows"), allow(dead_code))]
+fn is_crash_dialog(title: &str, text: &str) -> bool {
+    CRASH_TITLES.iter().any(|it| title.contains(it))
+        || CRASH_TEXTS.iter().any(|it| text.contains(it))
+}
+
+/// Finds a visible crash dialog (see `is_crash_dialog`) of the given launcher process (ie.
+/// bwheadless or injectory) or its direct child processes (StarCraft). Outside of Windows, dialogs
+/// can't be detected.
+pub fn find_crash_dialog(launcher_process_id: u32) -> Option<CrashDialog> {
+    #[cfg(target_os = "windows")]
+    {
+        use crate::window_title::launched_process_ids;
+        use windows_sys::Win32::Foundation::{BOOL, HWND, LPARAM};
+        use windows_sys::Win32::UI::WindowsAndMessaging::{
+            EnumWindows, GetClassNameW, GetDlgItemTextW, GetWindowTextW, GetWindowThreadProcessId,
+            IsWindowVisible,
+        };
+
+        /// Window class of message boxes, including the assertion dialogs of BWAPI and the CRT
+        const DIALOG_CLASS: &str = "#32770";
+        /// Control id of the text of a message box
+        const MESSAGE_BOX_TEXT: i32 = 0xffff;
+
+        struct Search {
+            process_ids: Vec<u32>,
+            found: Option<CrashDialog>,
+        }
+
+        unsafe extern "system" fn find_dialog(window: HWND, search: LPARAM) -> BOOL {
+            let search = &mut *(search as *mut Search);
+            let mut process_id = 0;
+            GetWindowThreadProcessId(window, &mut process_id);
+            if !search.process_ids.contains(&process_id) || IsWindowVisible(window) == 0 {
+                return 1;
+            }
+            let mut class = [0u16; 64];
+            let length = GetClassNameW(window, class.as_mut_ptr(), class.len() as i32);
+            if String::from_utf16_lossy(&class[..length.max(0) as usize]) != DIALOG_CLASS {
+                return 1;
+            }
+            let mut title = [0u16; 256];
+            let length = GetWindowTextW(window, title.as_mut_ptr(), title.len() as i32);
+            let title = String::from_utf16_lossy(&title[..length.max(0) as usize]);
+            let mut text = [0u16; 1024];
+            let length = GetDlgItemTextW(
+                window,
+                MESSAGE_BOX_TEXT,
+                text.as_mut_ptr(),
+                text.len() as i32,
+            );
+            let text = String::from_utf16_lossy(&text[..length as usize]);
+            if !is_crash_dialog(&title, &text) {
+                log::debug!("Ignoring dialog '{title}' of process {process_id}: {text}");
+                return 1;
+            }
+            search.found = Some(CrashDialog { process_id, title });
+            // Stop enumerating
+            0
+        }
+
+        let mut search = Search {
+            process_ids: launched_process_ids(launcher_process_id)?,
+            found: None,
+        };
+        unsafe {
+            EnumWindows(Some(find_dialog), &mut search as *mut Search as LPARAM);
+        }
+        search.found
+    }
+    #[cfg(not(target_os = "windows"))]
+    {
+        let _ = launcher_process_id;
+        None
+    }
+}
+
+/// Kills the process showing the dialog, the launcher exits with it
+pub fn kill(dialog: &CrashDialog) -> bool {
+    #[cfg(target_os = "windows")]
+    {
+        use windows_sys::Win32::Foundation::CloseHandle;
+        use windows_sys::Win32::System::Threading::{
+            OpenProcess, TerminateProcess, PROCESS_TERMINATE,
+        };
+
+        unsafe {
+            let process = OpenProcess(PROCESS_TERMINATE, 0, dialog.process_id);
+            if process == 0 {
+                return false;
+            }
+            let killed = TerminateProcess(process, 1) != 0;
+            CloseHandle(process);
+            killed
+        }
+    }
+    #[cfg(not(target_os = "windows"))]
+    {
+        let _ = dialog;
+        false
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::crash_dialog::is_crash_dialog;
+
+    #[test]
+    fn test_is_crash_dialog() {
+        assert!(is_crash_dialog(
+            "Microsoft Visual C++ Runtime Library",
+            "Assertion failed!\n\nProgram: C:\\StarCraft\\StarCraft.exe"
+        ));
+        assert!(is_crash_dialog(
+            "StarCraft.exe - Application Error",
+            "The instruction at 0x1000abcd referenced memory at 0x00000000."
+        ));
+        assert!(is_crash_dialog("BWAPI", "Unhandled exception in BWAPI.dll"));
+        assert!(!is_crash_dialog(
+            "Brood War",
+            "Unable to connect to the network."
+        ));
+        assert!(!is_crash_dialog("", ""));
+    }
+}
@@ -0,0 +1,193 @@
+//! Keeps the evidence if StarCraft crashes (usually inside a bot's dll): Windows Error Reporting
+//! writes dumps of the crashed processes while the game runs, under Wine the crash backtrace is
+//! taken from the game log instead.
+use std::fs::{create_dir_all, read_dir, read_to_string, rename, write};
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+#[cfg(target_os = "windows")]
+use anyhow::Context;
+#[cfg(target_os = "windows")]
+use log::{debug, warn};
+#[cfg(target_os = "windows")]
+use registry::{Data, Hive, Security};
+
+#[cfg(target_os = "windows")]
+const LOCAL_DUMPS_KEY: &str = r"SOFTWARE\Microsoft\Windows\Windows Error Reporting\LocalDumps";
+/// StarCraft runs inside of bwheadless for headless bots
+#[cfg(target_os = "windows")]
+const DUMPED_EXECUTABLES: [&str; 3] = ["StarCraft.exe", "bwheadless.exe", "injectory_x86.exe"];
+/// Dumps can get large: Only minidumps are written, and at most this many per executable
+#[cfg(target_os = "windows")]
+const MAX_DUMPS: u32 = 5;
+/// Lines of a Wine backtrace kept
+const MAX_BACKTRACE_LINES: usize = 200;
+
+/// How crashes of client bot processes (exiting with a non-zero code) are handled
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct CrashDetectionConfig {
+    /// Report bots exiting with a non-zero code while StarCraft runs as failed
+    pub detect: bool,
+    /// Let Windows Error Reporting write a minidump, if an exe bot crashes. WER writes it while
+    /// the process crashes, BWAIShotgun only notices the exit code once the process is gone -
+    /// too late for `MiniDumpWriteDump`. Requires writing the LocalDumps keys of HKLM.
+    pub create_dump: bool,
+    /// Folder of the dumps (relative to BWAIShotgun), defaults to 'crash_dumps'
+    pub dump_dir: Option<PathBuf>,
+}
+
+/// Registers the processes with WER LocalDumps as long as it is alive, the registry keys created
+/// are removed when dropped. Existing keys are left as they are. `bot_executables` are registered
+/// in addition to StarCraft and its launchers.
+pub struct LocalDumps {
+    dump_folder: PathBuf,
+    #[cfg(target_os = "windows")]
+    created_keys: Vec<String>,
+}
+
+impl LocalDumps {
+    pub fn new(dump_folder: PathBuf, bot_executables: &[String]) -> anyhow::Result<Self> {
+        create_dir_all(&dump_folder)?;
+        #[cfg(target_os = "windows")]
+        {
+            let mut local_dumps = Self {
+                dump_folder,
+                created_keys: vec![],
+            };
+            let dump_folder = utfx::U16CString::from_os_str(local_dumps.dump_folder.as_os_str())
+                .context("Invalid dump folder")?;
+            for exe in DUMPED_EXECUTABLES
+                .iter()
+                .copied()
+                .chain(bot_executables.iter().map(String::as_str))
+            {
+                let key_path = format!(r"{LOCAL_DUMPS_KEY}\{exe}");
+                if Hive::LocalMachine
+                    .open(key_path.as_str(), Security::Read)
+                    .is_ok()
+                {
+                    debug!("Keeping existing crash dump settings of '{exe}'");
+                    continue;
+                }
+                let key = Hive::LocalMachine
+                    .create(key_path.as_str(), Security::Read | Security::Write)
+                    .context(
+                        "Could not register crash dump collection (BWAIShotgun must run as administrator)",
+                    )?;
+                local_dumps.created_keys.push(key_path);
+                key.set_value("DumpFolder", &Data::ExpandString(dump_folder.clone()))?;
+                // Minidump
+                key.set_value("DumpType", &Data::U32(1))?;
+                key.set_value("DumpCount", &Data::U32(MAX_DUMPS))?;
+            }
+            Ok(local_dumps)
+        }
+        #[cfg(not(target_os = "windows"))]
+        {
+            let _ = bot_executables;
+            Ok(Self { dump_folder })
+        }
+    }
+
+    /// Moves the dumps of the process with the given id to `target_folder`
+    pub fn collect(&self, process_id: u32, target_folder: &Path) -> anyhow::Result<Vec<PathBuf>> {
+        // WER names dumps '<exe>.<pid>.dmp'
+        let suffix = format!(".{process_id}.dmp");
+        let mut collected = vec![];
+        for dump in self.dumps()? {
+            if dump
+                .file_name()
+                .is_some_and(|name| name.to_string_lossy().ends_with(&suffix))
+            {
+                let target = target_folder.join(dump.file_name().expect("Dump without name"));
+                rename(&dump, &target)?;
+                collected.push(target);
+            }
+        }
+        Ok(collected)
+    }
+
+    /// Dumps not collected (ie. of StarCraft processes launched by injectory)
+    pub fn dumps(&self) -> anyhow::Result<Vec<PathBuf>> {
+        Ok(read_dir(&self.dump_folder)?
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "dmp"))
+            .collect())
+    }
+
+    pub fn dump_folder(&self) -> &Path {
+        &self.dump_folder
+    }
+}
+
+impl Drop for LocalDumps {
+    fn drop(&mut self) {
+        #[cfg(target_os = "windows")]
+        for key_path in self.created_keys.iter() {
+            if let Err(err) = Hive::LocalMachine.delete(key_path.as_str(), true) {
+                warn!(r"Could not remove HKEY_LOCAL_MACHINE\{key_path}: {err}");
+            }
+        }
+    }
+}
+
+/// Extracts the backtrace Wine prints to stderr if a process crashes, and writes it to
+/// 'crash_backtrace.txt' next to the log
+pub fn collect_wine_backtrace(game_err_log: &Path) -> anyhow::Result<Option<PathBuf>> {
+    let Ok(log) = read_to_string(game_err_log) else {
+        return Ok(None);
+    };
+    let Some(backtrace) = wine_backtrace(&log) else {
+        return Ok(None);
+    };
+    let target = game_err_log.with_file_name("crash_backtrace.txt");
+    write(&target, backtrace)?;
+    Ok(Some(target))
+}
+
+fn wine_backtrace(log: &str) -> Option<String> {
+    let lines: Vec<_> = log.lines().collect();
+    let start = lines.iter().position(|line| {
+        line.contains("Unhandled exception") || line.contains("Unhandled page fault")
+    })?;
+    Some(
+        lines[start..]
+            .iter()
+            .take(MAX_BACKTRACE_LINES)
+            .map(|line| format!("{line}\n"))
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use crate::crash_dumps::{wine_backtrace, CrashDetectionConfig};
+
+    #[test]
+    fn test_crash_detection_config() {
+        assert!(!CrashDetectionConfig::default().detect);
+        let config: CrashDetectionConfig =
+            toml::from_str("detect = true\ncreate_dump = true").unwrap();
+        assert!(config.detect);
+        assert!(config.create_dump);
+        assert_eq!(config.dump_dir, None);
+    }
+
+    #[test]
+    fn test_wine_backtrace() {
+        let log = "fixme:ntdll:NtQuerySystemInformation\n\
+        wine: Unhandled page fault on read access to 00000000 at address 1000ABCD (thread 0024)\n\
+        Backtrace:\n\
+        =>0 0x1000abcd in bot (+0xabcd)\n";
+        let backtrace = wine_backtrace(log).unwrap();
+        assert!(backtrace.starts_with("wine: Unhandled page fault"));
+        assert!(backtrace.contains("Backtrace:"));
+        assert_eq!(
+            wine_backtrace("fixme:ntdll:NtQuerySystemInformation\n"),
+            None
+        );
+    }
+}
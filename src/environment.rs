@@ -0,0 +1,253 @@
+//! A fingerprint of the machine games run on, to tell apart results of different setups (ie. a
+//! slower CPU or another Wine version). Collecting it never fails, unknown values are left out.
+use std::fs::File;
+use std::io::copy;
+use std::path::Path;
+use std::process::Command;
+
+use log::info;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::tools::tools_sha256;
+use crate::wrapper::ExecutionWrapper;
+
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct Environment {
+    pub shotgun_version: String,
+    pub os: String,
+    pub os_version: Option<String>,
+    pub cpu: Option<String>,
+    pub cores: Option<usize>,
+    pub total_memory_mb: Option<u64>,
+    /// StarCraft runs in Wine, either as wrapper or because BWAIShotgun itself runs in it
+    pub wine: bool,
+    pub wine_version: Option<String>,
+    pub starcraft_sha256: Option<String>,
+    /// See `tools_sha256`
+    pub tools_sha256: String,
+}
+
+impl Environment {
+    pub fn collect(
+        starcraft_path: Option<&Path>,
+        tools_dir: &Path,
+        wrapper: &ExecutionWrapper,
+    ) -> Self {
+        let wine_version = if matches!(wrapper, ExecutionWrapper::Wine) {
+            command_output("wine", "--version")
+        } else if runs_in_wine() {
+            wine_get_version()
+        } else {
+            None
+        };
+        Self {
+            shotgun_version: env!("CARGO_PKG_VERSION").to_string(),
+            os: std::env::consts::OS.to_string(),
+            os_version: os_version(),
+            cpu: cpu(),
+            cores: std::thread::available_parallelism().ok().map(|it| it.get()),
+            total_memory_mb: total_memory_mb(),
+            wine: matches!(wrapper, ExecutionWrapper::Wine) || runs_in_wine(),
+            wine_version,
+            starcraft_sha256: starcraft_path.and_then(|it| file_sha256(&it.join("StarCraft.exe"))),
+            tools_sha256: tools_sha256(tools_dir),
+        }
+    }
+
+    pub fn print(&self) {
+        let unknown = |value: Option<String>| value.unwrap_or_else(|| "unknown".to_string());
+        info!("BWAIShotgun: {}", self.shotgun_version);
+        info!("OS: {} {}", self.os, unknown(self.os_version.clone()));
+        info!("CPU: {}", unknown(self.cpu.clone()));
+        info!("Cores: {}", unknown(self.cores.map(|it| it.to_string())));
+        info!(
+            "Memory: {}",
+            unknown(self.total_memory_mb.map(|it| format!("{it} MB")))
+        );
+        info!(
+            "Wine: {}",
+            match (self.wine, &self.wine_version) {
+                (false, _) => "no".to_string(),
+                (true, version) => unknown(version.clone()),
+            }
+        );
+        info!(
+            "StarCraft.exe SHA-256: {}",
+            unknown(self.starcraft_sha256.clone())
+        );
+        info!("Tools SHA-256: {}", self.tools_sha256);
+    }
+}
+
+fn command_output(program: &str, arg: &str) -> Option<String> {
+    let output = Command::new(program).arg(arg).output().ok()?;
+    let output = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (!output.is_empty()).then_some(output)
+}
+
+fn file_sha256(path: &Path) -> Option<String> {
+    let mut file = File::open(path).ok()?;
+    let mut hasher = Sha256::new();
+    copy(&mut file, &mut hasher).ok()?;
+    Some(format!("{:x}", hasher.finalize()))
+}
+
+/// The value of the first line starting with `key`, ie. from `/proc/cpuinfo` or `/etc/os-release`
+#[cfg(any(not(target_os = "windows"), test))]
+fn find_value(content: &str, key: &str, separator: char) -> Option<String> {
+    content
+        .lines()
+        .filter_map(|line| line.split_once(separator))
+        .find(|(name, _)| name.trim() == key)
+        .map(|(_, value)| value.trim().trim_matches('"').to_string())
+        .filter(|it| !it.is_empty())
+}
+
+#[cfg(not(target_os = "windows"))]
+fn os_version() -> Option<String> {
+    let name = std::fs::read_to_string("/etc/os-release")
+        .ok()
+        .and_then(|it| find_value(&it, "PRETTY_NAME", '='));
+    let kernel = std::fs::read_to_string("/proc/sys/kernel/osrelease")
+        .ok()
+        .map(|it| it.trim().to_string());
+    match (name, kernel) {
+        (Some(name), Some(kernel)) => Some(format!("{name} (kernel {kernel})")),
+        (name, kernel) => name.or(kernel),
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn os_version() -> Option<String> {
+    use registry::{Data, Hive, Security};
+
+    let key = Hive::LocalMachine
+        .open(
+            r"SOFTWARE\Microsoft\Windows NT\CurrentVersion",
+            Security::Read,
+        )
+        .ok()?;
+    let value = |name: &str| match key.value(name).ok()? {
+        Data::String(value) => Some(value.to_string_lossy()),
+        _ => None,
+    };
+    match (value("ProductName"), value("CurrentBuildNumber")) {
+        (Some(name), Some(build)) => Some(format!("{name} (build {build})")),
+        (name, build) => name.or(build),
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+fn cpu() -> Option<String> {
+    find_value(
+        &std::fs::read_to_string("/proc/cpuinfo").ok()?,
+        "model name",
+        ':',
+    )
+}
+
+#[cfg(target_os = "windows")]
+fn cpu() -> Option<String> {
+    std::env::var("PROCESSOR_IDENTIFIER").ok()
+}
+
+#[cfg(not(target_os = "windows"))]
+fn total_memory_mb() -> Option<u64> {
+    parse_mem_total(&std::fs::read_to_string("/proc/meminfo").ok()?)
+}
+
+/// `MemTotal` of `/proc/meminfo` in MB
+#[cfg(not(target_os = "windows"))]
+fn parse_mem_total(meminfo: &str) -> Option<u64> {
+    let kb: u64 = find_value(meminfo, "MemTotal", ':')?
+        .trim_end_matches("kB")
+        .trim()
+        .parse()
+        .ok()?;
+    Some(kb / 1024)
+}
+
+#[cfg(target_os = "windows")]
+fn total_memory_mb() -> Option<u64> {
+    use windows_sys::Win32::System::SystemInformation::{GlobalMemoryStatusEx, MEMORYSTATUSEX};
+
+    let mut status = MEMORYSTATUSEX {
+        dwLength: std::mem::size_of::<MEMORYSTATUSEX>() as u32,
+        ..unsafe { std::mem::zeroed() }
+    };
+    (unsafe { GlobalMemoryStatusEx(&mut status) } != 0).then_some(status.ullTotalPhys / 1024 / 1024)
+}
+
+/// Wine adds its own registry key
+fn runs_in_wine() -> bool {
+    #[cfg(target_os = "windows")]
+    {
+        registry::Hive::LocalMachine
+            .open(r"Software\Wine", registry::Security::Read)
+            .is_ok()
+    }
+    #[cfg(not(target_os = "windows"))]
+    {
+        false
+    }
+}
+
+/// Asks the ntdll of Wine for its version, formatted like `wine --version` (ie. 'wine-9.0')
+fn wine_get_version() -> Option<String> {
+    #[cfg(target_os = "windows")]
+    {
+        use std::ffi::{c_char, CStr};
+        use windows_sys::Win32::System::LibraryLoader::{GetModuleHandleA, GetProcAddress};
+
+        let ntdll = unsafe { GetModuleHandleA(c"ntdll.dll".as_ptr().cast()) };
+        if ntdll == 0 {
+            return None;
+        }
+        // Only Wine's ntdll exports it
+        let wine_get_version =
+            unsafe { GetProcAddress(ntdll, c"wine_get_version".as_ptr().cast()) }?;
+        let wine_get_version: unsafe extern "C" fn() -> *const c_char =
+            unsafe { std::mem::transmute(wine_get_version) };
+        let version = unsafe { wine_get_version() };
+        (!version.is_null()).then(|| {
+            format!(
+                "wine-{}",
+                unsafe { CStr::from_ptr(version) }.to_string_lossy()
+            )
+        })
+    }
+    #[cfg(not(target_os = "windows"))]
+    {
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::environment::find_value;
+
+    #[test]
+    fn test_find_value() {
+        let os_release = "NAME=\"Ubuntu\"\nPRETTY_NAME=\"Ubuntu 22.04.3 LTS\"\n";
+        assert_eq!(
+            find_value(os_release, "PRETTY_NAME", '=').as_deref(),
+            Some("Ubuntu 22.04.3 LTS")
+        );
+        let cpuinfo = "processor\t: 0\nmodel name\t: AMD Ryzen 7 5800X 8-Core Processor\n";
+        assert_eq!(
+            find_value(cpuinfo, "model name", ':').as_deref(),
+            Some("AMD Ryzen 7 5800X 8-Core Processor")
+        );
+        assert_eq!(find_value(cpuinfo, "flags", ':'), None);
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    fn test_parse_mem_total() {
+        assert_eq!(
+            crate::environment::parse_mem_total("MemTotal:       16315588 kB\nMemFree: 1 kB\n"),
+            Some(15933)
+        );
+    }
+}
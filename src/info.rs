@@ -0,0 +1,105 @@
+use log::{info, warn};
+
+use crate::bot_scan::scan_bot_folder;
+use crate::java_setup::java_component;
+use crate::paths::Paths;
+use crate::starcraft_setup::{snp_support, starcraft_component, SnpSupport};
+use crate::tournament_module::available_modules;
+use crate::vcredist::{VC_2013, VC_2015};
+use crate::wrapper::ExecutionWrapper;
+use crate::ShotgunConfig;
+
+/// Prints how BWAIShotgun resolved its configuration, without downloading or launching anything.
+pub fn print_info(config: &ShotgunConfig, paths: &Paths) {
+    info!("Base folder: '{}'", paths.home.display());
+    info!("Config folder: '{}'", paths.config_dir.display());
+    info!("Bots folder: '{}'", paths.bots_dir.display());
+    info!("Tools folder: '{}'", paths.tools_dir.display());
+    let tournament_modules = available_modules(&paths.tm_dir);
+    if tournament_modules.is_empty() {
+        info!(
+            "Tournament modules: none found in '{}'",
+            paths.tm_dir.display()
+        );
+    }
+    for (prefix, versions) in tournament_modules {
+        info!(
+            "Tournament module prefix '{prefix}': BWAPI {}",
+            versions.join(", ")
+        );
+    }
+    info!("Download folder: '{}'", paths.downloads_dir.display());
+
+    let starcraft = starcraft_component(
+        paths,
+        config.starcraft_path.clone(),
+        config.download_connections,
+    );
+    info!("StarCraft configuration: {:?}", config.starcraft_path);
+    match starcraft.installed_path() {
+        Some(starcraft_path) => {
+            info!("StarCraft path: '{}'", starcraft_path.display());
+            let starcraft_exe = starcraft_path.join("StarCraft.exe");
+            info!(
+                "StarCraft executable: '{}'{}",
+                starcraft_exe.display(),
+                if starcraft_exe.exists() {
+                    ""
+                } else {
+                    " (missing)"
+                }
+            );
+            info!(
+                "SNP_DirectIP.snp: {}",
+                match snp_support(&starcraft_path) {
+                    SnpSupport::Full => "supports 8 bots per game",
+                    SnpSupport::Limited => "might not support more than ~6 bots per game",
+                    SnpSupport::Missing => "missing",
+                }
+            );
+            // Wine resolves the Visual C++ runtime differently, it can't be checked the same way
+            if !matches!(config.wrapper, ExecutionWrapper::Wine) {
+                for runtime in [&VC_2013, &VC_2015] {
+                    if runtime.is_installed(&starcraft_path) {
+                        info!("{}: installed", runtime.name);
+                    } else {
+                        info!(
+                            "{}: missing (required for BWAPI 4.x bots), install it from {}",
+                            runtime.name, runtime.download_url
+                        );
+                    }
+                }
+            }
+        }
+        None => info!("StarCraft path: not found (will be installed on the first run)"),
+    }
+
+    let java = java_component(paths, config.java_path.clone(), config.download_connections);
+    info!("Java configuration: {:?}", config.java_path);
+    match java.installed_path() {
+        Some(java_path) => info!("Java path: '{}'", java_path.display()),
+        None => info!("Java path: not found (will be installed when running a java bot)"),
+    }
+    info!("Wrapper: {:?}", config.wrapper);
+
+    let mut bot_folders: Vec<_> = paths
+        .bots_dir
+        .read_dir()
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|it| it.path())
+        .filter(|it| it.is_dir())
+        .collect();
+    bot_folders.sort();
+    for bot_folder in bot_folders {
+        for file in scan_bot_folder(&bot_folder) {
+            warn!(
+                "Found '{}': {} - {}",
+                bot_folder.join(&file.path).display(),
+                file.reason,
+                file.action
+            );
+        }
+    }
+}
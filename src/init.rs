@@ -0,0 +1,105 @@
+//! Writes starter configuration files. The templates are the commented 'game.toml' and
+//! 'bots/template/bot.toml' shipped with BWAIShotgun, tests make sure they match the schemas.
+use std::fs::{create_dir_all, write};
+
+use anyhow::{ensure, Context};
+use log::info;
+
+use crate::paths::Paths;
+use crate::BotDefinition;
+
+const GAME_TOML_TEMPLATE: &str = include_str!("../game.toml");
+const BOT_TOML_TEMPLATE: &str = include_str!("../bots/template/bot.toml");
+
+/// The bot.toml template, with the race line uncommented if `race` is given
+fn bot_toml(race: Option<&str>) -> anyhow::Result<String> {
+    let Some(race) = race else {
+        return Ok(BOT_TOML_TEMPLATE.to_string());
+    };
+    let bot_toml = BOT_TOML_TEMPLATE.replacen(
+        "#race=\"Terran\"",
+        &format!("race=\"{race}\"\n#race=\"Terran\""),
+        1,
+    );
+    toml::from_str::<BotDefinition>(&bot_toml)
+        .with_context(|| format!("'{race}' is not a valid race"))?;
+    Ok(bot_toml)
+}
+
+/// Writes 'game.toml' and, if `bot` is given, 'bots/<bot>/bot.toml' with an empty
+/// 'bwapi-data/AI' folder for the bot binary. Existing files are only overwritten with `force`.
+pub fn write_templates(
+    paths: &Paths,
+    bot: Option<&str>,
+    race: Option<&str>,
+    force: bool,
+) -> anyhow::Result<()> {
+    let mut files = vec![(
+        paths.config_dir.join("game.toml"),
+        GAME_TOML_TEMPLATE.to_string(),
+    )];
+    if let Some(bot) = bot {
+        files.push((paths.bots_dir.join(bot).join("bot.toml"), bot_toml(race)?));
+    }
+    let existing: Vec<_> = files
+        .iter()
+        .filter(|(path, _)| path.exists())
+        .map(|(path, _)| format!("'{}'", path.display()))
+        .collect();
+    ensure!(
+        force || existing.is_empty(),
+        "Not overwriting {}, use '--force' to overwrite",
+        existing.join(", ")
+    );
+    for (path, content) in files {
+        if let Some(folder) = path.parent() {
+            create_dir_all(folder)?;
+        }
+        write(&path, content).with_context(|| format!("Could not write '{}'", path.display()))?;
+        info!("Wrote '{}'", path.display());
+    }
+    if let Some(bot) = bot {
+        let ai_folder = paths.bots_dir.join(bot).join("bwapi-data").join("AI");
+        create_dir_all(&ai_folder)?;
+        info!(
+            "Copy the bot binary and BWAPI.dll of '{bot}' to '{}' and '{}'",
+            ai_folder.display(),
+            ai_folder.with_file_name("BWAPI.dll").display()
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::init::{bot_toml, write_templates, GAME_TOML_TEMPLATE};
+    use crate::paths::Paths;
+    use crate::test_util::TempDir;
+    use crate::{BotDefinition, GameConfig, GameType, Race};
+    use std::fs::{create_dir_all, read_to_string};
+
+    #[test]
+    fn test_templates_match_schema() {
+        toml::from_str::<GameConfig<GameType>>(GAME_TOML_TEMPLATE).unwrap();
+        let definition = toml::from_str::<BotDefinition>(&bot_toml(Some("Zerg")).unwrap()).unwrap();
+        assert_eq!(definition.race, Race::Zerg);
+        assert!(bot_toml(Some("Elves")).is_err());
+    }
+
+    #[test]
+    fn test_write_templates() {
+        let home = TempDir::new("init");
+        create_dir_all(home.join("bots")).unwrap();
+        let paths = Paths::with_home(home.to_path_buf());
+
+        write_templates(&paths, Some("MyBot"), Some("Protoss"), false).unwrap();
+        assert!(
+            read_to_string(home.join("bots").join("MyBot").join("bot.toml"))
+                .unwrap()
+                .contains("race=\"Protoss\"")
+        );
+        assert!(home.join("bots/MyBot/bwapi-data/AI").is_dir());
+        assert!(write_templates(&paths, None, None, false).is_err());
+        write_templates(&paths, None, None, true).unwrap();
+    }
+}
@@ -1,30 +1,58 @@
+use std::fmt::{Display, Formatter};
 use std::fs::{copy, create_dir_all, File};
+use std::path::{Component, Path, PathBuf};
 use std::process::Command;
+use std::time::Duration;
 
-use anyhow::ensure;
+use anyhow::{ensure, Context};
+use log::{debug, info};
 
 use crate::botsetup::{BotSetup, LaunchBuilder};
-use crate::{tools_folder, AutoMenu, BwapiConnectMode, BwapiIni, GameConfig};
-
-pub enum InjectoryConnectMode {
-    Host {
-        map: Option<String>,
-        player_count: usize,
-    },
-    Join,
+use crate::bwapi::ini_path;
+use crate::role::BotRole;
+use crate::{AutoMenu, BwapiConnectMode, BwapiIni, BwapiVersion, GameConfig};
+
+/// How the hosting BWAPI (which looks for maps relative to the bot folder) gets access to the map
+enum MapAccess {
+    /// Newer versions of BWAPI accept an absolute map path
+    AbsolutePath,
+    /// The bot folder links to the maps folder of StarCraft
+    Link(PathBuf),
+    /// Fallback: The map is copied into the bot folder (see `MAP_COPY_FOLDER`)
+    Copy(PathBuf),
+}
+
+impl Display for MapAccess {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MapAccess::AbsolutePath => write!(f, "absolute map path"),
+            MapAccess::Link(link) => write!(f, "linked maps folder '{}'", link.display()),
+            MapAccess::Copy(map) => write!(f, "copied map to '{}'", map.display()),
+        }
+    }
 }
 
+/// Older BWAPI versions only find maps relative to the bot folder (the working dir of StarCraft),
+/// copies are kept in this subfolder to not clutter the bot folder.
+const MAP_COPY_FOLDER: &str = "bwapi-data/tmp_maps";
+
 pub struct Injectory {
     pub bot_setup: BotSetup,
-    pub game_name: String,
-    pub connect_mode: InjectoryConnectMode,
+    pub role: BotRole,
     pub wmode: bool,
     pub sound: bool,
     pub game_speed: i32,
+    pub wait_for_exit: bool,
+    pub kill_on_exit: bool,
+    /// Injected before BWAPI.dll: Replaces the install path old BWAPI versions read from the
+    /// registry with `BWAISHOTGUN_INSTALLPATH` (the bot folder)
+    pub oldbwapi_dll: PathBuf,
+    /// 'tools/injectory_x86.exe', unless replaced by 'injectory_exe'
+    pub injectory_exe: PathBuf,
 }
 
 impl LaunchBuilder for Injectory {
-    fn build_command(&self, _game_config: &GameConfig) -> anyhow::Result<Command> {
+    fn build_command(&self, game_config: &GameConfig) -> anyhow::Result<Command> {
         ensure!(
             self.bot_setup.starcraft_exe.exists(),
             "Could not find 'StarCraft.exe'"
@@ -41,69 +69,327 @@ impl LaunchBuilder for Injectory {
             "Could not find '{}'",
             bwapi_dll.to_string_lossy()
         );
-        let injectory = tools_folder().join("injectory_x86.exe");
+        let tools_dir = &self.bot_setup.tools_dir;
+        let injectory = &self.injectory_exe;
         ensure!(
             injectory.exists(),
             r"Could not find '{}'. Please make sure to extract all files, or check your antivirus software.",
-            tools_folder().to_string_lossy()
+            injectory.to_string_lossy()
         );
-        let bwapi_ini = bwapi_data.join("bwapi.ini");
-        let mut bwapi_ini_file = File::create(&bwapi_ini)?;
-        BwapiIni {
-            auto_menu: match &self.connect_mode {
-                InjectoryConnectMode::Host { map, player_count } => AutoMenu::AutoMenu {
-                    name: self.bot_setup.player_name.clone(),
-                    game_name: self.game_name.clone(),
-                    race: self.bot_setup.race,
-                    connect_mode: BwapiConnectMode::Host {
-                        map: map.clone(),
-                        player_count: *player_count,
-                    },
-                },
-                InjectoryConnectMode::Join => AutoMenu::AutoMenu {
-                    name: self.bot_setup.player_name.clone(),
-                    game_name: self.game_name.clone(),
-                    race: self.bot_setup.race,
-                    connect_mode: BwapiConnectMode::Join,
-                },
-            },
-            game_speed: self.game_speed,
-            sound: self.sound,
-            tm_module: self.bot_setup.tournament_module.clone(),
-            ..BwapiIni::from(&self.bot_setup)
-        }
-        .write(&mut bwapi_ini_file)?;
-
-        // BWAPI will look for the map in the "bot" folder, not in the starcraft path, so we'll copy the map over.
-        // We really need to copy, because it will open the map to check for settings.
-        // One caveat: BWAPI does not allow game speed selection, so this might host with an invalid game speed
-        if let InjectoryConnectMode::Host { map: Some(map), .. } = &self.connect_mode {
-            let original_map = self.bot_setup.starcraft_path.join(map);
-            ensure!(
-                original_map.exists(),
-                "Map '{}' does not exist",
-                original_map.to_string_lossy()
-            );
-            let tmp_map = self.bot_setup.bot_base_path.join(map);
-            create_dir_all(tmp_map.parent().expect("Map file has no parent directory"))?;
-            copy(original_map, tmp_map)?;
+        ensure!(
+            self.oldbwapi_dll.exists(),
+            "Could not find '{}'",
+            self.oldbwapi_dll.to_string_lossy()
+        );
+        let host_map = match &self.role {
+            BotRole::Host { map: Some(map), .. } => Some(self.provide_map(map)?),
+            _ => None,
+        };
+
+        let bwapi_ini = &self.bot_setup.bwapi_ini;
+        let ini = self.bwapi_ini(host_map, game_config);
+        if self.bot_setup.validate_bwapi_ini {
+            ini.validate()?;
         }
+        ini.write(&mut File::create(bwapi_ini)?)?;
 
         let mut cmd = self.bot_setup.wrapper.wrap_executable(injectory);
         cmd.arg("-l").arg(&self.bot_setup.starcraft_exe);
-        cmd.arg("-i")
-            .args([tools_folder().join("oldbwapi.dll"), bwapi_dll]);
+        cmd.arg("-i").args([&self.oldbwapi_dll, &bwapi_dll]);
         if self.wmode {
-            cmd.arg(tools_folder().join("WMode.dll"));
+            cmd.arg(tools_dir.join("WMode.dll"));
+        }
+        if self.wait_for_exit {
+            cmd.arg("--wait-for-exit");
+        }
+        if self.kill_on_exit {
+            cmd.arg("--kill-on-exit");
         }
-        cmd.arg("--wait-for-exit").arg("--kill-on-exit");
         // Newer versions of BWAPI no longer use the registry key (aka installpath) - but allow overriding the bwapi_ini location.
         // Note that injectory does NOT do any registry trickery (bwheadless does) - so old bots (< 4.x) will most likely not work.
-        cmd.env("BWAPI_CONFIG_INI", &*bwapi_ini.to_string_lossy());
+        cmd.env("BWAPI_CONFIG_INI", bwapi_ini);
 
         // Old versions of BWAPI need a hack: We replace the value returned from the registry query with this path:
         cmd.env("BWAISHOTGUN_INSTALLPATH", &self.bot_setup.bot_base_path);
         cmd.current_dir(&self.bot_setup.bot_base_path);
+        self.bot_setup.add_dll_search_path(&mut cmd)?;
         Ok(cmd)
     }
 }
+
+impl Injectory {
+    fn bwapi_ini(&self, host_map: Option<String>, game_config: &GameConfig) -> BwapiIni {
+        BwapiIni {
+            auto_menu: AutoMenu::AutoMenu {
+                name: self.bot_setup.player_name.clone(),
+                race: self.bot_setup.race,
+                game_name: match &self.role {
+                    BotRole::Host { game_name, .. } => game_name.clone(),
+                    BotRole::Join { game_name } => game_name
+                        .clone()
+                        .unwrap_or_else(|| "JOIN_FIRST".to_string()),
+                },
+                connect_mode: match &self.role {
+                    BotRole::Host {
+                        min_players,
+                        max_players,
+                        ..
+                    } => BwapiConnectMode::Host {
+                        map: host_map,
+                        min_players: *min_players,
+                        max_players: *max_players,
+                        lobby_grace: Duration::from_secs(game_config.lobby_grace_secs),
+                        computer_races: game_config.computer_races.clone(),
+                        force_sizes: game_config.game_type.force_sizes(),
+                    },
+                    BotRole::Join { .. } => BwapiConnectMode::Join,
+                },
+            },
+            game_speed: self.game_speed,
+            sound: self.sound,
+            drop_players: game_config.drop_players,
+            seed: game_config.random_seed,
+            tm_module: self.bot_setup.tournament_module.clone(),
+            ..BwapiIni::from(&self.bot_setup)
+        }
+    }
+
+    /// Makes the map available to BWAPI, returns the map path to use in the bwapi.ini
+    fn provide_map(&self, map: &str) -> anyhow::Result<String> {
+        let original_map = self.bot_setup.starcraft_path.join(map);
+        ensure!(
+            original_map.exists(),
+            "Map '{}' does not exist",
+            original_map.to_string_lossy()
+        );
+        let (map_access, map_path) = if matches!(
+            self.bot_setup.bwapi_version,
+            Some(BwapiVersion::Bwapi412 | BwapiVersion::Bwapi420 | BwapiVersion::Bwapi440)
+        ) {
+            (MapAccess::AbsolutePath, ini_path(&original_map))
+        } else if let Some(link) = self.link_maps_folder(map) {
+            (MapAccess::Link(link), map.to_string())
+        } else {
+            // We really need to copy, because it will open the map to check for settings.
+            // One caveat: BWAPI does not allow game speed selection, so this might host with an invalid game speed
+            let map_file_name = original_map
+                .file_name()
+                .context("Map path has no file name")?
+                .to_string_lossy();
+            let map_path = format!("{MAP_COPY_FOLDER}/{map_file_name}");
+            let tmp_map = self.bot_setup.bot_base_path.join(&map_path);
+            create_dir_all(tmp_map.parent().expect("Map file has no parent directory"))?;
+            copy(&original_map, &tmp_map)?;
+            (MapAccess::Copy(tmp_map), map_path)
+        };
+        info!(
+            "Providing map to '{}': {}",
+            self.bot_setup.player_name, map_access
+        );
+        Ok(map_path)
+    }
+
+    /// Links the top level folder of the map (ie. 'maps') into the bot folder
+    fn link_maps_folder(&self, map: &str) -> Option<PathBuf> {
+        let Some(Component::Normal(maps_folder)) = Path::new(map).components().next() else {
+            return None;
+        };
+        if Path::new(map).components().count() < 2 {
+            return None;
+        }
+        let link = self.bot_setup.bot_base_path.join(maps_folder);
+        let target = self.bot_setup.starcraft_path.join(maps_folder);
+        if let Ok(metadata) = link.symlink_metadata() {
+            // Reuse a link from an earlier run, but never touch an actual folder
+            return metadata.file_type().is_symlink().then_some(link);
+        }
+        match create_link(&target, &link) {
+            Ok(()) => Some(link),
+            Err(err) => {
+                debug!("Could not link '{}': {err}", link.display());
+                None
+            }
+        }
+    }
+}
+
+fn create_link(target: &Path, link: &Path) -> anyhow::Result<()> {
+    #[cfg(target_os = "windows")]
+    {
+        // Junctions don't require any special privileges, in contrast to symlinks
+        let status = Command::new("cmd")
+            .arg("/C")
+            .arg("mklink")
+            .arg("/J")
+            .arg(link)
+            .arg(target)
+            .stdout(std::process::Stdio::null())
+            .status()
+            .context("Could not run 'mklink'")?;
+        ensure!(status.success(), "'mklink' failed with {status}");
+        Ok(())
+    }
+    #[cfg(not(target_os = "windows"))]
+    std::os::unix::fs::symlink(target, link).context("Could not create symlink")
+}
+
+/// Removes map copies created for hosting
+pub fn remove_map_copies(bot_base_path: &Path) -> anyhow::Result<()> {
+    let map_copy_folder = bot_base_path.join(MAP_COPY_FOLDER);
+    if map_copy_folder.exists() {
+        debug!("Removing map copies in '{}'", map_copy_folder.display());
+        std::fs::remove_dir_all(map_copy_folder)?;
+    }
+    Ok(())
+}
+
+/// Removes a maps folder link created for hosting
+pub fn remove_map_link(bot_base_path: &Path, map: &str) -> anyhow::Result<()> {
+    let Some(Component::Normal(maps_folder)) = Path::new(map).components().next() else {
+        return Ok(());
+    };
+    let link = bot_base_path.join(maps_folder);
+    if link
+        .symlink_metadata()
+        .is_ok_and(|it| it.file_type().is_symlink())
+    {
+        debug!("Removing map link '{}'", link.display());
+        #[cfg(target_os = "windows")]
+        std::fs::remove_dir(&link)?;
+        #[cfg(not(target_os = "windows"))]
+        std::fs::remove_file(&link)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::botsetup::BotSetup;
+    use crate::injectory::Injectory;
+    use crate::role::BotRole;
+    use crate::{GameConfig, GameType};
+    use std::path::{Path, PathBuf};
+
+    fn game_config(toml: &str) -> GameConfig {
+        toml::from_str::<GameConfig<GameType>>(toml)
+            .unwrap()
+            .validate_game_type(Path::new("starcraft"))
+            .unwrap()
+    }
+
+    fn bwapi_ini(role: BotRole, host_map: Option<String>) -> String {
+        bwapi_ini_for(
+            role,
+            host_map,
+            &game_config("game_type = { Melee = [{ name = 'A' }] }"),
+        )
+    }
+
+    fn bwapi_ini_for(role: BotRole, host_map: Option<String>, game_config: &GameConfig) -> String {
+        let injectory = Injectory {
+            bot_setup: BotSetup::for_test(),
+            role,
+            wmode: true,
+            sound: false,
+            game_speed: 0,
+            wait_for_exit: true,
+            kill_on_exit: true,
+            oldbwapi_dll: PathBuf::from("tools/oldbwapi.dll"),
+            injectory_exe: PathBuf::from("tools/injectory_x86.exe"),
+        };
+        let mut out = vec![];
+        injectory
+            .bwapi_ini(host_map, game_config)
+            .write(&mut out)
+            .unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_bwapi_ini_host() {
+        let ini = bwapi_ini(
+            BotRole::Host {
+                game_name: "Bot".to_string(),
+                map: Some("maps/map.scx".to_string()),
+                min_players: 3,
+                max_players: 3,
+            },
+            Some("maps/map.scx".to_string()),
+        );
+        assert!(ini.contains("map=maps/map.scx\n"));
+        assert!(ini.contains("wait_for_min_players=3\n"));
+        assert!(!ini.contains("wait_for_time"));
+        assert!(!ini.contains("game="));
+        assert!(!ini.contains("enemy_count"));
+    }
+
+    #[test]
+    fn test_bwapi_ini_host_computers() {
+        let mut game_config = game_config(
+            "game_type = { Melee = [{ name = 'A' }] }\ncomputer_races = [\"Zerg\", \"Random\"]",
+        );
+        let host = BotRole::Host {
+            game_name: "Bot".to_string(),
+            map: Some("maps/map.scx".to_string()),
+            min_players: 1,
+            max_players: 1,
+        };
+        let ini = bwapi_ini_for(host.clone(), Some("maps/map.scx".to_string()), &game_config);
+        assert!(ini.contains("auto_menu=SINGLE_PLAYER\n"));
+        assert!(ini.contains("enemy_count=2\nenemy_race_1=Zerg\nenemy_race_2=Random\n"));
+        assert!(!ini.contains("lan_mode"));
+        assert!(!ini.contains("wait_for_"));
+        game_config.computer_races.clear();
+        let host = BotRole::Host {
+            game_name: "Bot".to_string(),
+            map: Some("maps/map.scx".to_string()),
+            min_players: 2,
+            max_players: 4,
+        };
+        let ini = bwapi_ini_for(host, Some("maps/map.scx".to_string()), &game_config);
+        assert!(ini.contains("auto_menu=LAN\n"));
+        assert!(
+            ini.contains("wait_for_min_players=2\nwait_for_max_players=4\nwait_for_time=30000\n")
+        );
+        assert!(!ini.contains("enemy_"));
+    }
+
+    #[test]
+    fn test_bwapi_ini_host_forces() {
+        let game_config = game_config(
+            "[game_type.TopVsBottom]
+forces = [
+    { name = 'Terran', bots = [{ name = 'A' }, { name = 'B' }] },
+    { name = 'Zerg', bots = [{ name = 'C' }] },
+]",
+        );
+        let host = BotRole::Host {
+            game_name: "Bot".to_string(),
+            map: Some("maps/map.scx".to_string()),
+            min_players: 3,
+            max_players: 3,
+        };
+        let ini = bwapi_ini_for(host, Some("maps/map.scx".to_string()), &game_config);
+        assert!(ini.contains("game_type=TOP_VS_BOTTOM\ngame_type_extra=2 vs 1\n"));
+        let ini = bwapi_ini_for(BotRole::Join { game_name: None }, None, &game_config);
+        assert!(!ini.contains("game_type"));
+    }
+
+    #[test]
+    fn test_bwapi_ini_join() {
+        let ini = bwapi_ini(
+            BotRole::Join {
+                game_name: Some("Bot".to_string()),
+            },
+            None,
+        );
+        assert!(ini.contains("game=Bot\n"));
+        assert!(!ini.contains("map="));
+    }
+
+    #[test]
+    fn test_bwapi_ini_join_human() {
+        let ini = bwapi_ini(BotRole::Join { game_name: None }, None);
+        assert!(ini.contains("game=JOIN_FIRST\n"));
+    }
+}
@@ -1,9 +1,13 @@
-use crate::base_folder;
+use crate::paths::Paths;
 use crate::setup::{ComponentConfig, ComponentInstallation};
 use hex_literal::hex;
 use std::path::PathBuf;
 
-pub fn java_component(config: ComponentConfig) -> ComponentInstallation {
+pub fn java_component(
+    paths: &Paths,
+    config: ComponentConfig,
+    download_connections: usize,
+) -> ComponentInstallation {
     ComponentInstallation {
         name: "Java 8 JRE",
         download_name: "jre.zip",
@@ -12,7 +16,9 @@ pub fn java_component(config: ComponentConfig) -> ComponentInstallation {
         provider: |component| component.download_and_unzip(true).map(|_| component.internal_folder.join("bin").join("javaw.exe")),
         config,
         hashes: &[hex!("ab1c3756c0f94e982edf77e7048263d2c7fc1048c57dd1185e5f441f007e9653") ],
-        internal_folder: base_folder().join("jre"),
+        internal_folder: paths.home.join("jre"),
+        downloads_dir: paths.downloads_dir.clone(),
+        download_connections,
     }
 }
 
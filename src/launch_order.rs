@@ -0,0 +1,227 @@
+//! Client bots (exe/jar) *must* be launched before DLL bots, as they need to connect to their
+//! resp. BWAPI server. Within that constraint, the host is launched first, followed by the bots
+//! ordered by their `order` - otherwise the bots keep the order of 'game.toml'.
+//! In 'TopVsBottom' games, bots are put into the forces in the order they join. All bots of the
+//! top force are therefore launched before the bots of the bottom force.
+use std::collections::HashSet;
+
+use anyhow::{bail, ensure};
+
+/// What the launch order of a bot depends on
+#[derive(Debug, Clone)]
+pub struct LaunchConstraints {
+    pub name: String,
+    pub is_dll: bool,
+    pub host: bool,
+    pub order: Option<u32>,
+    /// Index of the force of 'TopVsBottom' games
+    pub force: Option<usize>,
+}
+
+/// A bot (by its index in 'game.toml') and why it was put at its position in launch order
+#[derive(Debug, PartialEq, Eq)]
+pub struct LaunchSlot {
+    pub bot: usize,
+    pub reasons: Vec<String>,
+}
+
+/// Plans the launch order, fails if the constraints can't be satisfied. In bot hosted games, the
+/// first launched bot hosts.
+pub fn plan_launch_order(
+    bots: &[LaunchConstraints],
+    human_host: bool,
+) -> anyhow::Result<Vec<LaunchSlot>> {
+    let hosts: Vec<_> = bots.iter().filter(|bot| bot.host).collect();
+    ensure!(
+        hosts.len() <= 1,
+        "Only one bot can host, but {} are configured to host",
+        hosts
+            .iter()
+            .map(|bot| format!("'{}'", bot.name))
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+    if let Some(host) = hosts.first() {
+        ensure!(
+            !human_host,
+            "'{}' is configured to host, but the game is hosted by a human",
+            host.name
+        );
+        ensure!(
+            !host.is_dll || bots.iter().all(|bot| bot.is_dll),
+            "'{}' is a DLL bot and cannot host, because client bots must be launched first",
+            host.name
+        );
+        ensure!(
+            host.force.unwrap_or(0) == 0,
+            "'{}' is configured to host, but the host is always part of the top force",
+            host.name
+        );
+    }
+    let mut orders = HashSet::new();
+    for bot in bots.iter() {
+        if let Some(order) = bot.order {
+            ensure!(
+                orders.insert(order),
+                "Order {} is used by multiple bots, '{}' needs another order",
+                order,
+                bot.name
+            );
+        }
+    }
+
+    let mut launch_order: Vec<_> = (0..bots.len()).collect();
+    // Stable, bots which are equal keep their order of 'game.toml'
+    launch_order.sort_by_key(|&index| {
+        let bot = &bots[index];
+        (
+            bot.force,
+            bot.is_dll,
+            !bot.host,
+            bot.order.unwrap_or(u32::MAX),
+        )
+    });
+    if let Some(pair) = launch_order
+        .windows(2)
+        .find(|pair| bots[pair[0]].is_dll && !bots[pair[1]].is_dll)
+    {
+        bail!(
+            "'{}' is a DLL bot of the top force, but client bot '{}' of the bottom force must be launched before it",
+            bots[pair[0]].name,
+            bots[pair[1]].name
+        );
+    }
+    Ok(launch_order
+        .into_iter()
+        .enumerate()
+        .map(|(position, index)| {
+            let bot = &bots[index];
+            let mut reasons = vec![];
+            if !human_host && position == 0 {
+                reasons.push(if bot.host {
+                    "host, because of 'host = true'".to_string()
+                } else {
+                    "host, because it is launched first".to_string()
+                });
+            }
+            if bot.is_dll && bots[index + 1..].iter().any(|other| !other.is_dll) {
+                reasons.push("moved after client bots, because it is a DLL bot".to_string());
+            }
+            if let Some(order) = bot.order {
+                reasons.push(format!("'order = {order}'"));
+            }
+            LaunchSlot {
+                bot: index,
+                reasons,
+            }
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::launch_order::{plan_launch_order, LaunchConstraints};
+
+    fn bot(name: &str, is_dll: bool) -> LaunchConstraints {
+        LaunchConstraints {
+            name: name.to_string(),
+            is_dll,
+            host: false,
+            order: None,
+            force: None,
+        }
+    }
+
+    fn planned(bots: &[LaunchConstraints], human_host: bool) -> Vec<usize> {
+        plan_launch_order(bots, human_host)
+            .unwrap()
+            .iter()
+            .map(|slot| slot.bot)
+            .collect()
+    }
+
+    #[test]
+    fn test_keeps_configured_order() {
+        let bots = [bot("A", false), bot("B", false), bot("C", true)];
+        assert_eq!(planned(&bots, false), [0, 1, 2]);
+        let slots = plan_launch_order(&bots, false).unwrap();
+        assert_eq!(slots[0].reasons, ["host, because it is launched first"]);
+        assert!(slots[1].reasons.is_empty());
+        assert!(slots[2].reasons.is_empty());
+    }
+
+    #[test]
+    fn test_dll_bots_after_client_bots() {
+        let bots = [bot("Dll", true), bot("Exe", false), bot("Dll2", true)];
+        assert_eq!(planned(&bots, false), [1, 0, 2]);
+        let slots = plan_launch_order(&bots, false).unwrap();
+        assert_eq!(
+            slots[1].reasons,
+            ["moved after client bots, because it is a DLL bot"]
+        );
+        assert!(slots[2].reasons.is_empty());
+        assert_eq!(planned(&bots, true), [1, 0, 2]);
+    }
+
+    #[test]
+    fn test_host_and_order() {
+        let mut bots = [bot("A", false), bot("B", false), bot("C", false)];
+        bots[0].order = Some(2);
+        bots[1].order = Some(1);
+        bots[2].host = true;
+        assert_eq!(planned(&bots, false), [2, 1, 0]);
+        let slots = plan_launch_order(&bots, false).unwrap();
+        assert_eq!(slots[0].reasons, ["host, because of 'host = true'"]);
+        assert_eq!(slots[1].reasons, ["'order = 1'"]);
+    }
+
+    #[test]
+    fn test_dll_host() {
+        let mut bots = [bot("Exe", false), bot("Dll", true)];
+        bots[1].host = true;
+        assert!(plan_launch_order(&bots, false).is_err());
+        let mut bots = [bot("Dll", true), bot("Dll2", true)];
+        bots[1].host = true;
+        assert_eq!(planned(&bots, false), [1, 0]);
+    }
+
+    #[test]
+    fn test_forces() {
+        let mut bots = [
+            bot("Top1", false),
+            bot("Top2", false),
+            bot("Bottom1", false),
+            bot("Bottom2", true),
+        ];
+        bots[1].order = Some(2);
+        bots[2].order = Some(1);
+        for (index, bot) in bots.iter_mut().enumerate() {
+            bot.force = Some(index / 2);
+        }
+        let launch_order = planned(&bots, false);
+        assert_eq!(launch_order, [1, 0, 2, 3]);
+        // The first 2 bots to join make up the top force
+        let top: Vec<_> = launch_order[..2].iter().map(|&it| &bots[it].name).collect();
+        assert_eq!(top, ["Top2", "Top1"]);
+
+        bots[2].host = true;
+        assert!(plan_launch_order(&bots, false).is_err());
+        bots[2].host = false;
+        bots[0].is_dll = true;
+        bots[1].is_dll = true;
+        assert!(plan_launch_order(&bots, false).is_err());
+    }
+
+    #[test]
+    fn test_unsatisfiable() {
+        let mut bots = [bot("A", false), bot("B", false)];
+        bots[0].host = true;
+        assert!(plan_launch_order(&bots, true).is_err());
+        bots[1].host = true;
+        assert!(plan_launch_order(&bots, false).is_err());
+        let mut bots = [bot("A", false), bot("B", false)];
+        bots[0].order = Some(1);
+        bots[1].order = Some(1);
+        assert!(plan_launch_order(&bots, false).is_err());
+    }
+}
@@ -0,0 +1,206 @@
+use std::fs::{read_dir, remove_dir_all, remove_file, File};
+use std::io::copy;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use anyhow::Context;
+use serde::Deserialize;
+use zip::write::FileOptions;
+use zip::ZipWriter;
+
+/// Log files of a single bot of the game
+#[derive(Debug)]
+pub struct BotLogs {
+    pub name: String,
+    pub log_dir: PathBuf,
+    pub tm_dir: PathBuf,
+    /// Where StarCraft writes the replays of the bot, see `replay_folder`
+    pub replay_dir: PathBuf,
+}
+
+/// How much of past games is kept in the log archive and crash dump folders, the oldest games are
+/// deleted first. Without limits, nothing is deleted.
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct LogRetention {
+    /// Keep the files of at most this many games
+    pub max_games: Option<usize>,
+    /// Keep at most this many megabytes
+    pub max_size_mb: Option<u64>,
+}
+
+impl LogRetention {
+    /// Deletes the oldest entries of `folder` (files or folders, each belonging to one game)
+    /// accepted by `filter`, until the limits are met. The newest entry is always kept, it was
+    /// just written. Returns the deleted entries.
+    pub fn apply(
+        &self,
+        folder: &Path,
+        filter: &dyn Fn(&Path) -> bool,
+    ) -> anyhow::Result<Vec<PathBuf>> {
+        if self.max_games.is_none() && self.max_size_mb.is_none() {
+            return Ok(vec![]);
+        }
+        let Ok(entries) = read_dir(folder) else {
+            return Ok(vec![]);
+        };
+        let mut entries: Vec<_> = entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| filter(path))
+            .map(|path| {
+                let modified = path
+                    .metadata()
+                    .and_then(|it| it.modified())
+                    .unwrap_or(SystemTime::UNIX_EPOCH);
+                (modified, path)
+            })
+            .collect();
+        // Newest first
+        entries.sort_by(|(a, _), (b, _)| b.cmp(a));
+        let max_size = self.max_size_mb.map(|it| it * 1024 * 1024);
+        let mut size = 0;
+        let mut deleted = vec![];
+        for (index, (_, path)) in entries.into_iter().enumerate() {
+            size += entry_size(&path);
+            let keep = index == 0
+                || self.max_games.is_none_or(|max| index < max)
+                    && max_size.is_none_or(|max| size <= max);
+            if keep {
+                continue;
+            }
+            if path.is_dir() {
+                remove_dir_all(&path)
+            } else {
+                remove_file(&path)
+            }
+            .with_context(|| format!("Could not delete '{}'", path.display()))?;
+            deleted.push(path);
+        }
+        Ok(deleted)
+    }
+}
+
+fn entry_size(path: &Path) -> u64 {
+    if path.is_dir() {
+        read_dir(path)
+            .map(|entries| entries.flatten().map(|it| entry_size(&it.path())).sum())
+            .unwrap_or(0)
+    } else {
+        path.metadata().map(|it| it.len()).unwrap_or(0)
+    }
+}
+
+/// Zips the logs and TM results of all bots into `archive`. Each bot gets its own folder, numbered
+/// in launch order (the same bot might play multiple times).
+pub fn write_log_archive(archive: &Path, bots: &[BotLogs]) -> anyhow::Result<()> {
+    let file = File::create(archive)
+        .with_context(|| format!("Could not create '{}'", archive.display()))?;
+    let mut zip = ZipWriter::new(file);
+    for (index, bot) in bots.iter().enumerate() {
+        let folder = format!("{}_{}", index + 1, bot.name);
+        add_folder(&mut zip, &bot.log_dir, &format!("{folder}/logs"), &|_| true)?;
+        add_folder(&mut zip, &bot.tm_dir, &format!("{folder}/tm"), &|path| {
+            path.extension().is_some_and(|ext| ext == "csv")
+        })?;
+    }
+    zip.finish()?;
+    Ok(())
+}
+
+fn add_folder(
+    zip: &mut ZipWriter<File>,
+    folder: &Path,
+    prefix: &str,
+    filter: &dyn Fn(&Path) -> bool,
+) -> anyhow::Result<()> {
+    let Ok(entries) = read_dir(folder) else {
+        // Ie. no TM results, because the bot ran without TM
+        return Ok(());
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let name = format!("{prefix}/{}", entry.file_name().to_string_lossy());
+        if path.is_dir() {
+            add_folder(zip, &path, &name, filter)?;
+        } else if filter(&path) {
+            zip.start_file(name, FileOptions::default())?;
+            copy(&mut File::open(&path)?, zip)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::log_archive::{write_log_archive, BotLogs, LogRetention};
+    use crate::test_util::TempDir;
+    use std::fs::{create_dir_all, write, File};
+    use std::time::{Duration, SystemTime};
+    use zip::ZipArchive;
+
+    #[test]
+    fn test_log_retention() {
+        let folder = TempDir::new("log-retention");
+        create_dir_all(folder.join("game_3")).unwrap();
+        let now = SystemTime::now();
+        for (age, name) in [(3, "game_1.zip"), (2, "game_2.zip"), (1, "game_3/dump.dmp")] {
+            write(folder.join(name), "log").unwrap();
+            File::options()
+                .write(true)
+                .open(folder.join(name))
+                .unwrap()
+                .set_modified(now - Duration::from_secs(age * 60))
+                .unwrap();
+        }
+        write(folder.join("notes.txt"), "not an archive").unwrap();
+        let filter = |path: &std::path::Path| path.extension().is_none_or(|ext| ext == "zip");
+
+        let deleted = LogRetention::default().apply(&folder, &filter).unwrap();
+        assert!(deleted.is_empty());
+
+        let retention = LogRetention {
+            max_games: Some(2),
+            max_size_mb: None,
+        };
+        let deleted = retention.apply(&folder, &filter).unwrap();
+        assert_eq!(deleted, [folder.join("game_1.zip")]);
+
+        let retention = LogRetention {
+            max_games: None,
+            max_size_mb: Some(0),
+        };
+        retention.apply(&folder, &filter).unwrap();
+        assert!(!folder.join("game_2.zip").exists());
+        // The newest game is always kept
+        assert!(folder.join("game_3").exists());
+        assert!(folder.join("notes.txt").exists());
+    }
+
+    #[test]
+    fn test_write_log_archive() {
+        let folder = TempDir::new("log-archive");
+        create_dir_all(folder.join("logs")).unwrap();
+        create_dir_all(folder.join("tm")).unwrap();
+        write(folder.join("logs").join("bot_out.log"), "out").unwrap();
+        write(folder.join("tm").join("result.csv"), "result").unwrap();
+        write(folder.join("tm").join("TM_440.dll"), "dll").unwrap();
+        let archive = folder.join("logs.zip");
+
+        write_log_archive(
+            &archive,
+            &[BotLogs {
+                name: "Bot".to_string(),
+                log_dir: folder.join("logs"),
+                tm_dir: folder.join("tm"),
+                replay_dir: folder.join("replays"),
+            }],
+        )
+        .unwrap();
+
+        let zip = ZipArchive::new(File::open(archive).unwrap()).unwrap();
+        let mut names: Vec<_> = zip.file_names().collect();
+        names.sort();
+        assert_eq!(names, ["1_Bot/logs/bot_out.log", "1_Bot/tm/result.csv"]);
+    }
+}
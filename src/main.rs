@@ -1,42 +1,92 @@
-use std::collections::HashSet;
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::fmt::{Debug, Display, Formatter};
-use std::fs::{create_dir_all, metadata, read_to_string, remove_file, File};
+use std::fs::{create_dir_all, read_to_string, remove_file, File, OpenOptions};
+use std::io::IsTerminal;
+use std::ops::Deref;
 use std::path::{Path, PathBuf};
-use std::process::Child;
+use std::process::{Child, Command};
 use std::str::FromStr;
-use std::time::Duration;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-use anyhow::{anyhow, ensure, Context};
+use anyhow::{anyhow, bail, ensure, Context};
 use clap::Parser;
-use crc::{Crc, CRC_32_ISO_HDLC};
 use log::{debug, error, info, warn, LevelFilter};
 use retry::delay::Fixed;
 use retry::{retry, OperationResult};
 use serde::de::Unexpected;
-use serde::{Deserialize, Deserializer};
-use simplelog::{ColorChoice, Config, TermLogger, TerminalMode};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use simplelog::{ColorChoice, ConfigBuilder, TermLogger, TerminalMode};
 
 use crate::botsetup::{Binary, BotSetup, LaunchBuilder};
-use crate::bwapi::{AutoMenu, BwapiConnectMode, BwapiIni, BwapiVersion, GameTableAccess};
-use crate::bwheadless::{BwHeadless, BwHeadlessConnectMode};
+use crate::bwapi::{
+    check_bwapi_path, parse_ini, AutoMenu, BwapiConfig, BwapiConnectMode, BwapiIni,
+    BwapiIniCleanup, BwapiVersion, GameTableAccess, IniSections, DEFAULT_REPLAY_PATH,
+};
+use crate::bwapi_registry::InstallPathOverride;
+use crate::bwheadless::{BwHeadless, BwHeadlessCapabilities, BwHeadlessConnectMode};
 use crate::cli::Cli;
-use crate::injectory::{Injectory, InjectoryConnectMode};
+use crate::crash_dumps::{CrashDetectionConfig, LocalDumps};
+use crate::environment::Environment;
+use crate::injectory::{remove_map_copies, remove_map_link, Injectory};
 use crate::java_setup::{java_component, java_default_config};
-use crate::setup::ComponentConfig;
-use crate::starcraft_setup::{starcraft_component, starcraft_default_config};
+use crate::launch_order::{plan_launch_order, LaunchConstraints};
+use crate::log_archive::{write_log_archive, BotLogs, LogRetention};
+use crate::maps::{auto_menu_map_problem, check_map_file_name, copy_sanitized_map, find_map};
+use crate::paths::Paths;
+use crate::prepare_cache::PrepareCache;
+use crate::reporters::{
+    create_reporters, file_reporter, replay_folder, report_all, ReporterKind, ReporterOptions,
+};
+use crate::results::{
+    BotResult, GameResult, LaunchMetrics, LobbyFailure, LobbyFailureKind, Outcome, PhaseTimer,
+    ResultFormat,
+};
+use crate::role::GameRole;
+use crate::setup::{ComponentConfig, ComponentInstallation};
+use crate::starcraft_setup::{
+    snp_support, starcraft_component, starcraft_default_config, SnpSupport,
+};
+use crate::tm_results::FrameLimits;
+use crate::tournament_module::{TmMissing, TournamentModule};
+use crate::vcredist::VcRuntime;
 use crate::wrapper::ExecutionWrapper;
 
+mod bot_scan;
 mod botsetup;
 mod bwapi;
+mod bwapi_registry;
 mod bwheadless;
 mod cli;
+mod crash_dialog;
+mod crash_dumps;
+mod environment;
+mod info;
+mod init;
 mod injectory;
 mod java_setup;
+mod launch_order;
+mod log_archive;
+mod manifest;
+mod maps;
+mod memory;
+mod paths;
+mod prepare_cache;
+mod probe;
+mod reporters;
+mod results;
+mod role;
 mod setup;
 mod starcraft_setup;
+#[cfg(test)]
+mod test_util;
+mod tm_results;
+mod tools;
+mod tournament_module;
+mod vcredist;
+mod window_title;
 mod wrapper;
 
-#[derive(Deserialize, Debug, Default)]
+#[derive(Deserialize, Debug)]
 struct ShotgunConfig {
     #[serde(default = "starcraft_default_config")]
     starcraft_path: ComponentConfig,
@@ -45,11 +95,205 @@ struct ShotgunConfig {
     #[serde(default)]
     wrapper: ExecutionWrapper,
     replay_path: Option<String>,
+    /// Default memory limit of client bots, can be overridden per bot
+    memory_limit_mb: Option<u64>,
+    /// Default time client bots get to connect to BWAPI, can be overridden per bot
+    connect_timeout_secs: Option<u64>,
+    /// Time client bots get to exit on their own after their BWAPI server exited
+    #[serde(default = "default_bot_shutdown_grace_secs")]
+    bot_shutdown_grace_secs: u64,
+    /// End the game for all bots, as soon as the StarCraft of one bot exited
+    #[serde(default)]
+    kill_on_first_exit: bool,
+    /// Prefix log lines with the current time
+    #[serde(default)]
+    log_timestamps: bool,
+    /// Prefix log lines with the id of the logging thread
+    #[serde(default)]
+    log_thread_id: bool,
+    /// Keep the maps folder links created for headful hosts after the game
+    #[serde(default)]
+    keep_map_links: bool,
+    /// Verify the files in the 'tools' folder on startup
+    #[serde(default)]
+    check_tools_integrity: bool,
+    /// Wait for confirmation before launching, if the pre-flight checks found problems
+    #[serde(default)]
+    confirm_warnings: bool,
+    /// Let injectory wait for StarCraft to exit (headful bots)
+    #[serde(default = "default_true")]
+    injectory_wait: bool,
+    /// Let injectory kill StarCraft when it exits itself (headful bots)
+    #[serde(default = "default_true")]
+    injectory_kill_on_exit: bool,
+    /// Keep crash dumps of StarCraft (Wine: crash backtraces) in the log folder of the bot
+    #[serde(default)]
+    collect_crash_dumps: bool,
+    #[serde(default)]
+    crash_detection: CrashDetectionConfig,
+    /// Program (relative to BWAIShotgun, or on the PATH) and arguments run after every game, see
+    /// `post_game_command`
+    post_game_command: Option<Vec<String>>,
+    /// Replaces 'tools/oldbwapi.dll' injected into StarCraft for headful bots
+    oldbwapi_dll_path: Option<PathBuf>,
+    /// Replaces 'tools/bwheadless.exe' used to launch headless bots
+    bwheadless_exe: Option<PathBuf>,
+    /// Replaces 'tools/injectory_x86.exe' used to launch headful bots
+    injectory_exe: Option<PathBuf>,
+    /// Keep the bwapi.ini of each bot as 'bwapi_last_success.ini' after games without failures
+    #[serde(default)]
+    copy_bwapi_ini_on_success: bool,
+    /// Keep the bwapi.ini of each bot as 'bwapi_last.ini' after every game
+    #[serde(default)]
+    copy_bwapi_ini_always: bool,
+    /// Folder levels of 'bwapi-data/AI' searched for the bot binary (1 = no subfolders, max 5)
+    #[serde(default = "default_bot_binary_scan_depth")]
+    bot_binary_scan_depth: usize,
+    /// Check the generated bwapi.ini files (ie. for line breaks in player names) before launching
+    #[serde(default)]
+    validate_bwapi_ini: bool,
+    /// Parallel connections used to download StarCraft and Java, 1 disables segmented downloads
+    #[serde(default = "default_download_connections")]
+    download_connections: usize,
+    /// Replaces the 'tools' folder (relative to BWAIShotgun), ie. to share it between installations
+    tools_path: Option<PathBuf>,
+    /// Append the result of every game to this file (relative to BWAIShotgun), reported like the
+    /// 'json' or 'csv' reporter of 'game.toml'
+    result_file: Option<PathBuf>,
+    /// Append startup times of every game to this file (relative to BWAIShotgun)
+    metrics_file: Option<PathBuf>,
+    #[serde(default)]
+    result_format: ResultFormat,
+    /// Truncate player names longer than BWAPI supports instead of failing
+    #[serde(default)]
+    auto_truncate_player_names: bool,
+    #[serde(default)]
+    tm_missing: TmMissing,
+    /// Move suspicious files found in bot folders (see `bot_scan`) to their quarantine folder
+    #[serde(default)]
+    auto_quarantine: bool,
+    #[serde(default)]
+    log_retention: LogRetention,
+}
+
+fn default_bot_shutdown_grace_secs() -> u64 {
+    2
+}
+
+fn default_bot_binary_scan_depth() -> usize {
+    1
+}
+
+fn default_download_connections() -> usize {
+    4
+}
+
+/// The tool in the 'tools' folder, unless replaced by a CLI argument (as given) or 'shotgun.toml'
+/// (relative to BWAIShotgun). Replacements must exist, missing tools are reported when launching.
+fn tool_path(
+    paths: &Paths,
+    file_name: &str,
+    cli_path: Option<PathBuf>,
+    config_path: Option<PathBuf>,
+) -> anyhow::Result<PathBuf> {
+    let Some(path) = cli_path.or_else(|| config_path.map(|it| paths.home.join(it))) else {
+        return Ok(paths.tools_dir.join(file_name));
+    };
+    ensure!(
+        path.is_file(),
+        "'{file_name}' is replaced by '{}', which does not exist",
+        path.display()
+    );
+    debug!("Using '{}' instead of '{file_name}'", path.display());
+    Ok(path)
+}
+
+/// Longest character name BWAPI accepts
+const MAX_PLAYER_NAME_LEN: usize = 24;
+
+/// Empty or too long names make BWAPI use a fallback name, the bots could not find each other
+fn check_player_name(name: &str, auto_truncate: bool) -> anyhow::Result<String> {
+    ensure!(
+        !name.trim().is_empty(),
+        "The player name {name:?} is empty, BWAPI would use a fallback name"
+    );
+    let length = name.chars().count();
+    if length <= MAX_PLAYER_NAME_LEN {
+        return Ok(name.to_string());
+    }
+    ensure!(
+        auto_truncate,
+        "The player name '{name}' has {length} characters, but BWAPI only supports {MAX_PLAYER_NAME_LEN}. Set a shorter 'player_name' or 'auto_truncate_player_names = true' in 'shotgun.toml'"
+    );
+    let truncated = truncate_player_name(name);
+    warn!("The player name '{name}' is too long for BWAPI, using '{truncated}' instead");
+    Ok(truncated)
+}
+
+/// The first `MAX_PLAYER_NAME_LEN` characters of the name
+fn truncate_player_name(name: &str) -> String {
+    name.chars().take(MAX_PLAYER_NAME_LEN).collect()
+}
+
+/// Different names which are equal after truncation (ie. 'MyVeryLongBotName_Version1' and
+/// '..._Version2') would join as the same player, and joiners could not tell the hosts apart
+fn check_truncated_names_unique<'a>(
+    names: impl IntoIterator<Item = &'a str>,
+) -> anyhow::Result<()> {
+    let mut truncated_names: HashMap<String, &str> = HashMap::new();
+    for name in names {
+        let truncated = truncate_player_name(name);
+        if let Some(other) = truncated_names.insert(truncated.clone(), name) {
+            ensure!(
+                other == name,
+                "The player names '{other}' and '{name}' are both truncated to '{truncated}', please set a shorter 'player_name' for one of them"
+            );
+        }
+    }
+    Ok(())
+}
+
+impl Default for ShotgunConfig {
+    fn default() -> Self {
+        toml::from_str("").expect("Default 'shotgun.toml' is invalid")
+    }
 }
 
-#[derive(Deserialize, Clone, Copy, Debug)]
+impl ShotgunConfig {
+    /// The configured `tools_path`, or the default of `paths`
+    fn resolve_tools_folder(&self, paths: &Paths) -> PathBuf {
+        match &self.tools_path {
+            Some(tools_path) => paths.home.join(tools_path),
+            None => paths.tools_dir.clone(),
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(from = "HeadfulModeConfig")]
 pub enum HeadfulMode {
     Off,
+    On { no_wmode: bool, no_sound: bool },
+}
+
+/// Accepts `headful = true`, `headful = "On"` and `headful = { On = { no_wmode = true } }`
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum HeadfulModeConfig {
+    Enabled(bool),
+    Named(HeadfulModeName),
+    Table(HeadfulModeTable),
+}
+
+#[derive(Deserialize)]
+enum HeadfulModeName {
+    Off,
+    On,
+}
+
+#[derive(Deserialize)]
+enum HeadfulModeTable {
+    Off {},
     On {
         #[serde(default)]
         no_wmode: bool,
@@ -64,77 +308,754 @@ impl Default for HeadfulMode {
     }
 }
 
-#[derive(Deserialize, Debug)]
+impl From<HeadfulModeConfig> for HeadfulMode {
+    fn from(config: HeadfulModeConfig) -> Self {
+        match config {
+            HeadfulModeConfig::Enabled(false)
+            | HeadfulModeConfig::Named(HeadfulModeName::Off)
+            | HeadfulModeConfig::Table(HeadfulModeTable::Off {}) => HeadfulMode::Off,
+            HeadfulModeConfig::Enabled(true) | HeadfulModeConfig::Named(HeadfulModeName::On) => {
+                HeadfulMode::On {
+                    no_wmode: false,
+                    no_sound: false,
+                }
+            }
+            HeadfulModeConfig::Table(HeadfulModeTable::On { no_wmode, no_sound }) => {
+                HeadfulMode::On { no_wmode, no_sound }
+            }
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
 pub struct BotLaunchConfig {
     pub name: String,
     pub player_name: Option<String>,
     pub race: Option<Race>,
     #[serde(default)]
     pub headful: HeadfulMode,
+    /// Shorthand for `no_wmode` of `headful`
+    #[serde(default)]
+    pub no_wmode: bool,
+    /// Shorthand for `no_sound` of `headful`
+    #[serde(default)]
+    pub no_sound: bool,
+    /// Bots with a lower order are launched first (client bots are still launched before DLL bots)
+    pub order: Option<u32>,
+    /// Explicitly select this bot as host, instead of the first launched bot
+    #[serde(default)]
+    pub host: bool,
+    /// Command line arguments for exe/jar bots, in addition to the ones in 'bot.toml'
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// StarCraft installation to use for this bot (relative to BWAIShotgun)
+    pub starcraft_path: Option<PathBuf>,
+    /// Title of the StarCraft window of headful bots, defaults to the player name. Empty keeps
+    /// the original title.
+    pub window_title: Option<String>,
+    /// Ini file (relative to BWAIShotgun) with additional sections and keys for the bwapi.ini,
+    /// keys set by BWAIShotgun take precedence
+    pub extra_bwapi_ini: Option<PathBuf>,
 }
 
-#[derive(Deserialize, Debug)]
+impl BotLaunchConfig {
+    /// The headful mode, including the top level `no_wmode`/`no_sound` settings
+    pub fn headful_mode(&self) -> HeadfulMode {
+        match self.headful {
+            HeadfulMode::Off => HeadfulMode::Off,
+            HeadfulMode::On { no_wmode, no_sound } => HeadfulMode::On {
+                no_wmode: no_wmode || self.no_wmode,
+                no_sound: no_sound || self.no_sound,
+            },
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
 pub enum GameType {
     Melee(Vec<BotLaunchConfig>),
+    /// Exactly two forces, the first one is the top force
+    TopVsBottom {
+        forces: Vec<Force>,
+        /// Require both forces to have the same number of bots, ie. for maps with fixed teams
+        #[serde(default)]
+        balanced: bool,
+    },
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Force {
+    pub name: String,
+    pub bots: Vec<BotLaunchConfig>,
+}
+
+impl GameType {
+    /// All bots, in the order of their forces
+    pub fn bots(&self) -> Vec<&BotLaunchConfig> {
+        match self {
+            GameType::Melee(bots) => bots.iter().collect(),
+            GameType::TopVsBottom { forces, .. } => {
+                forces.iter().flat_map(|force| force.bots.iter()).collect()
+            }
+        }
+    }
+
+    pub fn bots_mut(&mut self) -> Vec<&mut BotLaunchConfig> {
+        match self {
+            GameType::Melee(bots) => bots.iter_mut().collect(),
+            GameType::TopVsBottom { forces, .. } => forces
+                .iter_mut()
+                .flat_map(|force| force.bots.iter_mut())
+                .collect(),
+        }
+    }
+
+    /// Number of players of the top and bottom force, `None` for games without forces
+    pub fn force_sizes(&self) -> Option<(usize, usize)> {
+        match self {
+            GameType::Melee(_) => None,
+            GameType::TopVsBottom { forces, .. } => Some((
+                forces.first().map_or(0, |it| it.bots.len()),
+                forces.get(1).map_or(0, |it| it.bots.len()),
+            )),
+        }
+    }
+}
+
+/// What a game type was validated against
+#[derive(Debug, Clone)]
+pub struct ValidationContext {
+    /// The default StarCraft installation, bots might use their own
+    pub starcraft_path: PathBuf,
+    pub min_players: usize,
+    pub max_players: usize,
+}
+
+/// A `GameType` which passed the checks specific to its type, see `TryFrom`
+#[derive(Debug)]
+pub struct ValidatedGameType {
+    game_type: GameType,
+    validation_context: ValidationContext,
+}
+
+impl ValidatedGameType {
+    pub fn validation_context(&self) -> &ValidationContext {
+        &self.validation_context
+    }
+}
+
+impl Deref for ValidatedGameType {
+    type Target = GameType;
+
+    fn deref(&self) -> &GameType {
+        &self.game_type
+    }
+}
+
+impl TryFrom<(GameType, &Path)> for ValidatedGameType {
+    type Error = anyhow::Error;
+
+    fn try_from((game_type, starcraft_path): (GameType, &Path)) -> anyhow::Result<Self> {
+        let validation_context = match &game_type {
+            GameType::Melee(_) => ValidationContext {
+                starcraft_path: starcraft_path.to_path_buf(),
+                min_players: 1,
+                max_players: 8,
+            },
+            GameType::TopVsBottom { forces, balanced } => {
+                let [top, bottom] = forces.as_slice() else {
+                    bail!(
+                        "'TopVsBottom' needs exactly 2 forces (top and bottom), but {} are configured",
+                        forces.len()
+                    );
+                };
+                for force in forces {
+                    ensure!(!force.name.trim().is_empty(), "All forces need a name");
+                    ensure!(!force.bots.is_empty(), "Force '{}' has no bots", force.name);
+                }
+                ensure!(
+                    top.name != bottom.name,
+                    "Both forces are named '{}'",
+                    top.name
+                );
+                ensure!(
+                    !balanced || top.bots.len() == bottom.bots.len(),
+                    "Force '{}' has {} bots, but '{}' has {} - 'balanced' requires the same number",
+                    top.name,
+                    top.bots.len(),
+                    bottom.name,
+                    bottom.bots.len()
+                );
+                ValidationContext {
+                    starcraft_path: starcraft_path.to_path_buf(),
+                    min_players: 2,
+                    max_players: 8,
+                }
+            }
+        };
+        let bots = game_type.bots();
+        ensure!(
+            bots.len() >= validation_context.min_players,
+            "{} bots are configured, but the game needs at least {}. Add some to 'game_type' or pass them on the command line",
+            bots.len(),
+            validation_context.min_players
+        );
+        ensure!(
+            bots.len() <= validation_context.max_players,
+            "{} bots are configured, but the game allows at most {} players",
+            bots.len(),
+            validation_context.max_players
+        );
+        if let Some(index) = bots.iter().position(|bot| bot.name.trim().is_empty()) {
+            bail!("Bot {} of 'game_type' has no name", index + 1);
+        }
+        Ok(Self {
+            game_type,
+            validation_context,
+        })
+    }
 }
 
+/// A game configuration is loaded with a plain `GameType` (`GameConfig<GameType>`), the bots from
+/// the command line are merged into that. Only `validate_game_type` makes it a `GameConfig`.
 #[derive(Deserialize, Debug)]
-pub struct GameConfig {
+pub struct GameConfig<G = ValidatedGameType> {
     pub map: Option<String>,
     pub game_name: Option<String>,
-    pub game_type: GameType,
+    /// Without a `game_name`, name the game after the hosting bot instead of 'shotgun'
+    #[serde(default = "default_true")]
+    pub game_name_auto: bool,
+    pub game_type: G,
     #[serde(default)]
     pub human_host: bool,
     #[serde(default)]
     pub human_speed: bool,
     #[serde(default = "default_latency")]
     pub latency_frames: u32,
+    /// Overrides `latency_frames`, see `GameSpeedPreset`
+    pub speed_preset: Option<GameSpeedPreset>,
     pub time_out_at_frame: Option<u32>,
+    /// What happens once `time_out_at_frame` is reached
+    #[serde(default)]
+    pub timeout_action: TimeoutAction,
+    /// If false, bots failing to prepare are skipped instead of aborting the game
+    #[serde(default = "default_true")]
+    pub require_all_bots_ready: bool,
+    /// Let BWAPI drop players not responding in the lobby/game
+    #[serde(default = "default_true")]
+    pub drop_players: bool,
+    /// Let a single headless bot analyze `replay` instead of playing a game
+    #[serde(default)]
+    pub replay_mode: bool,
+    /// Replay for `replay_mode`, relative to StarCraft
+    pub replay: Option<PathBuf>,
+    /// If false, all bots are muted - regardless of their `no_sound` setting
+    #[serde(default = "default_true")]
+    pub sound: bool,
+    /// Log folder of each bot (relative to BWAIShotgun), '{bot}' and '{game_id}' are replaced
+    pub log_dir: Option<String>,
+    /// Chat message the tournament module sends at game start (`TM_ANNOUNCE`), see `announcement`
+    pub announce: Option<String>,
+    /// Require `map` in human hosted games too, ie. for bots selecting their strategy by map
+    #[serde(default)]
+    pub map_required_for_human_host: bool,
+    /// Passed to the bots like `map`, but not validated (the human might pick another one)
+    pub map_hint: Option<String>,
+    /// Copy maps with file names BWAPI can't handle to 'maps/BWAIShotgun' with a sanitized name,
+    /// instead of failing
+    #[serde(default)]
+    pub auto_rename_maps: bool,
+    /// Fixed seed for StarCraft's RNG, to make games reproducible (requires BWAPI 4.x)
+    pub random_seed: Option<u32>,
+    /// Races of computer players added by the (headful) host. BWAPI only offers the default
+    /// melee AI, there are no difficulties to choose from.
+    #[serde(default)]
+    pub computer_races: Vec<Race>,
+    /// Players (bots and humans) the host waits for before starting, defaults to the number of bots
+    pub min_players: Option<usize>,
+    /// Players allowed to join the host, defaults to `min_players`
+    pub max_players: Option<usize>,
+    /// Seconds the host waits for more players once `min_players` joined
+    #[serde(default = "default_lobby_grace_secs")]
+    pub lobby_grace_secs: u64,
+    /// Adds a slot for a human to watch the game. StarCraft has no observer slots, the human
+    /// joins as a player and has to stay passive.
+    #[serde(default)]
+    pub allow_human_observer: bool,
+    /// Milliseconds to wait after starting StarCraft for a bot, before its client process is
+    /// started and the next bot is launched
+    #[serde(default)]
+    pub bot_launch_pause_ms: u32,
+    /// Milliseconds to wait after launching a headful host, so its StarCraft created the game
+    /// before other bots try to join
+    #[serde(default = "default_headful_host_delay_ms")]
+    pub headful_host_delay_ms: u32,
+    /// Seconds the game may take to start after all bots were launched, before all bots are
+    /// killed. Requires a TM, as only the TM reports the start of the game.
+    pub lobby_timeout_secs: Option<u64>,
+    /// Replaces the tournament module setting of all bots for this game
+    pub tournament_module: Option<TournamentModule>,
+    /// Checked after the game with the frame times of the TM, and passed to TMs supporting it
+    pub frame_limits: Option<FrameLimits>,
+    /// Write the result of the game with these reporters
+    #[serde(default)]
+    pub reporters: Vec<ReporterKind>,
+    /// Options of the reporters, ie. `[reporter.basil]`
+    #[serde(default)]
+    pub reporter: ReporterOptions,
     // Configured by CLI or shotgun.toml, not the game config
     #[serde(skip)]
     pub replay_path: Option<String>,
 }
 
+/// Replaces the `{name}` placeholders of a template, unknown placeholders are kept
+fn expand_template(template: &str, values: &[(&str, &str)]) -> String {
+    values
+        .iter()
+        .fold(template.to_string(), |result, (name, value)| {
+            result.replace(&format!("{{{name}}}"), value)
+        })
+}
+
+/// Environment variable telling bots (and StarCraft) the map of the game, if it is known
+const MAP_ENV_VAR: &str = "BWAISHOTGUN_MAP";
+
 fn default_latency() -> u32 {
     3
 }
 
-impl GameConfig {
-    fn load(starcraft_path: &Path) -> anyhow::Result<GameConfig> {
-        let game_toml_path = base_folder().join("game.toml");
+/// Latency frames (bwheadless' `-gs`) are the frames between a command being issued and being
+/// executed. StarCraft keeps this delay at roughly the same time for all game speeds, so slower
+/// speeds need fewer frames. The presets keep the default delay of 3 frames at "Fastest" (126ms),
+/// rounded up to whole frames. Presets don't change the game speed itself, see `human_speed`.
+#[derive(Deserialize, Debug, Copy, Clone, Eq, PartialEq)]
+pub enum GameSpeedPreset {
+    /// 167ms per frame
+    Slowest,
+    /// 111ms per frame
+    Slower,
+    /// 83ms per frame
+    Slow,
+    /// 67ms per frame
+    Normal,
+    /// 56ms per frame
+    Fast,
+    /// 48ms per frame
+    Faster,
+    /// 42ms per frame, used by bot tournaments
+    Fastest,
+    /// Latency frames as given
+    Custom(u32),
+}
+
+impl GameSpeedPreset {
+    pub fn to_latency_frames(self) -> u32 {
+        let frame_ms = match self {
+            GameSpeedPreset::Slowest => 167,
+            GameSpeedPreset::Slower => 111,
+            GameSpeedPreset::Slow => 83,
+            GameSpeedPreset::Normal => 67,
+            GameSpeedPreset::Fast => 56,
+            GameSpeedPreset::Faster => 48,
+            GameSpeedPreset::Fastest => 42,
+            GameSpeedPreset::Custom(frames) => return frames,
+        };
+        // The delay of the default latency at "Fastest"
+        let delay_ms = default_latency() * 42;
+        delay_ms.div_ceil(frame_ms)
+    }
+}
+
+/// Reaching `time_out_at_frame` is reported as `time_limit_exceeded` in the results with both
+#[derive(Deserialize, Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub enum TimeoutAction {
+    /// The TM ends the game
+    #[default]
+    Kill,
+    /// The game plays out
+    Flag,
+}
+
+impl TimeoutAction {
+    /// Whether the game reached `time_out_at_frame`, judged by the `last_frame` logged by the TM.
+    /// The TM leaves the game in frame `time_out_at_frame` before logging it.
+    fn time_limit_exceeded(time_out_at_frame: Option<u32>, last_frame: Option<u32>) -> bool {
+        matches!(
+            (time_out_at_frame, last_frame),
+            (Some(limit), Some(frame)) if frame + 1 >= limit
+        )
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_headful_host_delay_ms() -> u32 {
+    5000
+}
+
+fn default_lobby_grace_secs() -> u64 {
+    30
+}
+
+impl GameConfig<GameType> {
+    fn load(paths: &Paths) -> anyhow::Result<GameConfig<GameType>> {
+        let game_toml_path = paths.config_dir.join("game.toml");
         debug!("Loading {}", game_toml_path.display());
-        let result: GameConfig = toml::from_str(
+        let result: GameConfig<GameType> = toml::from_str(
             read_to_string(game_toml_path)
                 .context("'game.toml' is missing")?
                 .as_str(),
         )
         .context("'game.toml' is invalid")?;
+        result.validate()?;
+        Ok(result)
+    }
+
+    /// Checks which don't need the bots or the StarCraft installation
+    fn validate(&self) -> anyhow::Result<()> {
+        let map_set = matches!(&self.map, Some(s) if !s.is_empty());
         ensure!(
-            result.human_host || matches!(&result.map, Some(s) if !s.is_empty()),
+            self.human_host || self.replay_mode || map_set,
             "Map must be set for bot-hosted games"
         );
-        if let Some(map_path) = result.map.as_ref().map(Path::new) {
-            let map_path_rel = starcraft_path.join(map_path);
+        ensure!(
+            !self.map_required_for_human_host || map_set,
+            "Map must be set, 'map_required_for_human_host' is enabled"
+        );
+        if self.replay_mode {
+            self.validate_replay_mode()?;
+        }
+        if !self.computer_races.is_empty() {
+            // BWAPI only adds computers in single player games, there is no lobby to join
+            let bots = self.game_type.bots().len();
             ensure!(
-                map_path.is_absolute() && map_path.exists() || map_path_rel.exists(),
-                "Could not find map '{}'",
-                map_path.to_string_lossy()
+                bots == 1,
+                "'computer_races' are only supported in single player games, but {bots} bots are configured"
+            );
+            ensure!(
+                self.min_players.is_none()
+                    && self.max_players.is_none()
+                    && !self.allow_human_observer,
+                "'min_players', 'max_players' and 'allow_human_observer' can't be used with 'computer_races', the game is played in single player"
             );
         }
-        Ok(result)
+        if let (Some(map), false) = (&self.map, self.auto_rename_maps) {
+            check_map_file_name(map)?;
+        }
+        Ok(())
     }
-}
 
-#[derive(Deserialize, Debug)]
-pub enum TournamentModule {
-    None,
-    Default,
-    Custom { prefix: String },
+    /// Replays are played back by bwheadless, so only a single headless bot can analyze them
+    fn validate_replay_mode(&self) -> anyhow::Result<()> {
+        ensure!(
+            self.replay.is_some(),
+            "'replay_mode' requires 'replay' to be set"
+        );
+        ensure!(
+            !self.human_host,
+            "'replay_mode' can't be used in games hosted by a human"
+        );
+        let bots = self.game_type.bots();
+        ensure!(
+            bots.len() == 1,
+            "Replays are analyzed by a single bot, but {} are configured",
+            bots.len()
+        );
+        if let Some(bot) = bots
+            .iter()
+            .find(|bot| !matches!(bot.headful, HeadfulMode::Off))
+        {
+            bail!(
+                "'{}' is headful, but replays can only be analyzed headless",
+                bot.name
+            );
+        }
+        Ok(())
+    }
+
+    /// Validates the game type against the default StarCraft installation, see `ValidatedGameType`
+    fn validate_game_type(self, starcraft_path: &Path) -> anyhow::Result<GameConfig> {
+        let GameConfig {
+            game_type,
+            map,
+            game_name,
+            game_name_auto,
+            human_host,
+            human_speed,
+            latency_frames,
+            speed_preset,
+            time_out_at_frame,
+            timeout_action,
+            require_all_bots_ready,
+            drop_players,
+            replay_mode,
+            replay,
+            sound,
+            log_dir,
+            announce,
+            map_required_for_human_host,
+            map_hint,
+            auto_rename_maps,
+            random_seed,
+            computer_races,
+            min_players,
+            max_players,
+            lobby_grace_secs,
+            allow_human_observer,
+            bot_launch_pause_ms,
+            headful_host_delay_ms,
+            lobby_timeout_secs,
+            tournament_module,
+            frame_limits,
+            reporters,
+            reporter,
+            replay_path,
+        } = self;
+        Ok(GameConfig {
+            game_type: ValidatedGameType::try_from((game_type, starcraft_path))?,
+            map,
+            game_name,
+            game_name_auto,
+            human_host,
+            human_speed,
+            latency_frames,
+            speed_preset,
+            time_out_at_frame,
+            timeout_action,
+            require_all_bots_ready,
+            drop_players,
+            replay_mode,
+            replay,
+            sound,
+            log_dir,
+            announce,
+            map_required_for_human_host,
+            map_hint,
+            auto_rename_maps,
+            random_seed,
+            computer_races,
+            min_players,
+            max_players,
+            lobby_grace_secs,
+            allow_human_observer,
+            bot_launch_pause_ms,
+            headful_host_delay_ms,
+            lobby_timeout_secs,
+            tournament_module,
+            frame_limits,
+            reporters,
+            reporter,
+            replay_path,
+        })
+    }
 }
 
-impl Default for TournamentModule {
-    fn default() -> Self {
-        Self::Default
+impl<G> GameConfig<G> {
+    /// The minimum and maximum number of players of a game with the given number of bots, in a
+    /// game type allowing `max_game_players`
+    fn lobby_players(
+        &self,
+        bots: usize,
+        max_game_players: usize,
+    ) -> anyhow::Result<(usize, usize)> {
+        ensure!(
+            !self.human_host || (self.min_players.is_none() && self.max_players.is_none()),
+            "'min_players' and 'max_players' can only be used in games hosted by a bot"
+        );
+        ensure!(
+            !self.human_host || !self.allow_human_observer,
+            "'allow_human_observer' can only be used in games hosted by a bot, the human host can watch already"
+        );
+        let min_players = self.min_players.unwrap_or(bots);
+        let max_players =
+            self.max_players.unwrap_or(min_players) + usize::from(self.allow_human_observer);
+        ensure!(
+            min_players >= bots,
+            "'min_players = {min_players}' is less than the {bots} bots, the game could start without all of them"
+        );
+        ensure!(
+            min_players <= max_players,
+            "'min_players = {min_players}' is greater than 'max_players = {max_players}'"
+        );
+        let computers = self.computer_races.len();
+        ensure!(
+            max_players + computers <= max_game_players,
+            "{max_players} players and {computers} computers don't fit into a game of {max_game_players} players"
+        );
+        Ok((min_players, max_players))
+    }
+
+    /// `latency_frames`, unless overridden by `speed_preset`
+    fn effective_latency_frames(&self) -> u32 {
+        self.speed_preset
+            .map(GameSpeedPreset::to_latency_frames)
+            .unwrap_or(self.latency_frames)
+    }
+
+    /// The map told to the bots with `MAP_ENV_VAR`, `map` takes precedence over `map_hint`
+    fn announced_map(&self) -> Option<&str> {
+        self.map
+            .as_deref()
+            .filter(|map| !map.is_empty())
+            .or(self.map_hint.as_deref())
+    }
+
+    /// The name of the game to create/join, see `game_name_auto`. BWAPI can't create games with
+    /// names differing from the player name in LAN, so the hosting bot's name is a good default.
+    fn default_game_name(&self, host_name: Option<&str>) -> String {
+        match (&self.game_name, host_name) {
+            (Some(game_name), _) => game_name.clone(),
+            (None, Some(host_name)) if self.game_name_auto => host_name.to_string(),
+            _ => "shotgun".to_string(),
+        }
+    }
+
+    /// The configured log folder of a bot, defaults to the 'logs' folder of the bot
+    fn bot_log_dir(
+        &self,
+        paths: &Paths,
+        bot_name: &str,
+        bot_path: &Path,
+        game_id: &str,
+    ) -> PathBuf {
+        match &self.log_dir {
+            Some(template) => paths.home.join(expand_template(
+                template,
+                &[("bot", bot_name), ("game_id", game_id)],
+            )),
+            None => bot_path.join("logs"),
+        }
+    }
+
+    /// The `announce` template with '{bots}' (ie. "BananaBrain vs Stardust"), '{map}' (file name
+    /// without extension) and '{game_id}' replaced
+    fn announcement(&self, bots: &[&str], game_id: &str) -> Option<String> {
+        let map = self
+            .announced_map()
+            .and_then(|map| Path::new(map).file_stem())
+            .map(|map| map.to_string_lossy().to_string())
+            .unwrap_or_default();
+        self.announce.as_ref().map(|template| {
+            expand_template(
+                template,
+                &[
+                    ("bots", &bots.join(" vs ")),
+                    ("map", &map),
+                    ("game_id", game_id),
+                ],
+            )
+        })
+    }
+
+    /// Replaces a `map` which is not an existing path with the map found by `find_map`. If
+    /// multiple maps match, the user is asked for a more specific name when `interactive`.
+    fn resolve_map(&mut self, starcraft_path: &Path, interactive: bool) -> anyhow::Result<()> {
+        let Some(mut map) = self.map.clone().filter(|map| !map.is_empty()) else {
+            return Ok(());
+        };
+        let map_path = Path::new(&map);
+        if map_path.is_absolute() && map_path.exists() || starcraft_path.join(map_path).exists() {
+            return Ok(());
+        }
+        loop {
+            let mut found = find_map(&map, starcraft_path)?;
+            match found.len() {
+                0 => anyhow::bail!(
+                    "Could not find a map matching '{map}' in '{}'",
+                    starcraft_path.join("maps").display()
+                ),
+                1 => {
+                    let found = found.remove(0);
+                    let found = found.strip_prefix(starcraft_path).unwrap_or(&found);
+                    info!("Using map '{}' for '{map}'", found.display());
+                    self.map = Some(found.to_string_lossy().to_string());
+                    return Ok(());
+                }
+                _ => {
+                    let candidates: Vec<_> = found
+                        .iter()
+                        .map(|it| it.strip_prefix(starcraft_path).unwrap_or(it))
+                        .map(|it| format!("- {}", it.display()))
+                        .collect();
+                    let candidates = candidates.join("\n");
+                    ensure!(
+                        interactive,
+                        "Multiple maps match '{map}', please be more specific:\n{candidates}"
+                    );
+                    warn!("Multiple maps match '{map}':\n{candidates}");
+                    warn!("Please enter a more specific name:");
+                    let mut input = String::new();
+                    std::io::stdin().read_line(&mut input)?;
+                    if !input.trim().is_empty() {
+                        map = input.trim().to_string();
+                    }
+                }
+            }
+        }
+    }
+
+    /// The map to use instead of `map` in the given installation, if BWAPI can't handle its file
+    /// name (see `auto_rename_maps`)
+    fn renamed_map(&self, starcraft_path: &Path) -> anyhow::Result<Option<String>> {
+        let Some(map) = self.map.as_ref().filter(|map| !map.is_empty()) else {
+            return Ok(None);
+        };
+        match check_map_file_name(map) {
+            Ok(()) => Ok(None),
+            Err(_) if self.auto_rename_maps => {
+                copy_sanitized_map(map, starcraft_path, "BWAPI can't handle its file name")
+                    .map(Some)
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    /// The map to use instead of `map`, if a headful host can't select it with auto_menu (see
+    /// `auto_menu_map_problem`). Without `auto_rename_maps`, this fails instead of stalling in the
+    /// map selection.
+    fn auto_menu_map(&self, starcraft_path: &Path) -> anyhow::Result<Option<String>> {
+        let Some(map) = self.map.as_ref().filter(|map| !map.is_empty()) else {
+            return Ok(None);
+        };
+        let Some(problem) = auto_menu_map_problem(map, starcraft_path) else {
+            return Ok(None);
+        };
+        ensure!(
+            self.auto_rename_maps,
+            "Map '{map}' {problem}, the headful host would stop at the map selection. Please move the map or set 'auto_rename_maps = true'"
+        );
+        let renamed = copy_sanitized_map(
+            map,
+            starcraft_path,
+            &format!("it {problem}, which the headful host can't select"),
+        )?;
+        if let Some(problem) = auto_menu_map_problem(&renamed, starcraft_path) {
+            bail!("Map '{renamed}' {problem}, the headful host would stop at the map selection");
+        }
+        Ok(Some(renamed))
+    }
+
+    fn validate_map(&self, starcraft_path: &Path) -> anyhow::Result<()> {
+        if let Some(replay) = self.replay.as_ref().filter(|_| self.replay_mode) {
+            ensure!(
+                starcraft_path.join(replay).exists(),
+                "Could not find replay '{}' in '{}'",
+                replay.display(),
+                starcraft_path.display()
+            );
+        }
+        if let Some(map_path) = self.map.as_ref().map(Path::new) {
+            let map_path_rel = starcraft_path.join(map_path);
+            ensure!(
+                map_path.is_absolute() && map_path.exists() || map_path_rel.exists(),
+                "Could not find map '{}' in '{}'",
+                map_path.to_string_lossy(),
+                starcraft_path.to_string_lossy()
+            );
+        }
+        Ok(())
     }
 }
 
@@ -144,18 +1065,113 @@ struct BotDefinition {
     executable: Option<String>,
     #[serde(default)]
     tournament_module: TournamentModule,
+    /// Client bots exceeding this limit will be killed
+    memory_limit_mb: Option<u64>,
+    /// Time the client bot gets to connect to BWAPI
+    connect_timeout_secs: Option<u64>,
+    #[serde(default)]
+    bwapi: BwapiConfig,
+    /// StarCraft installation to use for this bot (relative to the bot folder)
+    starcraft_path: Option<PathBuf>,
+    /// Pick the most recently modified binary, if multiple are found in 'bwapi-data/AI'
+    #[serde(default)]
+    prefer_newest: bool,
+    /// Entry module (relative to 'bwapi-data/AI'), if there are helper dlls. `executable` takes precedence.
+    ai_module: Option<String>,
+    /// File name patterns (ie. 'opencv*.dll') to skip when searching for the bot binary
+    #[serde(default)]
+    ignore: Vec<String>,
+    /// Command line arguments, only supported for exe/jar bots (appended after the jar file)
+    #[serde(default, alias = "executable_args")]
+    args: Vec<String>,
+    /// Races the bot can play, empty means all races
+    supported_races: Option<Vec<Race>>,
+    /// Oldest BWAIShotgun version supporting all settings used by the bot
+    min_shotgun_version: Option<semver::Version>,
+    /// How many instances of the bot can play in the same game (ie. because of shared files)
+    max_instances: Option<usize>,
+    /// Program (relative to the bot folder) and arguments run by 'bwaishotgun probe'
+    probe_command: Option<Vec<String>>,
 }
 
-#[derive(Debug, Copy, Clone, Eq, PartialEq)]
-pub enum Race {
-    Protoss,
-    Terran,
-    Zerg,
-    Random,
-}
+impl BotDefinition {
+    /// Reads 'bot.toml' of the bot in `bot_folder`
+    fn load(name: &str, bot_folder: &Path) -> anyhow::Result<Self> {
+        Ok(toml::from_str(
+            read_to_string(bot_folder.join("bot.toml"))
+                .with_context(|| {
+                    format!(
+                        "Could not read 'bot.toml' for bot '{}' in: '{}'",
+                        name,
+                        bot_folder.to_string_lossy(),
+                    )
+                })?
+                .as_str(),
+        )?)
+    }
 
-impl<'d> Deserialize<'d> for Race {
-    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    /// The bot binary: `executable`, `ai_module` or the one found in 'bwapi-data/AI'
+    fn find_binary(
+        &self,
+        paths: &Paths,
+        path: &Path,
+        bot_binary_scan_depth: usize,
+    ) -> anyhow::Result<Binary> {
+        let bwapi_data_path = path.join("bwapi-data");
+        #[allow(unused_mut)]
+        let mut ai_module_path = bwapi_data_path.components();
+        // Workaround BWAPI 3.7.x "strangeness" of removing ":" ..., we'll do it for windows only
+        // for obvious reasons
+        #[cfg(target_os = "windows")]
+        ai_module_path.next();
+        let ai_module_path = ai_module_path.as_path().join("AI");
+
+        let bot_binary = self.executable.as_deref().and_then(|s| {
+            // First try from bot path
+            Binary::from_path(path.join(s).as_path())
+                // Then from base path
+                .or_else(|| Binary::from_path(paths.home.join(s).as_path()))
+        });
+        if let Some(bot_binary) = bot_binary {
+            Ok(bot_binary)
+        } else if let Some(ai_module) = &self.ai_module {
+            // Then the explicitly named module in the AI folder
+            ensure!(
+                bwapi_data_path.join("AI").join(ai_module).exists(),
+                "Could not find ai_module '{}' in 'bwapi-data/AI'",
+                ai_module
+            );
+            Binary::from_path(&ai_module_path.join(ai_module))
+                .with_context(|| format!("'{ai_module}' is not a dll, jar or exe"))
+        } else {
+            // Lastly search
+            Binary::search(
+                ai_module_path.as_path(),
+                self.prefer_newest,
+                &self.ignore,
+                bot_binary_scan_depth,
+            )
+            .context("Could not find bot binary in 'bwapi-data/AI'")
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Race {
+    Protoss,
+    Terran,
+    Zerg,
+    Random,
+}
+
+impl Race {
+    pub fn all() -> [Race; 4] {
+        [Race::Protoss, Race::Terran, Race::Zerg, Race::Random]
+    }
+}
+
+impl<'d> Deserialize<'d> for Race {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
         D: Deserializer<'d>,
     {
@@ -172,6 +1188,15 @@ impl<'d> Deserialize<'d> for Race {
     }
 }
 
+impl Serialize for Race {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
 impl Display for Race {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(
@@ -187,65 +1212,141 @@ impl Display for Race {
     }
 }
 
-/// bwaishotgun base folder
-pub fn base_folder() -> PathBuf {
-    std::env::current_exe()
-        .expect("Could not find executable")
-        .parent()
-        .expect("BWAIShotgun folder does not exist")
-        .to_owned()
+pub struct BotProcess {
+    result: BotResult,
+    bwheadless: Child,
+    bot: Option<Child>,
+    restart: Option<BotRestart>,
+    tm_dir: PathBuf,
+    /// Where StarCraft writes the replays of the bot
+    replay_dir: PathBuf,
+    log_dir: PathBuf,
+    memory_limit_mb: Option<u64>,
+    /// Not yet set title of the StarCraft window
+    window_title: Option<String>,
+    /// When the TM of this bot reported the first frame
+    game_started_at: Option<Instant>,
+    server_exited_at: Option<Instant>,
+    /// A crash dialog was detected and StarCraft killed, it might take a moment to disappear
+    crash_dialog_found: bool,
+    finished: bool,
 }
 
-/// tools folder
-pub fn tools_folder() -> PathBuf {
-    base_folder().join("tools")
+/// Client bots crashing more often than this are not restarted anymore
+const MAX_BOT_RESTARTS: u32 = 3;
+
+/// Everything required to relaunch a crashed client bot
+pub struct BotRestart {
+    binary: Binary,
+    args: Vec<String>,
+    working_dir: PathBuf,
+    log_dir: PathBuf,
+    map: Option<String>,
+    attempts: u32,
 }
 
-pub fn download_folder() -> anyhow::Result<PathBuf> {
-    let download_folder = base_folder().join("download");
-    create_dir_all(&download_folder)?;
-    Ok(download_folder)
+impl BotRestart {
+    fn spawn(
+        &self,
+        wrapper: &ExecutionWrapper,
+        java: &ComponentInstallation,
+    ) -> anyhow::Result<Child> {
+        let mut cmd = client_bot_command(&self.binary, &self.args, wrapper, java)?
+            .ok_or_else(|| anyhow!("DLL bots cannot be restarted"))?;
+        let log = |name| {
+            OpenOptions::new()
+                .append(true)
+                .create(true)
+                .open(self.log_dir.join(name))
+        };
+        cmd.current_dir(&self.working_dir)
+            .stdout(log("bot_out.log")?)
+            .stderr(log("bot_err.log")?);
+        if let Some(map) = &self.map {
+            cmd.env(MAP_ENV_VAR, map);
+        }
+        Ok(cmd.spawn()?)
+    }
 }
 
-pub struct BotProcess {
-    bwheadless: Child,
-    bot: Option<Child>,
+/// Command to launch a client bot, `None` for DLL bots which are loaded by BWAPI
+fn client_bot_command(
+    binary: &Binary,
+    args: &[String],
+    wrapper: &ExecutionWrapper,
+    java: &ComponentInstallation,
+) -> anyhow::Result<Option<Command>> {
+    let mut cmd = match binary {
+        Binary::Dll(_) => return Ok(None),
+        Binary::Jar(jar) => {
+            let mut cmd = wrapper.wrap_executable(java.to_path()?);
+            cmd.arg("-jar").arg(jar);
+            cmd
+        }
+        Binary::Exe(exe) => wrapper.wrap_executable(exe),
+    };
+    cmd.args(args);
+    Ok(Some(cmd))
 }
 
 #[derive(Debug)]
 pub struct PreparedBot {
     binary: Binary,
     tournament_module: Option<String>,
+    /// The tournament module, which was skipped because it is missing
+    missing_tournament_module: Option<String>,
     supports_character_name: bool,
     race: Race,
     name: String,
     working_dir: PathBuf,
     log_dir: PathBuf,
     headful: HeadfulMode,
+    window_title: Option<String>,
+    order: Option<u32>,
+    host: bool,
+    memory_limit_mb: Option<u64>,
+    connect_timeout_secs: Option<u64>,
+    bwapi_version: Option<BwapiVersion>,
+    bwapi_config: BwapiConfig,
+    extra_bwapi_ini: IniSections,
+    starcraft_path: PathBuf,
+    args: Vec<String>,
+    prepare_time: Duration,
 }
 
 impl PreparedBot {
+    #[allow(clippy::too_many_arguments)]
     fn prepare(
+        paths: &Paths,
         config: &BotLaunchConfig,
         path: &Path,
         definition: &BotDefinition,
+        default_starcraft_path: &Path,
+        log_dir: PathBuf,
+        tournament_module_override: Option<&TournamentModule>,
+        bot_binary_scan_depth: usize,
+        auto_truncate_player_names: bool,
+        tm_missing: TmMissing,
+        warnings: &mut Vec<String>,
     ) -> anyhow::Result<Self> {
+        let started = Instant::now();
         let bwapi_data_path = path.join("bwapi-data");
-        #[allow(unused_mut)]
-        let mut ai_module_path = bwapi_data_path.components();
-        // Workaround BWAPI 3.7.x "strangeness" of removing ":" ..., we'll do it for windows only
-        // for obvious reasons
-        #[cfg(target_os = "windows")]
-        ai_module_path.next();
-        let ai_module_path = ai_module_path.as_path().join("AI");
         let read_path = bwapi_data_path.join("read");
         let write_path = bwapi_data_path.join("write");
-        let log_dir = path.join("logs");
         create_dir_all(read_path).context("Could not create read folder")?;
         create_dir_all(write_path).context("Could not create write folder")?;
         create_dir_all(&log_dir).context("Could not create log folder")?;
         let tm_path = path.join("tm");
         create_dir_all(&tm_path).context("Could not create tm folder")?;
+        // The TM silently writes no results, if it can't write to its folder
+        let sentinel = tm_path.join(".write_test");
+        File::create(&sentinel).with_context(|| {
+            format!(
+                "The tm folder '{}' is not writable, the tournament module would not be able to write results",
+                tm_path.to_string_lossy()
+            )
+        })?;
+        remove_file(&sentinel).ok();
 
         for entry in tm_path.read_dir()?.flatten().filter(|it| {
             it.path()
@@ -257,83 +1358,720 @@ impl PreparedBot {
             remove_file(entry.path()).ok();
         }
 
-        let bot_binary = definition.executable.as_deref().and_then(|s| {
-            // First try from bot path
-            Binary::from_path(path.join(s).as_path())
-                // Then from base path
-                .or_else(|| Binary::from_path(base_folder().join(s).as_path()))
-        });
-        let bot_binary = if let Some(bot_binary) = bot_binary {
-            bot_binary
-        } else {
-            // Lastly search
-            Binary::search(ai_module_path.as_path())
-                .context("Could not find bot binary in 'bwapi-data/AI'")?
-        };
+        let bot_binary = definition.find_binary(paths, path, bot_binary_scan_depth)?;
+        ensure!(
+            definition.bwapi.shared_memory || matches!(bot_binary, Binary::Dll(_)),
+            "'{}' is a client bot and requires 'shared_memory' to be enabled",
+            config.name
+        );
         let race = config.race.unwrap_or(definition.race);
+        if let Some(supported_races) = &definition.supported_races {
+            let supported_races = if supported_races.is_empty() {
+                &Race::all()[..]
+            } else {
+                supported_races
+            };
+            if !supported_races.contains(&race) {
+                warnings.push(format!(
+                    "Bot '{}' was configured to play as {} but declares support only for [{}].",
+                    config.name,
+                    race,
+                    supported_races
+                        .iter()
+                        .map(|it| it.to_string())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ));
+            }
+        }
+        let args = bot_args(definition, config, &bot_binary, warnings);
 
+        let checked = Instant::now();
+        let mut cache = PrepareCache::load(path);
         let bwapi_dll = bwapi_data_path.join("BWAPI.dll");
-        let bwapi_crc = Crc::<u32>::new(&CRC_32_ISO_HDLC).checksum(
-            std::fs::read(&bwapi_dll)
-                .with_context(|| format!("Could not check '{}'", bwapi_dll.to_string_lossy()))?
-                .as_slice(),
-        );
+        let bwapi_crc = cache.crc(&bwapi_dll)?;
         let bwapi_version = BwapiVersion::from_u32(bwapi_crc);
 
-        let tournament_module = match &definition.tournament_module {
-            TournamentModule::None => None,
-            TournamentModule::Default | TournamentModule::Custom { .. } => {
-                let prefix =
-                    if let TournamentModule::Custom { prefix } = &definition.tournament_module {
-                        prefix
-                    } else {
-                        "TM"
-                    };
-
-                if let Some(version) = &bwapi_version {
-                    let version = version.version_short();
-                    let tm_name = format!("{prefix}_{version}.dll");
-                    let tm_source_file = base_folder().join("tm").join(&tm_name);
-                    std::fs::copy(&tm_source_file, path.join(&tm_name)).with_context(|| {
-                        format!(
-                            "Could not copy tournament module: '{}'",
-                            tm_source_file.to_string_lossy(),
-                        )
-                    })?;
+        let (tournament_module_setting, reason) = match tournament_module_override {
+            Some(tournament_module) => (tournament_module, "game override"),
+            None => (&definition.tournament_module, "bot.toml"),
+        };
+        let mut missing_tournament_module = None;
+        let tournament_module = match &bwapi_version {
+            Some(version) => {
+                let tm_name = tournament_module_setting.file_name(version);
+                match tm_name.filter(|it| !paths.tm_dir.join(it).exists()) {
+                    Some(tm_name) if tm_missing != TmMissing::Error => {
+                        let message = format!(
+                            "'{}' runs without tournament module, '{}' is missing ({})",
+                            config.name,
+                            paths.tm_dir.join(&tm_name).display(),
+                            reason
+                        );
+                        if tm_missing == TmMissing::Warn {
+                            warn!("{message}");
+                        } else {
+                            info!("{message}");
+                        }
+                        missing_tournament_module = Some(tm_name);
+                    }
+                    _ => {
+                        tournament_module_setting.validate_presence(&paths.tm_dir, path, version)?
+                    }
+                }
+                if missing_tournament_module.is_some() {
+                    None
+                } else if let Some(tm_name) = tournament_module_setting.file_name(version) {
+                    let tm_source_file = paths.tm_dir.join(&tm_name);
+                    cache
+                        .copy_if_changed(&tm_source_file, &path.join(&tm_name))
+                        .with_context(|| {
+                            format!(
+                                "Could not copy tournament module: '{}'",
+                                tm_source_file.to_string_lossy(),
+                            )
+                        })?;
+                    info!(
+                        "'{}' uses tournament module '{}' ({})",
+                        config.name, tm_name, reason
+                    );
                     Some(tm_name)
                 } else {
-                    println!("Custom BWAPI.dll detected, not adding TM module");
+                    info!(
+                        "'{}' runs without tournament module ({})",
+                        config.name, reason
+                    );
                     None
                 }
             }
+            None => {
+                if !matches!(tournament_module_setting, TournamentModule::None) {
+                    info!(
+                        "'{}' uses a custom BWAPI.dll, not adding a tournament module",
+                        config.name
+                    );
+                }
+                None
+            }
+        };
+        cache.save();
+        debug!(
+            "Checked BWAPI.dll and tournament module of '{}' in {:?}, {} bytes were cached",
+            config.name,
+            checked.elapsed(),
+            cache.bytes_saved
+        );
+
+        let extra_bwapi_ini = match &config.extra_bwapi_ini {
+            Some(extra) => {
+                let extra = paths.home.join(extra);
+                parse_ini(
+                    &read_to_string(&extra)
+                        .with_context(|| format!("Could not read '{}'", extra.display()))?,
+                )
+                .with_context(|| format!("'{}' is invalid", extra.display()))?
+            }
+            None => vec![],
         };
+        let name = check_player_name(
+            config.player_name.as_ref().unwrap_or(&config.name),
+            auto_truncate_player_names,
+        )?;
+        let headful = config.headful_mode();
+        let window_title = match headful {
+            HeadfulMode::Off => None,
+            HeadfulMode::On { .. } => Some(config.window_title.clone().unwrap_or(name.clone())),
+        }
+        .filter(|title| !title.is_empty());
+        let starcraft_path = config
+            .starcraft_path
+            .as_ref()
+            .map(|it| paths.home.join(it))
+            .or_else(|| definition.starcraft_path.as_ref().map(|it| path.join(it)))
+            .unwrap_or_else(|| default_starcraft_path.to_path_buf());
+        check_bwapi_path(path, "bot folder", bwapi_version)?;
+        check_bwapi_path(&starcraft_path, "StarCraft folder", bwapi_version)?;
+        if let Binary::Dll(dll) = &bot_binary {
+            check_bwapi_path(dll, "AI module", bwapi_version)?;
+            // BWAPI 4.x reads a list of AI modules separated by ','
+            ensure!(
+                !dll.as_os_str().to_string_lossy().contains(','),
+                "The AI module '{}' contains ',', which BWAPI can't handle. Please rename it",
+                dll.display()
+            );
+        }
 
         Ok(Self {
             binary: bot_binary,
             race,
-            name: config
-                .player_name
-                .clone()
-                .unwrap_or_else(|| config.name.clone()),
+            name,
             working_dir: path.to_path_buf(),
             log_dir,
-            headful: config.headful,
+            headful,
+            window_title,
+            order: config.order,
+            host: config.host,
+            memory_limit_mb: definition.memory_limit_mb,
+            connect_timeout_secs: definition.connect_timeout_secs,
+            bwapi_version,
+            bwapi_config: definition.bwapi.clone(),
+            extra_bwapi_ini,
+            args,
+            starcraft_path,
             tournament_module,
+            missing_tournament_module,
             supports_character_name: !matches!(
                 bwapi_version,
                 Some(BwapiVersion::Bwapi375 | BwapiVersion::Bwapi412)
             ),
+            prepare_time: started.elapsed(),
         })
     }
 }
 
+/// Checks a StarCraft installation used by at least one bot, returns found problems which are
+/// not fatal
+fn check_installation(
+    starcraft_path: &Path,
+    game_config: &GameConfig,
+) -> anyhow::Result<Vec<String>> {
+    let starcraft_exe = starcraft_path.join("StarCraft.exe");
+    ensure!(
+        starcraft_exe.exists(),
+        "Could not locate 'StarCraft.exe' in configured location: '{}'",
+        starcraft_exe.to_string_lossy()
+    );
+    game_config.validate_map(starcraft_path)?;
+    Ok(match snp_support(starcraft_path) {
+        SnpSupport::Full => vec![],
+        SnpSupport::Limited => vec![format!("The 'SNP_DirectIP.snp' in '{}' might not support more than ~6 bots per game. Overwrite with the included 'SNP_DirectIP.snp' file to support more.", starcraft_path.display())],
+        SnpSupport::Missing => vec![format!("Could not find 'SNP_DirectIP.snp' in '{}', please copy the provided one or install BWAPI.", starcraft_path.display())],
+    })
+}
+
+/// False in CI or without a terminal, nothing must wait for user input then
+fn is_interactive() -> bool {
+    std::env::var_os("CI").is_none() && std::io::stdin().is_terminal()
+}
+
+/// Prints the pre-flight warnings and optionally waits for the user to acknowledge them. Never
+/// blocks without an interactive terminal (ie. in CI).
+fn report_warnings(warnings: &[String], confirm: bool) -> anyhow::Result<()> {
+    if warnings.is_empty() {
+        return Ok(());
+    }
+    warn!("Pre-flight checks found {} problem(s):", warnings.len());
+    for warning in warnings {
+        warn!("- {warning}");
+    }
+    if confirm && is_interactive() {
+        warn!("Press Enter to launch anyways (or ctrl+c to abort)");
+        std::io::stdin().read_line(&mut String::new())?;
+    }
+    Ok(())
+}
+
+impl PreparedBot {
+    /// Binary type, as shown in logs and results
+    fn bot_type_label(&self) -> &'static str {
+        match self.binary {
+            Binary::Dll(_) => "DLL",
+            Binary::Jar(_) => "JAR",
+            Binary::Exe(_) => "EXE",
+        }
+    }
+
+    /// DLL bots are loaded by BWAPI into StarCraft, no separate bot process is launched
+    fn is_headless_client(&self) -> bool {
+        matches!(self.binary, Binary::Dll(_))
+    }
+
+    /// JAR/EXE bots are separate processes, which connect to an already running BWAPI server
+    fn needs_server_process(&self) -> bool {
+        !self.is_headless_client()
+    }
+
+    fn launch_constraints(&self) -> LaunchConstraints {
+        LaunchConstraints {
+            name: self.name.clone(),
+            is_dll: self.is_headless_client(),
+            host: self.host,
+            order: self.order,
+            force: None,
+        }
+    }
+}
+
+/// The arguments of 'bot.toml' followed by those of the game. DLL bots are loaded by BWAPI and
+/// get none, a warning is added instead.
+fn bot_args(
+    definition: &BotDefinition,
+    config: &BotLaunchConfig,
+    bot_binary: &Binary,
+    warnings: &mut Vec<String>,
+) -> Vec<String> {
+    let args: Vec<_> = definition
+        .args
+        .iter()
+        .chain(config.args.iter())
+        .cloned()
+        .collect();
+    if !args.is_empty() && matches!(bot_binary, Binary::Dll(_)) {
+        warnings.push(format!(
+            "'{}' is a DLL bot, which is loaded by BWAPI - ignoring its command line arguments",
+            config.name
+        ));
+        return vec![];
+    }
+    args
+}
+
+/// Puts the bots into launch order, see `plan_launch_order`. Returns why each bot was put at its
+/// position. `bots` are in the order of their forces, see `GameType::bots`.
+fn order_bots(
+    bots: &mut Vec<PreparedBot>,
+    force_sizes: Option<(usize, usize)>,
+    human_host: bool,
+) -> anyhow::Result<Vec<Vec<String>>> {
+    let mut constraints: Vec<_> = bots.iter().map(PreparedBot::launch_constraints).collect();
+    // With bots missing, `GameRole::plan` rejects the game
+    if let Some((top, _)) = force_sizes.filter(|(top, bottom)| top + bottom == bots.len()) {
+        for (index, constraint) in constraints.iter_mut().enumerate() {
+            constraint.force = Some(usize::from(index >= top));
+        }
+    }
+    let launch_order = plan_launch_order(&constraints, human_host)?;
+    let mut unordered: Vec<_> = bots.drain(..).map(Some).collect();
+    Ok(launch_order
+        .into_iter()
+        .map(|slot| {
+            bots.push(unordered[slot.bot].take().expect("Bot launched twice"));
+            slot.reasons
+        })
+        .collect())
+}
+
+fn apply_log_retention(retention: &LogRetention, folder: &Path, filter: &dyn Fn(&Path) -> bool) {
+    match retention.apply(folder, filter) {
+        Ok(deleted) => {
+            for path in deleted {
+                debug!("Deleted '{}' (log retention)", path.display());
+            }
+        }
+        Err(err) => warn!(
+            "Could not apply the log retention to '{}': {err:?}",
+            folder.display()
+        ),
+    }
+}
+
+/// Moves the crash dumps of the bot's StarCraft process into its log folder, or extracts the
+/// crash backtrace under Wine
+fn collect_crash_evidence(
+    bot: &BotProcess,
+    local_dumps: Option<&LocalDumps>,
+    wrapper: &ExecutionWrapper,
+) -> Vec<PathBuf> {
+    let collected = match (local_dumps, wrapper) {
+        (Some(local_dumps), _) => local_dumps.collect(bot.bwheadless.id(), &bot.log_dir),
+        (None, ExecutionWrapper::Wine) => {
+            crash_dumps::collect_wine_backtrace(&bot.log_dir.join("game_err.log"))
+                .map(|backtrace| backtrace.into_iter().collect())
+        }
+        (None, _) => Ok(vec![]),
+    };
+    collected.unwrap_or_else(|err| {
+        warn!(
+            "Could not collect crash dumps of '{}': {err}",
+            bot.result.name
+        );
+        vec![]
+    })
+}
+
+/// The `post_game_command`, run in the BWAIShotgun folder. It gets the game id as
+/// `BWAISHOTGUN_GAME_ID` and the bots which crashed (comma separated) as `BWAISHOTGUN_CRASH`, which
+/// is only set if a crash was detected.
+fn build_post_game_command(
+    command: &[String],
+    home: &Path,
+    game_result: &GameResult,
+) -> anyhow::Result<Command> {
+    let Some((program, args)) = command.split_first() else {
+        bail!("'post_game_command' is empty");
+    };
+    let local_program = home.join(program);
+    let mut cmd = if local_program.exists() {
+        Command::new(local_program)
+    } else {
+        Command::new(program)
+    };
+    cmd.args(args)
+        .current_dir(home)
+        .env("BWAISHOTGUN_GAME_ID", &game_result.game_id);
+    let crashed: Vec<_> = game_result
+        .bots
+        .iter()
+        .filter(|it| it.crashed())
+        .map(|it| it.name.as_str())
+        .collect();
+    if !crashed.is_empty() {
+        cmd.env("BWAISHOTGUN_CRASH", crashed.join(","));
+    }
+    Ok(cmd)
+}
+
+fn run_post_game_command(command: &[String], home: &Path, game_result: &GameResult) {
+    match build_post_game_command(command, home, game_result).and_then(|mut cmd| Ok(cmd.status()?))
+    {
+        Ok(status) if !status.success() => warn!("'post_game_command' failed: {status}"),
+        Ok(_) => (),
+        Err(err) => error!("Could not run 'post_game_command': {err:?}"),
+    }
+}
+
+/// Checks the frame times of a bot against the `frame_limits`, returns the violated rule and a
+/// diagnostic if the TM came to a different verdict. BWAIShotgun's check wins.
+fn check_frame_limits(
+    limits: &FrameLimits,
+    tm_dir: &Path,
+    bot: &str,
+) -> (Option<String>, Option<String>) {
+    let Some(violation) = limits.check(tm_dir) else {
+        return (None, None);
+    };
+    let mismatch = tm_results::frame_limits_mismatch(
+        tm_results::read_tm_disqualification(tm_dir),
+        violation.map(|(rule, _)| rule),
+    );
+    if let Some(mismatch) = &mismatch {
+        warn!("'{bot}': {mismatch}");
+    }
+    let disqualification = violation.map(|(rule, slow_frames)| {
+        let disqualification = format!("{rule}, but {slow_frames} were");
+        warn!("'{bot}' exceeded the frame limits: {disqualification}");
+        disqualification
+    });
+    (disqualification, mismatch)
+}
+
+/// Everything needed to launch a bot, which is the same for all bots of a game
+struct LaunchContext<'a> {
+    game_config: &'a GameConfig,
+    game_role: GameRole,
+    game_id: &'a str,
+    paths: &'a Paths,
+    wrapper: &'a ExecutionWrapper,
+    java_component: &'a ComponentInstallation,
+    replay_path: Option<&'a String>,
+    validate_bwapi_ini: bool,
+    min_players: usize,
+    max_players: usize,
+    injectory_wait: bool,
+    injectory_kill_on_exit: bool,
+    oldbwapi_dll: &'a Path,
+    injectory_exe: &'a Path,
+    bwheadless_exe: &'a Path,
+    bwheadless_capabilities: BwHeadlessCapabilities,
+    announcement: Option<&'a str>,
+    connect_timeout_secs: Option<u64>,
+    memory_limit_mb: Option<u64>,
+}
+
+/// A bot with its StarCraft (and client process) running and connected
+struct BotLaunchResult {
+    name: String,
+    bwapi_version: Option<BwapiVersion>,
+    /// Name of the game the bot hosts or joins, a headful host renames the game
+    game_name: String,
+    /// Folder of a headful host, which might contain a link to the maps folder or map copies
+    map_link_dir: Option<PathBuf>,
+    process: BotProcess,
+}
+
+/// Starts StarCraft for the bot at `index` in launch order and, for client bots, the bot process.
+/// Returns once a client bot connected to BWAPI.
+fn launch_bot(
+    bot: PreparedBot,
+    index: usize,
+    game_name: &str,
+    context: &LaunchContext,
+    game_table_access: &mut GameTableAccess,
+    timer: &mut PhaseTimer,
+) -> anyhow::Result<BotLaunchResult> {
+    let game_config = context.game_config;
+    let host = context.game_role.is_host(index);
+    let binary_type = bot.bot_type_label();
+    let tm_dir = bot.working_dir.join("tm");
+    let replay_dir = replay_folder(
+        &bot.working_dir,
+        context
+            .replay_path
+            .map_or(DEFAULT_REPLAY_PATH, |it| it.as_str()),
+    );
+    let bwapi_ini = BwapiIni::path(&bot.working_dir, bot.bwapi_version, context.game_id);
+    let bot_setup = BotSetup {
+        starcraft_exe: bot.starcraft_path.join("StarCraft.exe"),
+        starcraft_path: bot.starcraft_path.clone(),
+        bot_base_path: bot.working_dir.clone(),
+        tournament_module: bot.tournament_module.as_ref().map(|s| s.into()),
+        player_name: bot.name.clone(),
+        race: bot.race,
+        wrapper: context.wrapper.clone(),
+        bot_binary: bot.binary.clone(),
+        replay_path: context.replay_path.cloned(),
+        bwapi_config: bot.bwapi_config.clone(),
+        bwapi_version: bot.bwapi_version,
+        tools_dir: context.paths.tools_dir.clone(),
+        bwapi_ini,
+        validate_bwapi_ini: context.validate_bwapi_ini,
+        extra_bwapi_ini: bot.extra_bwapi_ini.clone(),
+    };
+    let tournament_module = bot_setup.tournament_module.clone();
+    let mut game_name = game_name.to_string();
+    let mut map_link_dir = None;
+    let bwapi_launcher: Box<dyn LaunchBuilder> = if !matches!(bot.headful, HeadfulMode::Off) {
+        if host {
+            // Headful + Host => All other bots need to join the game with this bots player name
+            if bot.supports_character_name {
+                game_name = bot.name.clone();
+            } else {
+                warn!("Headful hosting bot uses very old BWAPI version, please ensure there's only one character with the name 'BWAPI'.");
+                game_name = "BWAPI".to_string();
+            }
+            map_link_dir = Some(bot.working_dir.clone());
+        }
+        Box::new(Injectory {
+            bot_setup,
+            role: context.game_role.bot_role(
+                index,
+                &game_name,
+                game_config.map.clone(),
+                context.min_players,
+                context.max_players,
+            ),
+            wmode: matches!(bot.headful, HeadfulMode::On { no_wmode, .. } if !no_wmode),
+            sound: game_config.sound
+                && matches!(bot.headful, HeadfulMode::On { no_sound, ..} if !no_sound),
+            game_speed: if game_config.human_speed { -1 } else { 0 },
+            wait_for_exit: context.injectory_wait,
+            kill_on_exit: context.injectory_kill_on_exit,
+            oldbwapi_dll: context.oldbwapi_dll.to_path_buf(),
+            injectory_exe: context.injectory_exe.to_path_buf(),
+        })
+    } else {
+        Box::new(BwHeadless {
+            bot_setup,
+            bwheadless_exe: context.bwheadless_exe.to_path_buf(),
+            capabilities: context.bwheadless_capabilities,
+            connect_mode: if let (true, Some(replay)) =
+                (game_config.replay_mode, &game_config.replay)
+            {
+                BwHeadlessConnectMode::Replay {
+                    replay: replay.clone(),
+                }
+            } else {
+                BwHeadlessConnectMode::Role(context.game_role.bot_role(
+                    index,
+                    &game_name,
+                    game_config.map.clone(),
+                    context.min_players,
+                    context.max_players,
+                ))
+            },
+        })
+    };
+    info!(
+        "{} with '{}' ({}){}",
+        if game_config.replay_mode {
+            "Analyzing the replay"
+        } else if host {
+            "Hosting game"
+        } else {
+            "Joining game"
+        },
+        bot.name,
+        binary_type,
+        tournament_module
+            .map(|tm| format!(" (with tournament module '{}')", tm.to_string_lossy()))
+            .unwrap_or_default()
+    );
+
+    let mut cmd = bwapi_launcher.build_command(game_config)?;
+    cmd.stdout(File::create(bot.log_dir.join("game_out.log"))?)
+        .stderr(File::create(bot.log_dir.join("game_err.log"))?);
+    let cmd = cmd
+        .env("TM_LOG_FRAMETIMES", r"tm\frames.csv")
+        .env("TM_LOG_RESULTS", r"tm\result.csv")
+        .env("TM_LOG_UNIT_EVENTS", r"tm\unit_events.csv");
+    match (game_config.time_out_at_frame, game_config.timeout_action) {
+        (Some(time_out_at_frame), TimeoutAction::Kill) => {
+            cmd.env("TM_TIME_OUT_AT_FRAME", time_out_at_frame.to_string());
+        }
+        (_, TimeoutAction::Flag) | (None, _) => (),
+    }
+    if let Some(frame_limits) = &game_config.frame_limits {
+        cmd.env("TM_FRAME_LIMITS", frame_limits.to_env());
+    }
+    if let Some(announcement) = context.announcement {
+        cmd.env("TM_ANNOUNCE", announcement);
+    }
+    if let Some(map) = game_config.announced_map() {
+        cmd.env(MAP_ENV_VAR, map);
+    }
+    let mut bwapi_child = cmd
+        .spawn()
+        .context("Could not run bwheadless (maybe deleted/blocked by a Virus Scanner?)")?;
+
+    debug!("Spawned Starcraft with PID: {}", bwapi_child.id());
+    if game_config.bot_launch_pause_ms > 0 {
+        debug!(
+            "Pausing {}ms before continuing",
+            game_config.bot_launch_pause_ms
+        );
+        std::thread::sleep(Duration::from_millis(
+            game_config.bot_launch_pause_ms.into(),
+        ));
+    }
+    let spawned_at = Instant::now();
+    let mut connect_time = None;
+
+    let bot_out_log = File::create(bot.log_dir.join("bot_out.log"))?;
+    let bot_err_log = File::create(bot.log_dir.join("bot_err.log"))?;
+    let restart =
+        (bot.bwapi_config.auto_restart && bot.needs_server_process()).then(|| BotRestart {
+            binary: bot.binary.clone(),
+            args: bot.args.clone(),
+            working_dir: bot.working_dir.clone(),
+            log_dir: bot.log_dir.clone(),
+            map: game_config.announced_map().map(str::to_string),
+            attempts: 0,
+        });
+    let bot_process = client_bot_command(
+        &bot.binary,
+        &bot.args,
+        context.wrapper,
+        context.java_component,
+    )?
+    .map(|ref mut cmd| -> anyhow::Result<Child> {
+        // Wait for server to be ready to accept connections
+        debug!("Waiting for free slots... ");
+        retry(Fixed::from_millis(100).take(100), || {
+            if game_table_access.has_free_slot() {
+                OperationResult::Ok(())
+            } else {
+                OperationResult::Retry("BWAPI Server is not ready")
+            }
+        })
+        .map_err(|err| {
+            anyhow!(
+                "{err}\nGame table history:\n{}",
+                game_table_access.history()
+            )
+        })?;
+        timer.end_phase(format!("'{}' BWAPI server ready", bot.name));
+
+        cmd.current_dir(&bot.working_dir);
+        cmd.stdout(bot_out_log);
+        cmd.stderr(bot_err_log);
+        if let Some(map) = game_config.announced_map() {
+            cmd.env(MAP_ENV_VAR, map);
+        }
+        info!("Launching bot '{}': {:?}", bot.name, cmd);
+
+        let mut child = cmd.spawn()?;
+
+        // Wait for the configured or estimated startup time before bailing
+        let startup_time = bot
+            .connect_timeout_secs
+            .or(context.connect_timeout_secs)
+            .map(Duration::from_secs)
+            .unwrap_or_else(|| bwapi_launcher.estimated_startup_time());
+        game_table_access.wait_for_all_slots_filled(
+            &bot.name,
+            startup_time,
+            &mut bwapi_child,
+            &mut child,
+        )?;
+        connect_time = Some(spawned_at.elapsed());
+        timer.end_phase(format!("'{}' connected", bot.name));
+
+        Ok(child)
+    })
+    .transpose()?;
+    if bot_process.is_none() {
+        timer.end_phase(format!("'{}' launched", bot.name));
+    }
+    Ok(BotLaunchResult {
+        name: bot.name.clone(),
+        bwapi_version: bot.bwapi_version,
+        game_name,
+        map_link_dir,
+        process: BotProcess {
+            result: BotResult {
+                name: bot.name,
+                race: bot.race,
+                binary_type,
+                bwapi_version: bot.bwapi_version.as_ref().map(BwapiVersion::version_short),
+                launch_order: index,
+                host,
+                prepare_time: bot.prepare_time,
+                connect_time,
+                exit_code: None,
+                failure: None,
+                outcome: None,
+                diagnostics: vec![],
+                tournament_module: bot.tournament_module.clone(),
+                missing_tournament_module: bot.missing_tournament_module.clone(),
+                start_location: None,
+                crash_dumps: vec![],
+            },
+            bwheadless: bwapi_child,
+            bot: bot_process,
+            restart,
+            tm_dir,
+            replay_dir,
+            log_dir: bot.log_dir,
+            memory_limit_mb: bot.memory_limit_mb.or(context.memory_limit_mb),
+            window_title: bot.window_title,
+            game_started_at: None,
+            server_exited_at: None,
+            crash_dialog_found: false,
+            finished: false,
+        },
+    })
+}
+
 fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+    let mut paths = Paths::resolve(cli.home.clone())?;
+    let shotgun_toml = read_to_string(paths.config_dir.join("shotgun.toml")).ok();
+    let shotgun_config = if let Some(cfg) = &shotgun_toml {
+        toml::from_str(cfg.as_str()).context("'shotgun.toml' is invalid")?
+    } else {
+        ShotgunConfig::default()
+    };
+    let mut log_config = ConfigBuilder::new();
+    log_config.set_time_level(if shotgun_config.log_timestamps {
+        LevelFilter::Error
+    } else {
+        LevelFilter::Off
+    });
+    log_config.set_thread_level(if shotgun_config.log_thread_id {
+        LevelFilter::Error
+    } else {
+        LevelFilter::Off
+    });
+    if shotgun_config.log_timestamps {
+        // Might fail if the local offset can't be determined, UTC is fine then
+        let _ = log_config.set_time_offset_to_local();
+    }
     TermLogger::init(
         std::env::var("LOG_LEVEL")
             .ok()
             .and_then(|level| LevelFilter::from_str(&level).ok())
             .unwrap_or(LevelFilter::Info),
-        Config::default(),
+        log_config.build(),
         TerminalMode::Mixed,
         ColorChoice::Auto,
     )?;
@@ -342,27 +2080,121 @@ fn main() -> anyhow::Result<()> {
         env!("CARGO_PKG_NAME"),
         env!("CARGO_PKG_VERSION")
     );
+    if shotgun_toml.is_none() {
+        warn!("'shotgun.toml' not found, using defaults");
+    }
+    paths.tools_dir = shotgun_config.resolve_tools_folder(&paths);
+    ensure!(
+        shotgun_config.tools_path.is_none() || paths.tools_dir.is_dir(),
+        "'tools_path' is set to '{}', which does not exist",
+        paths.tools_dir.display()
+    );
+    if cli.is_info() {
+        info::print_info(&shotgun_config, &paths);
+        return Ok(());
+    }
+    if cli.is_env() {
+        let starcraft = starcraft_component(
+            &paths,
+            shotgun_config.starcraft_path.clone(),
+            shotgun_config.download_connections,
+        );
+        Environment::collect(
+            starcraft.installed_path().as_deref(),
+            &paths.tools_dir,
+            &shotgun_config.wrapper,
+        )
+        .print();
+        return Ok(());
+    }
+    if let Some(args) = cli.init_args() {
+        return init::write_templates(&paths, args.bot, args.race, args.force);
+    }
+    // Collected and reported at once before launching, so they don't get lost in the launch output
+    let mut warnings = vec![];
+    if shotgun_config.check_tools_integrity {
+        let problems = tools::check_tools_integrity(&paths.tools_dir);
+        for problem in problems.iter() {
+            warnings.push(format!("Tool {problem}"));
+        }
+        if !problems.is_empty() {
+            warnings.push(
+                "Please re-extract BWAIShotgun and check your antivirus software.".to_string(),
+            );
+        }
+    }
     let ShotgunConfig {
         starcraft_path,
         java_path,
         wrapper,
         replay_path,
-    } = if let Ok(cfg) = read_to_string(base_folder().join("shotgun.toml")) {
-        toml::from_str(cfg.as_str()).context("'shotgun.toml' is invalid")?
-    } else {
-        warn!("'shotgun.toml' not found, using defaults");
-        ShotgunConfig::default()
+        memory_limit_mb,
+        connect_timeout_secs,
+        bot_shutdown_grace_secs,
+        kill_on_first_exit,
+        keep_map_links,
+        confirm_warnings,
+        injectory_wait,
+        injectory_kill_on_exit,
+        collect_crash_dumps,
+        crash_detection,
+        post_game_command,
+        oldbwapi_dll_path,
+        bwheadless_exe,
+        injectory_exe,
+        copy_bwapi_ini_on_success,
+        copy_bwapi_ini_always,
+        bot_binary_scan_depth,
+        validate_bwapi_ini,
+        download_connections,
+        auto_truncate_player_names,
+        result_file,
+        metrics_file,
+        result_format,
+        tm_missing,
+        auto_quarantine,
+        log_retention,
+        ..
+    } = shotgun_config;
+    let oldbwapi_dll = match oldbwapi_dll_path {
+        Some(path) => {
+            let path = paths.home.join(path);
+            ensure!(
+                path.is_file(),
+                "'oldbwapi_dll_path' is set to '{}', which does not exist",
+                path.display()
+            );
+            path
+        }
+        None => {
+            let path = paths.tools_dir.join("oldbwapi.dll");
+            if !path.is_file() {
+                warnings.push(format!(
+                    "'{}' is missing, headful bots can't be launched. Please re-extract it from the BWAIShotgun release (https://github.com/Bytekeeper/BWAIShotgun/releases) and check your antivirus software.",
+                    path.display()
+                ));
+            }
+            path
+        }
     };
-    let starcraft_path = starcraft_component(starcraft_path).to_path()?;
-    let starcraft_exe = starcraft_path.join("StarCraft.exe");
-    let java_component = java_component(java_path);
-
-    ensure!(
-        starcraft_exe.exists(),
-        "Could not locate 'StarCraft.exe' in configured location: '{}'",
-        starcraft_exe.to_string_lossy()
-    );
+    let bwheadless_exe = tool_path(
+        &paths,
+        "bwheadless.exe",
+        cli.bwheadless_path.clone(),
+        bwheadless_exe,
+    )?;
+    let injectory_exe = tool_path(
+        &paths,
+        "injectory_x86.exe",
+        cli.injectory_path.clone(),
+        injectory_exe,
+    )?;
+    // Bots can override the installation, each one used is checked once the bots are prepared
+    let starcraft_path =
+        starcraft_component(&paths, starcraft_path, download_connections).to_path()?;
+    let java_component = java_component(&paths, java_path, download_connections);
 
+    wrapper.validate()?;
     match wrapper {
         ExecutionWrapper::Unconfigured => {
             // Currently, we don't support bot sandboxing
@@ -386,26 +2218,210 @@ fn main() -> anyhow::Result<()> {
         }
     }
 
-    let cli = Cli::parse();
+    let environment = Environment::collect(Some(&starcraft_path), &paths.tools_dir, &wrapper);
+
+    if let Some(bots) = cli.probe_bots() {
+        // Never part of a game, probes would interfere with the game table
+        return probe::probe_bots(
+            &paths,
+            bots,
+            &wrapper,
+            &java_component,
+            bot_binary_scan_depth,
+        );
+    }
 
-    let game_config = match cli.merge_into(GameConfig::load(&starcraft_path)?) {
-        Ok(config) => config,
+    let fix_registry = cli.fix_registry;
+    let quiet = cli.quiet;
+    let output_json = cli.json;
+    let skip_confirmation = cli.yes;
+    let archive_logs = cli.archive_logs.clone();
+    // Opened before the game, so an unusable file doesn't waste a whole game
+    let result_file = match &cli.results_csv {
+        Some(path) => Some((path.clone(), ResultFormat::Csv)),
+        None => result_file.map(|path| (paths.home.join(path), result_format)),
+    };
+    let mut run_reporters: Vec<_> = result_file
+        .map(|(path, format)| file_reporter(&path, format))
+        .transpose()?
+        .into_iter()
+        .collect();
+
+    let continue_on_error = cli.continue_on_error;
+    let game_configs = match &cli.manifest {
+        Some(manifest) => manifest::load_manifest(manifest)?
+            .into_iter()
+            .map(|game_config| cli.merge_into(game_config))
+            .collect(),
+        None => cli.merge_into(GameConfig::load(&paths)?).map(|it| vec![it]),
+    };
+    let game_configs = match game_configs {
+        Ok(game_configs) => game_configs,
         Err(cli::Error::ClapError(err)) => err.exit(),
     };
 
-    if let Ok(metadata) = metadata(starcraft_path.join("SNP_DirectIP.snp")) {
-        if metadata.len() != 46100 {
-            warn!("The 'SNP_DirectIP.snp' in your StarCraft installation might not support more than ~6 bots per game. Overwrite with the included 'SNP_DirectIP.snp' file to support more.");
+    let context = RunContext {
+        paths,
+        wrapper,
+        java_component,
+        replay_path,
+        memory_limit_mb,
+        connect_timeout_secs,
+        bot_shutdown_grace_secs,
+        keep_map_links,
+        confirm_warnings,
+        injectory_wait,
+        injectory_kill_on_exit,
+        collect_crash_dumps,
+        crash_detection,
+        oldbwapi_dll,
+        bwheadless_exe,
+        injectory_exe,
+        copy_bwapi_ini_on_success,
+        copy_bwapi_ini_always,
+        bot_binary_scan_depth,
+        validate_bwapi_ini,
+        auto_truncate_player_names,
+        fix_registry,
+        quiet,
+        output_json,
+        skip_confirmation,
+        archive_logs,
+        metrics_file,
+        tm_missing,
+        auto_quarantine,
+        log_retention,
+        kill_on_first_exit,
+        environment,
+    };
+    let games = game_configs.len();
+    let mut failed = 0;
+    let mut results = vec![];
+    for (index, game_config) in game_configs.into_iter().enumerate() {
+        if games > 1 {
+            info!("Running game {} of {games}", index + 1);
         }
-    } else {
-        warn!("Could not find 'SNP_DirectIP.snp' in your StarCraft installation, please copy the provided one or install BWAPI.");
+        let game_result = game_config
+            .validate_game_type(&starcraft_path)
+            .and_then(|game_config| {
+                // Opened before the game, so an unusable file doesn't waste a whole game
+                let reporters = create_reporters(
+                    &game_config.reporters,
+                    &game_config.reporter,
+                    &context.paths.home,
+                )?;
+                Ok((
+                    run_game(game_config, context.clone(), &warnings)?,
+                    reporters,
+                ))
+            });
+        match game_result {
+            Ok((game_result, mut reporters)) => {
+                report_all(&mut run_reporters, &game_result);
+                report_all(&mut reporters, &game_result);
+                if let Some(command) = &post_game_command {
+                    run_post_game_command(command, &context.paths.home, &game_result);
+                }
+                results.push(game_result);
+            }
+            Err(err) if continue_on_error => {
+                error!("Game {} of {games} failed: {err:?}", index + 1);
+                failed += 1;
+            }
+            Err(err) => return Err(err),
+        }
+    }
+    if games > 1 && !quiet && !output_json {
+        println!("{}", results::aggregate_table(&results));
     }
+    ensure!(failed == 0, "{failed} of {games} games failed");
+    Ok(())
+}
+
+/// Everything `run_game` needs from the startup, the same for each game of a run
+#[derive(Clone)]
+struct RunContext {
+    paths: Paths,
+    wrapper: ExecutionWrapper,
+    java_component: ComponentInstallation,
+    replay_path: Option<String>,
+    memory_limit_mb: Option<u64>,
+    connect_timeout_secs: Option<u64>,
+    bot_shutdown_grace_secs: u64,
+    keep_map_links: bool,
+    confirm_warnings: bool,
+    injectory_wait: bool,
+    injectory_kill_on_exit: bool,
+    collect_crash_dumps: bool,
+    crash_detection: CrashDetectionConfig,
+    oldbwapi_dll: PathBuf,
+    bwheadless_exe: PathBuf,
+    injectory_exe: PathBuf,
+    copy_bwapi_ini_on_success: bool,
+    copy_bwapi_ini_always: bool,
+    bot_binary_scan_depth: usize,
+    validate_bwapi_ini: bool,
+    auto_truncate_player_names: bool,
+    fix_registry: bool,
+    quiet: bool,
+    output_json: bool,
+    skip_confirmation: bool,
+    archive_logs: Option<PathBuf>,
+    metrics_file: Option<PathBuf>,
+    tm_missing: TmMissing,
+    auto_quarantine: bool,
+    log_retention: LogRetention,
+    kill_on_first_exit: bool,
+    environment: Environment,
+}
 
-    let mut game_table_access = GameTableAccess::new();
+/// Runs a single game, `startup_warnings` are reported for every game
+fn run_game(
+    mut game_config: GameConfig,
+    context: RunContext,
+    startup_warnings: &[String],
+) -> anyhow::Result<GameResult> {
+    let RunContext {
+        paths,
+        wrapper,
+        java_component,
+        replay_path,
+        memory_limit_mb,
+        connect_timeout_secs,
+        bot_shutdown_grace_secs,
+        keep_map_links,
+        confirm_warnings,
+        injectory_wait,
+        injectory_kill_on_exit,
+        collect_crash_dumps,
+        crash_detection,
+        oldbwapi_dll,
+        bwheadless_exe,
+        injectory_exe,
+        copy_bwapi_ini_on_success,
+        copy_bwapi_ini_always,
+        bot_binary_scan_depth,
+        validate_bwapi_ini,
+        auto_truncate_player_names,
+        fix_registry,
+        quiet,
+        output_json,
+        skip_confirmation,
+        archive_logs,
+        metrics_file,
+        tm_missing,
+        auto_quarantine,
+        log_retention,
+        kill_on_first_exit,
+        environment,
+    } = context;
+    let mut warnings = startup_warnings.to_vec();
+    let mut game_table_access = GameTableAccess::new(&paths.tools_dir);
     if let Some(game_table) = game_table_access.get_game_table() {
-        warn!(
+        warnings.push(
             "Detected a stale game table. If you did not run Starcraft with BWAPI yourself, \
         you should kill all running instances of StarCraft and any lingering bots."
+                .to_string(),
         );
 
         for server_process_id in game_table
@@ -414,266 +2430,1182 @@ fn main() -> anyhow::Result<()> {
             .filter(|it| it.is_connected && it.server_process_id != 0)
             .map(|it| it.server_process_id)
         {
-            warn!(
+            warnings.push(format!(
             "The process {} is in the game table already and will interfere with game creation.",
             server_process_id
-        );
+            ));
         }
     }
 
-    match game_config.game_type {
-        GameType::Melee(ref bots) => {
-            let bots: anyhow::Result<Vec<_>> = bots
-                .iter()
+    let run_started = Instant::now();
+    let mut timer = PhaseTimer::new();
+    let game_id = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|it| it.as_secs())
+        .unwrap_or_default()
+        .to_string();
+    let ValidationContext {
+        starcraft_path,
+        max_players: max_game_players,
+        ..
+    } = game_config.game_type.validation_context().clone();
+    match &*game_config.game_type {
+        GameType::Melee(_) | GameType::TopVsBottom { .. } => {
+            let bots: anyhow::Result<Vec<_>> = game_config
+                .game_type
+                .bots()
+                .into_iter()
                 .map(|cfg| {
-                    let mut bot_folder = base_folder();
-                    bot_folder.push("bots");
-                    bot_folder.push(&cfg.name);
-                    let bot_definition = toml::from_str::<BotDefinition>(
-                        read_to_string(bot_folder.join("bot.toml"))
-                            .with_context(|| {
-                                format!(
-                                    "Could not read 'bot.toml' for bot '{}' in: '{}'",
-                                    cfg.name,
-                                    bot_folder.to_string_lossy(),
-                                )
-                            })?
-                            .as_str(),
-                    )?;
+                    let bot_folder = paths.bots_dir.join(&cfg.name);
+                    let bot_definition = BotDefinition::load(&cfg.name, &bot_folder)?;
+                    if let Some(min_version) = &bot_definition.min_shotgun_version {
+                        let version = semver::Version::parse(env!("CARGO_PKG_VERSION"))?;
+                        ensure!(
+                            &version >= min_version,
+                            "Bot '{}' requires BWAIShotgun {} or newer, but this is {}. Please update BWAIShotgun.",
+                            cfg.name,
+                            min_version,
+                            version
+                        );
+                    }
                     if let Some(race) = &cfg.race {
                         if bot_definition.race != Race::Random && &bot_definition.race != race {
-                            info!(
+                            warnings.push(format!(
                                 "Bot '{}' is configured to play as {}, but its default race is {}!",
                                 cfg.name, race, bot_definition.race
-                            );
+                            ));
                         }
                     }
                     Ok((cfg, bot_folder, bot_definition))
                 })
                 .collect();
             let bots = bots?;
-            let player_count = bots.len();
-            let prepared_bots: anyhow::Result<Vec<_>> = bots
+            for (cfg, _, definition) in bots.iter() {
+                if let Some(max_instances) = definition.max_instances {
+                    let instances = bots
+                        .iter()
+                        .filter(|(other, _, _)| other.name == cfg.name)
+                        .count();
+                    ensure!(
+                        instances <= max_instances,
+                        "Bot '{}' is configured to play {} times, but allows at most {} instance(s) per game",
+                        cfg.name,
+                        instances,
+                        max_instances
+                    );
+                }
+            }
+            if auto_truncate_player_names {
+                check_truncated_names_unique(
+                    bots.iter()
+                        .map(|(cfg, _, _)| cfg.player_name.as_deref().unwrap_or(&cfg.name)),
+                )?;
+            }
+            let mut prepared_bots = if game_config.require_all_bots_ready {
+                bots.iter()
+                    .map(|(config, path, definition)| {
+                        PreparedBot::prepare(
+                            &paths,
+                            config,
+                            path,
+                            definition,
+                            &starcraft_path,
+                            game_config.bot_log_dir(&paths, &config.name, path, &game_id),
+                            game_config.tournament_module.as_ref(),
+                            bot_binary_scan_depth,
+                            auto_truncate_player_names,
+                            tm_missing,
+                            &mut warnings,
+                        )
+                    })
+                    .collect::<anyhow::Result<Vec<_>>>()?
+            } else {
+                let mut prepared_bots = vec![];
+                for (config, path, definition) in bots.iter() {
+                    match PreparedBot::prepare(
+                        &paths,
+                        config,
+                        path,
+                        definition,
+                        &starcraft_path,
+                        game_config.bot_log_dir(&paths, &config.name, path, &game_id),
+                        game_config.tournament_module.as_ref(),
+                        bot_binary_scan_depth,
+                        auto_truncate_player_names,
+                        tm_missing,
+                        &mut warnings,
+                    ) {
+                        Ok(bot) => prepared_bots.push(bot),
+                        Err(err) => {
+                            warnings.push(format!("Skipping bot '{}': {:?}", config.name, err))
+                        }
+                    }
+                }
+                let required = if game_config.human_host { 1 } else { 2 };
+                ensure!(
+                    prepared_bots.len() >= required,
+                    "Only {} bot(s) could be prepared, not enough to play a game",
+                    prepared_bots.len()
+                );
+                prepared_bots
+            };
+            if game_config.random_seed.is_some() {
+                for bot in prepared_bots.iter() {
+                    match bot.bwapi_version {
+                        Some(version) => ensure!(
+                            version.supports_seed(),
+                            "'{}' uses BWAPI 3.7.x, which does not support 'random_seed'",
+                            bot.name
+                        ),
+                        None => warnings.push(format!(
+                            "'{}' uses an unknown BWAPI version, which might ignore 'random_seed'",
+                            bot.name
+                        )),
+                    }
+                }
+            }
+            let (min_players, max_players) =
+                game_config.lobby_players(prepared_bots.len(), max_game_players)?;
+            let installations: BTreeSet<_> = prepared_bots
                 .iter()
-                .map(|(config, path, definition)| PreparedBot::prepare(config, path, definition))
+                .map(|bot| bot.starcraft_path.as_path())
                 .collect();
-            let mut prepared_bots = prepared_bots?;
+            if let Some(installation) = installations.first() {
+                game_config.resolve_map(installation, is_interactive())?;
+            }
+            let mut renamed_map = None;
+            for installation in installations.iter() {
+                renamed_map = game_config.renamed_map(installation)?.or(renamed_map);
+            }
+            if renamed_map.is_some() {
+                game_config.map = renamed_map;
+            }
+            for installation in installations {
+                warnings.extend(check_installation(installation, &game_config)?);
+            }
+            let launch_reasons = order_bots(
+                &mut prepared_bots,
+                game_config.game_type.force_sizes(),
+                game_config.human_host,
+            )?;
+            let game_role = GameRole::plan(
+                &prepared_bots,
+                &game_config,
+                (min_players, max_players),
+                &mut warnings,
+            )?;
+            if let GameRole::BotHost { bot } = game_role {
+                let host = &prepared_bots[bot];
+                if !matches!(host.headful, HeadfulMode::Off) {
+                    if let Some(map) = game_config.auto_menu_map(&host.starcraft_path)? {
+                        game_config.map = Some(map);
+                    }
+                }
+            }
+            // Wine resolves the Visual C++ runtime differently, it can't be checked the same way
+            if !matches!(wrapper, ExecutionWrapper::Wine) {
+                for bot in prepared_bots.iter() {
+                    if let Some(runtime) = bot.bwapi_version.and_then(VcRuntime::required_by) {
+                        if !runtime.is_installed(&bot.starcraft_path) {
+                            warnings.push(runtime.missing_message(&bot.name));
+                        }
+                    }
+                }
+            }
+            info!("Launch order:");
+            for (i, (bot, reasons)) in prepared_bots.iter().zip(launch_reasons).enumerate() {
+                let role = if game_role.is_host(i) { ", host" } else { "" };
+                if reasons.is_empty() {
+                    info!("{}. '{}' ({}{role})", i + 1, bot.name, bot.bot_type_label());
+                } else {
+                    info!(
+                        "{}. '{}' ({}{role}): {}",
+                        i + 1,
+                        bot.name,
+                        bot.bot_type_label(),
+                        reasons.join(", ")
+                    );
+                }
+            }
 
-            // Client bots *must* be ran first, as they need to connect to their resp. BWAPI Server
-            prepared_bots.sort_by_key(|bot| matches!(bot.binary, Binary::Dll(_)));
+            // Old BWAPI versions launched by injectory read their install path from the registry
+            let legacy_bots: Vec<_> = prepared_bots
+                .iter()
+                .filter(|bot| {
+                    bot.bwapi_version == Some(BwapiVersion::Bwapi375)
+                        && !matches!(bot.headful, HeadfulMode::Off)
+                })
+                .collect();
+            let mut _install_path_override = None;
+            if let Some(legacy_bot) = legacy_bots.first() {
+                let conflicts = bwapi_registry::conflicting_install_paths(&legacy_bot.working_dir);
+                for conflict in conflicts.iter() {
+                    warnings.push(format!(
+                        "Registry value '{}' is set to '{}' - '{}' uses BWAPI 3.7.x and might load the wrong bwapi.ini and AI module.",
+                        conflict.key,
+                        conflict.value.display(),
+                        legacy_bot.name
+                    ));
+                }
+                if fix_registry {
+                    if legacy_bots.len() > 1 {
+                        warnings.push(format!(
+                            "Multiple headful bots use BWAPI 3.7.x, the registry can only be fixed for '{}'",
+                            legacy_bot.name
+                        ));
+                    }
+                    _install_path_override =
+                        Some(InstallPathOverride::new(&legacy_bot.working_dir)?);
+                } else if !conflicts.is_empty() {
+                    warnings.push(
+                        "Use '--fix-registry' to override the value while the game is running."
+                            .to_string(),
+                    );
+                }
+            }
 
             let mut bot_names = HashSet::new();
             for bot in prepared_bots.iter().map(|it| &it.name) {
                 if !bot_names.insert(bot) {
-                    warn!("'{}' was added multiple times. All instances will use the same read/write/log folders and could fail to work properly. Also headful mode will not work as expected.", bot);
+                    warnings.push(format!("'{}' was added multiple times. All instances will use the same read/write/log folders and could fail to work properly. Also headful mode will not work as expected.", bot));
                 }
             }
-            let mut instances = vec![];
-            // If a human is going to host, no need to fire up a host
-            let mut host = !game_config.human_host;
-            // Game name is mutable, BWAPI can't create games with names differing from the player name in LAN
-            let mut game_name = game_config
-                .game_name
-                .as_deref()
-                .unwrap_or("shotgun")
-                .to_string();
-            for bot in prepared_bots {
-                let bot_setup = BotSetup {
-                    starcraft_exe: starcraft_exe.clone(),
-                    starcraft_path: starcraft_path.clone(),
-                    bot_base_path: bot.working_dir.clone(),
-                    tournament_module: bot.tournament_module.map(|s| s.into()),
-                    player_name: bot.name.clone(),
-                    race: bot.race,
-                    wrapper: wrapper.clone(),
-                    bot_binary: bot.binary.clone(),
-                    replay_path: game_config
-                        .replay_path
-                        .as_ref()
-                        .or_else(|| replay_path.as_ref())
-                        .cloned(),
-                };
-                let tournament_module = bot_setup.tournament_module.clone();
-                let bwapi_launcher: Box<dyn LaunchBuilder> = if !matches!(
-                    bot.headful,
-                    HeadfulMode::Off
-                ) {
-                    if host {
-                        // Headful + Host => All other bots need to join the game with this bots player name
-                        if bot.supports_character_name {
-                            game_name = bot.name.clone();
-                        } else {
-                            warn!("Headful hosting bot uses very old BWAPI version, please ensure there's only one character with the name 'BWAPI'.");
-                            game_name = "BWAPI".to_string();
-                        }
-                    }
-                    Box::new(Injectory {
-                        bot_setup,
-                        game_name: if game_config.human_host {
-                            "JOIN_FIRST".to_string()
-                        } else {
-                            game_name.clone()
-                        },
-                        connect_mode: if host {
-                            InjectoryConnectMode::Host {
-                                map: game_config.map.clone(),
-                                player_count,
+            let bot_folders: BTreeSet<_> = prepared_bots.iter().map(|it| &it.working_dir).collect();
+            for bot_folder in bot_folders {
+                let suspicious = bot_scan::scan_bot_folder(bot_folder);
+                if suspicious.is_empty() {
+                    continue;
+                }
+                if auto_quarantine {
+                    bot_scan::quarantine(bot_folder, &suspicious)?;
+                }
+                for file in suspicious.iter() {
+                    let path = bot_folder.join(&file.path);
+                    if auto_quarantine && file.quarantine {
+                        info!(
+                            "Moved '{}' to '{}': {}",
+                            path.display(),
+                            bot_scan::QUARANTINE_FOLDER,
+                            file.reason
+                        );
+                    } else {
+                        warnings.push(format!(
+                            "Found '{}': {} - {}{}",
+                            path.display(),
+                            file.reason,
+                            file.action,
+                            if file.quarantine {
+                                " (or set 'auto_quarantine = true' in 'shotgun.toml')"
+                            } else {
+                                ""
                             }
-                        } else {
-                            InjectoryConnectMode::Join
-                        },
-                        wmode: matches!(bot.headful, HeadfulMode::On { no_wmode, .. } if !no_wmode),
-                        sound: matches!(bot.headful, HeadfulMode::On { no_sound, ..} if !no_sound),
-                        game_speed: if game_config.human_speed { -1 } else { 0 },
+                        ));
+                    }
+                }
+            }
+            timer.end_phase("prepare");
+            report_warnings(&warnings, confirm_warnings && !skip_confirmation)?;
+            timer.skip_phase();
+            let create_bot_dumps = crash_detection.detect && crash_detection.create_dump;
+            let dump_dir = paths.home.join(
+                crash_detection
+                    .dump_dir
+                    .as_deref()
+                    .unwrap_or(Path::new("crash_dumps")),
+            );
+            let local_dumps = if (collect_crash_dumps || create_bot_dumps)
+                && !matches!(wrapper, ExecutionWrapper::Wine)
+            {
+                // Java bots would need 'java.exe' registered, which would catch any Java crash
+                let bot_executables: Vec<_> = prepared_bots
+                    .iter()
+                    .filter(|_| create_bot_dumps)
+                    .filter_map(|bot| match &bot.binary {
+                        Binary::Exe(exe) => exe.file_name(),
+                        Binary::Dll(_) | Binary::Jar(_) => None,
                     })
-                } else {
-                    Box::new(BwHeadless {
-                        bot_setup,
-                        game_name: if game_config.human_host {
-                            None
-                        } else {
-                            Some(game_name.clone())
-                        },
-                        connect_mode: if host {
-                            BwHeadlessConnectMode::Host {
-                                map: game_config.map.clone().ok_or_else(|| {
-                                    anyhow!("bwheadless cannot host without a map")
-                                })?,
-                                player_count,
+                    .map(|name| name.to_string_lossy().to_string())
+                    .collect();
+                match LocalDumps::new(dump_dir.join(&game_id), &bot_executables) {
+                    Ok(local_dumps) => Some(local_dumps),
+                    Err(err) => {
+                        warn!(
+                            "Crash dumps{} will not be written: {err:?}",
+                            if create_bot_dumps {
+                                " (including 'create_dump' of bots)"
+                            } else {
+                                ""
                             }
-                        } else {
-                            BwHeadlessConnectMode::Join
-                        },
-                    })
-                };
-                info!(
-                    "{} game with '{}'{}",
-                    if host { "Hosting" } else { "Joining" },
-                    bot.name,
-                    tournament_module
-                        .map(|tm| format!(" (with tournament module '{}')", tm.to_string_lossy()))
-                        .unwrap_or_else(|| "".to_string())
+                        );
+                        None
+                    }
+                }
+            } else {
+                if create_bot_dumps {
+                    warn!("'create_dump' is not supported with Wine, bot crash dumps will not be written");
+                }
+                None
+            };
+            let mut instances = vec![];
+            let game_start = Instant::now();
+            let game_started_at = SystemTime::now();
+            // Game name is mutable, BWAPI can't create games with names differing from the player name in LAN
+            let mut game_name = game_config.default_game_name(match game_role {
+                GameRole::BotHost { bot } => Some(prepared_bots[bot].name.as_str()),
+                GameRole::HumanHost => None,
+            });
+            // Folder of a headful host, which might contain a link to the maps folder or map copies
+            let mut map_link_dir = None;
+            // Detected before launching any bot, all headless bots use the same bwheadless
+            let bwheadless_capabilities = if prepared_bots
+                .iter()
+                .any(|bot| matches!(bot.headful, HeadfulMode::Off))
+            {
+                BwHeadlessCapabilities::detect(&bwheadless_exe, &wrapper)
+            } else {
+                BwHeadlessCapabilities::default()
+            };
+            let announcement = game_config.announcement(
+                &prepared_bots
+                    .iter()
+                    .map(|bot| bot.name.as_str())
+                    .collect::<Vec<_>>(),
+                &game_id,
+            );
+            let launch_context = LaunchContext {
+                game_config: &game_config,
+                game_role,
+                game_id: &game_id,
+                paths: &paths,
+                wrapper: &wrapper,
+                java_component: &java_component,
+                replay_path: game_config.replay_path.as_ref().or(replay_path.as_ref()),
+                validate_bwapi_ini,
+                min_players,
+                max_players,
+                injectory_wait,
+                injectory_kill_on_exit,
+                oldbwapi_dll: &oldbwapi_dll,
+                injectory_exe: &injectory_exe,
+                bwheadless_exe: &bwheadless_exe,
+                bwheadless_capabilities,
+                announcement: announcement.as_deref(),
+                connect_timeout_secs,
+                memory_limit_mb,
+            };
+            let bot_count = prepared_bots.len();
+            let mut bwapi_ini_cleanup =
+                BwapiIniCleanup::new(copy_bwapi_ini_always, copy_bwapi_ini_on_success);
+            for (index, bot) in prepared_bots.into_iter().enumerate() {
+                bwapi_ini_cleanup.add(
+                    BwapiIni::path(&bot.working_dir, bot.bwapi_version, &game_id),
+                    bot.log_dir.clone(),
                 );
-                host = false;
-
-                let mut cmd = bwapi_launcher.build_command(&game_config)?;
-                cmd.stdout(File::create(bot.log_dir.join("game_out.log"))?)
-                    .stderr(File::create(bot.log_dir.join("game_err.log"))?);
-                let cmd = cmd
-                    .env("TM_LOG_FRAMETIMES", r"tm\frames.csv")
-                    .env("TM_LOG_RESULTS", r"tm\result.csv")
-                    .env("TM_LOG_UNIT_EVENTS", r"tm\unit_events.csv");
-                if let Some(time_out_at_frame) = game_config.time_out_at_frame {
-                    cmd.env("TM_TIME_OUT_AT_FRAME", time_out_at_frame.to_string());
-                }
-                let mut bwapi_child = cmd.spawn().context(
-                    "Could not run bwheadless (maybe deleted/blocked by a Virus Scanner?)",
+                let headful_host =
+                    game_role.is_host(index) && !matches!(bot.headful, HeadfulMode::Off);
+                let launched = launch_bot(
+                    bot,
+                    index,
+                    &game_name,
+                    &launch_context,
+                    &mut game_table_access,
+                    &mut timer,
                 )?;
+                debug!(
+                    "Launched '{}' (BWAPI {}) for game '{}'",
+                    launched.name,
+                    launched
+                        .bwapi_version
+                        .as_ref()
+                        .map_or("unknown", BwapiVersion::version_short),
+                    launched.game_name
+                );
+                game_name = launched.game_name;
+                if launched.map_link_dir.is_some() {
+                    map_link_dir = launched.map_link_dir;
+                }
+                instances.push(launched.process);
+                if headful_host && index + 1 < bot_count && game_config.headful_host_delay_ms > 0 {
+                    info!(
+                            "Waiting {}ms for the headful host to create the game ('headful_host_delay_ms')",
+                            game_config.headful_host_delay_ms
+                        );
+                    std::thread::sleep(Duration::from_millis(
+                        game_config.headful_host_delay_ms.into(),
+                    ));
+                }
+            }
 
-                debug!("Spawned Starcraft with PID: {}", bwapi_child.id());
-
-                let bot_out_log = File::create(bot.log_dir.join("bot_out.log"))?;
-                let bot_err_log = File::create(bot.log_dir.join("bot_err.log"))?;
-                let bot_process = match bot.binary {
-                    Binary::Dll(_) => None,
-                    Binary::Jar(jar) => {
-                        let mut cmd =
-                            wrapper.wrap_executable(java_component.to_path()?);
-                        cmd.arg("-jar").arg(jar);
-                        Some(cmd)
+            let launch_duration = game_start.elapsed();
+            info!("All bots launched, waiting for game to complete");
+            if game_config.allow_human_observer {
+                info!(
+                    "Game is ready, you can now join as an observer. Game name: '{game_name}'. Connect via Local PC network, the game starts without you after {}s.",
+                    game_config.lobby_grace_secs
+                );
+            }
+            let mut game_started = false;
+            let mut game_over = false;
+            let lobby_started = Instant::now();
+            let mut lobby_failure = None;
+            // The bot whose StarCraft exited first
+            let mut first_exit = None;
+            // Only the TM reports the start of the game
+            let tm_active = instances
+                .iter()
+                .any(|it| it.result.tournament_module.is_some());
+            let lobby_timeout = game_config.lobby_timeout_secs.filter(|_| {
+                if !tm_active {
+                    warn!("'lobby_timeout_secs' is ignored, no bot runs with a tournament module");
+                }
+                tm_active
+            });
+
+            // Clean up a bit, kill Client bots to prevent them from spamming the slot table
+            // They will also print "Client And Server are not compatible" - if different versions of BWAPI are running with multiple clients
+            let mut remaining = instances.len();
+            while remaining > 0 {
+                for instance in instances
+                    .iter_mut()
+                    .filter(|it| it.game_started_at.is_none())
+                {
+                    if tm_results::game_started(&instance.tm_dir) {
+                        instance.game_started_at = Some(Instant::now());
                     }
-                    Binary::Exe(exe) => Some(wrapper.wrap_executable(exe)),
-                }
-                .map(|ref mut cmd| -> anyhow::Result<Child> {
-                    // Wait for server to be ready to accept connections
-                    debug!("Waiting for free slots... ");
-                    retry(Fixed::from_millis(100).take(100), || {
-                        if game_table_access.has_free_slot() {
-                            OperationResult::Ok(())
-                        } else {
-                            OperationResult::Retry("BWAPI Server is not ready")
+                }
+                if !game_started && instances.iter().any(|it| it.game_started_at.is_some()) {
+                    game_started = true;
+                    timer.end_phase("lobby");
+                } else if let (false, None, Some(timeout)) =
+                    (game_started, &lobby_failure, lobby_timeout)
+                {
+                    if lobby_started.elapsed() >= Duration::from_secs(timeout) {
+                        let failure = LobbyFailure {
+                            kind: LobbyFailureKind::Timeout { secs: timeout },
+                            game_table_history: game_table_access.history().to_string(),
+                        };
+                        error!(
+                            "{}, killing all bots\nGame table history:\n{}",
+                            failure.kind, failure.game_table_history
+                        );
+                        for instance in instances.iter_mut().filter(|it| !it.finished) {
+                            instance.restart = None;
+                            instance.bwheadless.kill().ok();
+                            if let Some(bot) = instance.bot.as_mut() {
+                                bot.kill().ok();
+                            }
+                        }
+                        lobby_failure = Some(failure);
+                    }
+                }
+                for instance in instances.iter_mut().filter(|it| !it.finished) {
+                    let BotProcess {
+                        ref mut result,
+                        ref mut bwheadless,
+                        ref mut bot,
+                        ref mut restart,
+                        memory_limit_mb,
+                        ref mut window_title,
+                        ref mut server_exited_at,
+                        ref mut crash_dialog_found,
+                        ref mut finished,
+                        ..
+                    } = *instance;
+                    if let Some(title) = window_title {
+                        if window_title::set_window_title(bwheadless.id(), title) {
+                            debug!("Set window title of '{}' to '{title}'", result.name);
+                            *window_title = None;
+                        }
+                    }
+                    if let Some(dialog) = (!*crash_dialog_found)
+                        .then(|| crash_dialog::find_crash_dialog(bwheadless.id()))
+                        .flatten()
+                    {
+                        *crash_dialog_found = true;
+                        error!(
+                            "BWAPI crash dialog detected for '{}': '{}' - killing StarCraft (process {})",
+                            result.name, dialog.title, dialog.process_id
+                        );
+                        if !crash_dialog::kill(&dialog) {
+                            bwheadless.kill().ok();
                         }
-                    }).map_err(anyhow::Error::msg)?;
-
-                    cmd.current_dir(bot.working_dir);
-                    cmd.stdout(bot_out_log);
-                    cmd.stderr(bot_err_log);
-                    debug!("Found. Firing up bot... '{:?}'", cmd);
-
-                    let mut child = cmd.spawn()?;
-
-                    // Wait up to 10 seconds before bailing
-                    debug!("Waiting for bot to take up slot...");
-                    retry(Fixed::from_millis(100).take(100), || {
-                        let slots_filled = game_table_access.all_slots_filled();
-                        match bwapi_child.try_wait() {
-                            Ok(None) => {
-                                // Ok, continue
-                            },
-                            Ok(Some(code)) => {
-                                error!("Starcraft died with: {}", code);
-                                return OperationResult::Err("BWAPI process died");
+                        result.failure.get_or_insert(format!(
+                            "BWAPI crash dialog detected: '{}'",
+                            dialog.title
+                        ));
+                    }
+                    // Bots might exit with an error once StarCraft is gone, that's no crash
+                    if crash_detection.detect
+                        && result.failure.is_none()
+                        && matches!(bwheadless.try_wait(), Ok(None))
+                    {
+                        if let Some(Ok(Some(status))) = bot.as_mut().map(|child| child.try_wait()) {
+                            if !status.success() {
+                                let code = status
+                                    .code()
+                                    .map_or("none".to_string(), |code| code.to_string());
+                                error!("Bot '{}' crashed with exit code {code}", result.name);
+                                result.failure = Some(format!("Bot crashed with exit code {code}"));
                             }
-                            Err(e) => {
-                                error!("Error waiting for BWAPI process: {e}");
-                                return OperationResult::Err("Could not wait for BWAPI process");
+                        }
+                    }
+                    if let (Some(limit), Some(child)) = (memory_limit_mb, bot.as_ref()) {
+                        if let Some(used) = memory::resident_memory(child) {
+                            let used_mb = used / (1024 * 1024);
+                            if used_mb > limit {
+                                error!(
+                                    "Bot '{}' uses {} MB, exceeding its memory limit of {} MB - killing it",
+                                    result.name, used_mb, limit
+                                );
+                                if let Some(mut child) = bot.take() {
+                                    child.kill().ok();
+                                    child.wait().ok();
+                                }
+                                result.failure =
+                                    Some(format!("Exceeded memory limit of {limit} MB"));
                             }
                         }
-                        match child.try_wait() {
-                            Ok(None) => {
-                                // Ok, continue
-                            },
-                            Ok(Some(code)) => {
-                                error!("Bot process died with: {}", code);
-                                return OperationResult::Err("Bot process died")
+                    }
+                    let server_exit = bwheadless.try_wait();
+                    if let (Some(restart), Ok(None)) = (restart.as_mut(), &server_exit) {
+                        // A bot quitting with exit code 0 is done, not crashed
+                        let crashed = bot.as_mut().is_some_and(
+                            |child| matches!(child.try_wait(), Ok(Some(status)) if !status.success()),
+                        );
+                        if crashed && restart.attempts < MAX_BOT_RESTARTS {
+                            restart.attempts += 1;
+                            warn!(
+                                "Bot '{}' exited while the game is still running, restarting it ({}/{})",
+                                result.name, restart.attempts, MAX_BOT_RESTARTS
+                            );
+                            match restart.spawn(&wrapper, &java_component) {
+                                Ok(child) => *bot = Some(child),
+                                Err(err) => {
+                                    error!("Could not restart bot '{}': {err}", result.name)
+                                }
                             }
-                            Err(e) => {
-                                error!("Error waiting for bot process: {e}");
-                                return OperationResult::Err("Could not wait for bot process");
+                        }
+                    }
+                    let Ok(Some(exit_status)) = server_exit else {
+                        continue;
+                    };
+                    if first_exit.is_none() {
+                        first_exit = Some(result.name.clone());
+                        if kill_on_first_exit {
+                            info!(
+                                    "StarCraft of '{}' exited first ({exit_status}{}), ending the game for all bots ('kill_on_first_exit')",
+                                    result.name,
+                                    result
+                                        .failure
+                                        .as_ref()
+                                        .map(|it| format!(", {it}"))
+                                        .unwrap_or_default()
+                                );
+                        }
+                    }
+                    if !game_over {
+                        game_over = true;
+                        // Without TM, the start of the game is unknown
+                        timer.end_phase(if game_started {
+                            "game"
+                        } else {
+                            "lobby and game"
+                        });
+                    }
+                    let remove = match bot {
+                        Some(ref mut bot) => {
+                            // Give the bot some time to write its files before killing it
+                            let exited_at = *server_exited_at.get_or_insert_with(Instant::now);
+                            if matches!(bot.try_wait(), Ok(Some(_))) {
+                                true
+                            } else if exited_at.elapsed()
+                                >= Duration::from_secs(bot_shutdown_grace_secs)
+                            {
+                                debug!("Bot '{}' did not exit in time, killing it", result.name);
+                                bot.kill().ok();
+                                true
+                            } else {
+                                false
                             }
                         }
-                        if slots_filled {
-                            OperationResult::Ok(())
+                        None => true,
+                    };
+                    if remove {
+                        result.exit_code = exit_status.code();
+                        *finished = true;
+                        remaining -= 1;
+                        info!("{} bots remaining", remaining);
+                    }
+                }
+                if kill_on_first_exit && first_exit.is_some() {
+                    for instance in instances.iter_mut().filter(|it| !it.finished) {
+                        // Client bots get 'bot_shutdown_grace_secs' to exit, once their StarCraft is gone
+                        instance.restart = None;
+                        instance.bwheadless.kill().ok();
+                    }
+                }
+                std::thread::sleep(Duration::from_secs(1));
+            }
+            timer.end_phase("cleanup");
+            if let (None, true, Some(bot)) = (&lobby_failure, tm_active, first_exit) {
+                // Checked again, the game might have started right before StarCraft exited
+                if !game_started
+                    && !instances
+                        .iter()
+                        .any(|it| tm_results::game_started(&it.tm_dir))
+                {
+                    let failure = LobbyFailure {
+                        kind: LobbyFailureKind::Exited { bot },
+                        game_table_history: game_table_access.history().to_string(),
+                    };
+                    error!(
+                        "{}\nGame table history:\n{}",
+                        failure.kind, failure.game_table_history
+                    );
+                    lobby_failure = Some(failure);
+                }
+            }
+            if let (Some(dir), Some(map)) = (&map_link_dir, &game_config.map) {
+                if !keep_map_links {
+                    if let Err(err) = remove_map_link(dir, map) {
+                        warn!("Could not remove map link: {err}");
+                    }
+                }
+                if let Err(err) = remove_map_copies(dir) {
+                    warn!("Could not remove map copies: {err}");
+                }
+            }
+            let bot_logs = instances
+                .iter()
+                .map(|it| BotLogs {
+                    name: it.result.name.clone(),
+                    log_dir: it.log_dir.clone(),
+                    tm_dir: it.tm_dir.clone(),
+                    replay_dir: it.replay_dir.clone(),
+                })
+                .collect();
+            let last_frame = instances
+                .iter()
+                .filter_map(|it| tm_results::read_last_frame(&it.tm_dir))
+                .max();
+            let time_limit_exceeded =
+                TimeoutAction::time_limit_exceeded(game_config.time_out_at_frame, last_frame);
+            if time_limit_exceeded && game_config.timeout_action == TimeoutAction::Flag {
+                warn!("The game exceeded 'time_out_at_frame' and was left to play out");
+            }
+            let mut game_result = GameResult {
+                game_id,
+                game_name,
+                started: game_started_at,
+                map: game_config.map.clone(),
+                replay_path: game_config
+                    .replay_path
+                    .as_ref()
+                    .or(replay_path.as_ref())
+                    .map(|it| it.as_str())
+                    .unwrap_or(DEFAULT_REPLAY_PATH)
+                    .to_string(),
+                duration: instances
+                    .iter()
+                    .filter_map(|it| it.game_started_at)
+                    .min()
+                    .unwrap_or(game_start)
+                    .elapsed(),
+                duration_frames: last_frame,
+                lobby_failure,
+                time_limit_exceeded,
+                launch_duration,
+                total_duration: run_started.elapsed(),
+                timings: timer.finish(),
+                environment: environment.clone(),
+                bots: instances
+                    .into_iter()
+                    .map(|it| {
+                        let crash_dumps = if collect_crash_dumps {
+                            collect_crash_evidence(&it, local_dumps.as_ref(), &wrapper)
                         } else {
-                            OperationResult::Retry(
-                                "Bot client executable did not connect to BWAPI server (did you try to run a human hosted game without hosting it?)",
-                            )
+                            vec![]
+                        };
+                        let failure = it.result.failure.or_else(|| {
+                            (!crash_dumps.is_empty()).then(|| "StarCraft crashed".to_string())
+                        });
+                        let (disqualification, mismatch) = game_config
+                            .frame_limits
+                            .as_ref()
+                            .map(|limits| check_frame_limits(limits, &it.tm_dir, &it.result.name))
+                            .unwrap_or_default();
+                        let outcome = if !crash_dumps.is_empty() {
+                            Some(Outcome::Crash)
+                        } else if let Some(rule) = disqualification {
+                            Some(Outcome::Disqualified { rule })
+                        } else {
+                            tm_results::read_tm_result(&it.tm_dir).map(|it| it.outcome())
+                        };
+                        BotResult {
+                            start_location: tm_results::read_start_location(
+                                &it.tm_dir,
+                                &it.result.name,
+                            ),
+                            failure,
+                            outcome,
+                            diagnostics: mismatch.into_iter().collect(),
+                            crash_dumps,
+                            ..it.result
                         }
                     })
-                    .map_err(anyhow::Error::msg)?;
-
-                    Ok(child)
-                })
-                .transpose()?;
-                instances.push(BotProcess {
-                    bwheadless: bwapi_child,
-                    bot: bot_process,
+                    .collect(),
+                bot_logs,
+            };
+            results::apply_disqualifications(&mut game_result.bots);
+            if let Some(local_dumps) = local_dumps {
+                if local_dumps.dumps().is_ok_and(|dumps| !dumps.is_empty()) {
+                    warn!(
+                        "Found crash dumps which could not be assigned to a bot in '{}'",
+                        local_dumps.dump_folder().display()
+                    );
+                }
+            }
+            bwapi_ini_cleanup.set_success(game_result.bots.iter().all(|bot| bot.failure.is_none()));
+            // Before archiving, so the per game inis are archived as well
+            drop(bwapi_ini_cleanup);
+            // After collecting crash dumps, so they are archived as well
+            if let Some(archive_folder) = &archive_logs {
+                let archive = archive_folder.join(format!("shotgun_{}.zip", game_result.game_id));
+                match create_dir_all(archive_folder)
+                    .map_err(anyhow::Error::from)
+                    .and_then(|_| write_log_archive(&archive, &game_result.bot_logs))
+                {
+                    Ok(()) => info!("Archived logs to '{}'", archive.display()),
+                    Err(err) => error!("Could not archive logs: {err:?}"),
+                }
+                apply_log_retention(&log_retention, archive_folder, &|path| {
+                    path.file_name()
+                        .is_some_and(|it| it.to_string_lossy().starts_with("shotgun_"))
+                        && path.extension().is_some_and(|ext| ext == "zip")
                 });
             }
-
-            info!("All bots launched, waiting for game to complete");
-
-            // Clean up a bit, kill Client bots to prevent them from spamming the slot table
-            // They will also print "Client And Server are not compatible" - if different versions of BWAPI are running with multiple clients
-            while !instances.is_empty() {
-                for i in (0..instances.len()).rev() {
-                    let BotProcess {
-                        ref mut bwheadless,
-                        ref mut bot,
-                    } = instances[i];
-                    let remove = matches!(bwheadless.try_wait(), Ok(Some(_)));
-                    if remove {
-                        if let Some(ref mut bot) = bot {
-                            bot.kill().ok();
-                        }
-                        instances.swap_remove(i);
-                        info!("{} bots remaining", instances.len());
-                    }
+            // One folder per game
+            apply_log_retention(&log_retention, &dump_dir, &Path::is_dir);
+            // Dumps collected into the log folders of the bots
+            for bot in game_result.bot_logs.iter() {
+                apply_log_retention(&log_retention, &bot.log_dir, &|path| {
+                    path.extension().is_some_and(|ext| ext == "dmp")
+                });
+            }
+            for bot in game_result.bots.iter() {
+                if let Some(failure) = &bot.failure {
+                    error!("Bot '{}' failed: {}", bot.name, failure);
                 }
-                std::thread::sleep(Duration::from_secs(1));
+            }
+            let metrics = LaunchMetrics::from(&game_result);
+            info!(
+                "Bots prepared in {:.1}s, launched in {:.1}s, total time {:.1}s",
+                metrics
+                    .bots
+                    .iter()
+                    .map(|it| it.prepare_time)
+                    .sum::<Duration>()
+                    .as_secs_f64(),
+                metrics.launch_duration.as_secs_f64(),
+                metrics.total_duration.as_secs_f64()
+            );
+            if let Some(metrics_file) = &metrics_file {
+                if let Err(err) = metrics.append_to(&paths.home.join(metrics_file)) {
+                    error!("Could not write the metrics to 'metrics_file': {err:?}");
+                }
+            }
+            if output_json {
+                println!("{}", serde_json::to_string_pretty(&game_result)?);
+            } else if !quiet {
+                println!("{}", game_result.summary_table());
             }
             info!("Done");
-            Ok(())
+            Ok(game_result)
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::paths::Paths;
+    use crate::results::test::game_result;
+    use crate::test_util::TempDir;
+    use crate::{
+        bot_args, build_post_game_command, check_player_name, check_truncated_names_unique,
+        expand_template, Binary, BotDefinition, BotLaunchConfig, GameConfig, GameSpeedPreset,
+        GameType, HeadfulMode, ShotgunConfig, TimeoutAction, ValidatedGameType,
+    };
+    use std::fs::{create_dir_all, write};
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn test_post_game_command() {
+        let env = |game_result| {
+            let cmd = build_post_game_command(
+                &["notify.sh".to_string(), "--all".to_string()],
+                Path::new("bwaishotgun"),
+                &game_result,
+            )
+            .unwrap();
+            assert_eq!(cmd.get_program(), "notify.sh");
+            assert_eq!(cmd.get_current_dir(), Some(Path::new("bwaishotgun")));
+            cmd.get_envs()
+                .map(|(key, value)| {
+                    (
+                        key.to_string_lossy().to_string(),
+                        value.map(|it| it.to_string_lossy().to_string()),
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            env(game_result()),
+            [(
+                "BWAISHOTGUN_GAME_ID".to_string(),
+                Some("1700000000".to_string())
+            )]
+        );
+        let mut crashed = game_result();
+        crashed.bots[1].failure = Some("Bot crashed with exit code 1".to_string());
+        assert!(env(crashed).contains(&(
+            "BWAISHOTGUN_CRASH".to_string(),
+            Some("ZergHell".to_string())
+        )));
+        assert!(build_post_game_command(&[], Path::new("bwaishotgun"), &game_result()).is_err());
+    }
+
+    #[test]
+    fn test_check_player_name() {
+        assert_eq!(check_player_name("NitekatT", false).unwrap(), "NitekatT");
+        let long_name = "Ääääääääääääääääääääääää";
+        assert_eq!(check_player_name(long_name, false).unwrap(), long_name);
+        assert!(check_player_name(" \t", true).is_err());
+        assert!(check_player_name("", true).is_err());
+        let too_long = "NitekatT_with_a_very_long_name";
+        assert!(check_player_name(too_long, false).is_err());
+        assert_eq!(
+            check_player_name(too_long, true).unwrap(),
+            "NitekatT_with_a_very_lon"
+        );
+    }
+
+    #[test]
+    fn test_long_bot_name() {
+        let name = "NitekatT_with_a_very_long_name";
+        check_truncated_names_unique([name, name, "MarineHell"]).unwrap();
+        assert!(
+            check_truncated_names_unique([name, "NitekatT_with_a_very_long_nickname"]).is_err()
+        );
+
+        // A headful host names the game after itself, joiners must look for the truncated name
+        let host_name = check_player_name(name, true).unwrap();
+        let config: GameConfig<GameType> = toml::from_str("game_type = { Melee = [] }").unwrap();
+        assert_eq!(config.default_game_name(Some(&host_name)), host_name);
+        assert_eq!(host_name.chars().count(), 24);
+    }
+
+    #[test]
+    fn test_resolve_tools_folder() {
+        let paths = Paths::with_home(PathBuf::from("shotgun"));
+        assert_eq!(
+            ShotgunConfig::default().resolve_tools_folder(&paths),
+            paths.tools_dir
+        );
+        let config: ShotgunConfig = toml::from_str("tools_path = 'shared/tools'").unwrap();
+        assert_eq!(
+            config.resolve_tools_folder(&paths),
+            PathBuf::from("shotgun").join("shared/tools")
+        );
+    }
+
+    #[test]
+    fn test_headful_mode() {
+        let headful_mode = |config: &str| {
+            toml::from_str::<BotLaunchConfig>(&format!("name = 'Bot'\n{config}"))
+                .unwrap()
+                .headful_mode()
+        };
+        let on = |no_wmode, no_sound| HeadfulMode::On { no_wmode, no_sound };
+        assert_eq!(headful_mode(""), HeadfulMode::Off);
+        assert_eq!(headful_mode("headful = 'Off'"), HeadfulMode::Off);
+        assert_eq!(headful_mode("headful = false"), HeadfulMode::Off);
+        assert_eq!(headful_mode("headful = true"), on(false, false));
+        assert_eq!(headful_mode("headful = 'On'"), on(false, false));
+        assert_eq!(headful_mode("headful = { On = {} }"), on(false, false));
+        assert_eq!(
+            headful_mode("headful = { On = { no_wmode = true, no_sound = true } }"),
+            on(true, true)
+        );
+        assert_eq!(
+            headful_mode("headful = 'On'\nno_wmode = true"),
+            on(true, false)
+        );
+        assert_eq!(headful_mode("no_sound = true"), HeadfulMode::Off);
+    }
+
+    #[test]
+    fn test_default_game_name() {
+        let config: GameConfig<GameType> = toml::from_str("game_type = { Melee = [] }").unwrap();
+        assert_eq!(config.default_game_name(Some("MarineHell")), "MarineHell");
+        assert_eq!(config.default_game_name(None), "shotgun");
+
+        let config: GameConfig<GameType> =
+            toml::from_str("game_type = { Melee = [] }\ngame_name_auto = false").unwrap();
+        assert_eq!(config.default_game_name(Some("MarineHell")), "shotgun");
+
+        let config: GameConfig<GameType> =
+            toml::from_str("game_type = { Melee = [] }\ngame_name = 'Test'").unwrap();
+        assert_eq!(config.default_game_name(Some("MarineHell")), "Test");
+    }
+
+    #[test]
+    fn test_auto_menu_map() {
+        let starcraft = TempDir::new("auto-menu-map");
+        create_dir_all(starcraft.join("custom")).unwrap();
+        create_dir_all(starcraft.join("maps")).unwrap();
+        write(starcraft.join("custom").join("(2)Ridge.scx"), "map").unwrap();
+        let config = |map: &str, extra: &str| {
+            toml::from_str::<GameConfig<GameType>>(&format!(
+                "game_type = {{ Melee = [] }}\nmap = '{map}'\n{extra}"
+            ))
+            .unwrap()
+        };
+
+        assert_eq!(
+            config("maps/(2)Ridge.scx", "")
+                .auto_menu_map(&starcraft)
+                .unwrap(),
+            None
+        );
+        assert!(config("custom/(2)Ridge.scx", "")
+            .auto_menu_map(&starcraft)
+            .is_err());
+        assert_eq!(
+            config("custom/(2)Ridge.scx", "auto_rename_maps = true")
+                .auto_menu_map(&starcraft)
+                .unwrap()
+                .as_deref(),
+            Some("maps/BWAIShotgun/(2)Ridge.scx")
+        );
+        assert!(starcraft.join("maps/BWAIShotgun/(2)Ridge.scx").exists());
+    }
+
+    #[test]
+    fn test_announced_map() {
+        let config: GameConfig<GameType> = toml::from_str(
+            "game_type = { Melee = [] }
+map_hint = 'hint.scx'",
+        )
+        .unwrap();
+        assert_eq!(config.announced_map(), Some("hint.scx"));
+
+        let config: GameConfig<GameType> = toml::from_str(
+            "game_type = { Melee = [] }
+map = 'map.scx'
+map_hint = 'hint.scx'",
+        )
+        .unwrap();
+        assert_eq!(config.announced_map(), Some("map.scx"));
+    }
+
+    #[test]
+    fn test_announcement() {
+        assert_eq!(
+            expand_template(
+                "{bot} {game_id} {unknown}",
+                &[("bot", "A"), ("game_id", "1")]
+            ),
+            "A 1 {unknown}"
+        );
+        let config: GameConfig<GameType> = toml::from_str(
+            "game_type = { Melee = [] }
+map = 'maps/sscai/(2)Eclipse 1.2.scx'
+announce = '{bots}, game 3 of 5, map {map}'",
+        )
+        .unwrap();
+        assert_eq!(
+            config.announcement(&["BananaBrain", "Stardust"], "1"),
+            Some("BananaBrain vs Stardust, game 3 of 5, map (2)Eclipse 1.2".to_string())
+        );
+    }
+
+    #[test]
+    fn test_validate_game_type() {
+        let melee = |names: &[&str]| {
+            let bots = names
+                .iter()
+                .map(|name| BotLaunchConfig {
+                    name: name.to_string(),
+                    ..Default::default()
+                })
+                .collect();
+            ValidatedGameType::try_from((GameType::Melee(bots), Path::new("starcraft")))
+        };
+        let game_type = melee(&["A", "B"]).unwrap();
+        assert_eq!(game_type.validation_context().max_players, 8);
+        assert_eq!(
+            game_type.validation_context().starcraft_path,
+            Path::new("starcraft")
+        );
+        assert!(melee(&[]).is_err());
+        assert!(melee(&["A"; 9]).is_err());
+        assert!(melee(&["A", " "]).is_err());
+
+        let top_vs_bottom = |extra: &str| {
+            let config: GameConfig<GameType> = toml::from_str(&format!(
+                "[game_type.TopVsBottom]
+forces = [
+    {{ name = 'Top', bots = [{{ name = 'A' }}, {{ name = 'B' }}] }},
+    {{ name = 'Bottom', bots = [{{ name = 'C' }}] }},
+]
+{extra}"
+            ))
+            .unwrap();
+            config.validate_game_type(Path::new("starcraft"))
+        };
+        let config = top_vs_bottom("").unwrap();
+        let game_type = &config.game_type;
+        assert_eq!(game_type.validation_context().min_players, 2);
+        assert_eq!(game_type.force_sizes(), Some((2, 1)));
+        let names: Vec<_> = game_type.bots().iter().map(|it| it.name.as_str()).collect();
+        assert_eq!(names, ["A", "B", "C"]);
+        assert!(top_vs_bottom("balanced = true").is_err());
+    }
+
+    #[test]
+    fn test_lobby_players() {
+        let config = |extra: &str| {
+            toml::from_str::<GameConfig<GameType>>(&format!(
+                "game_type = {{ Melee = [] }}\n{extra}"
+            ))
+            .unwrap()
+        };
+        assert_eq!(config("").lobby_players(2, 8).unwrap(), (2, 2));
+        assert_eq!(
+            config("max_players = 4").lobby_players(2, 8).unwrap(),
+            (2, 4)
+        );
+        assert_eq!(
+            config("min_players = 3").lobby_players(2, 8).unwrap(),
+            (3, 3)
+        );
+        assert!(config("min_players = 1").lobby_players(2, 8).is_err());
+        assert!(config("min_players = 4\nmax_players = 3")
+            .lobby_players(2, 8)
+            .is_err());
+        assert!(config("max_players = 9").lobby_players(2, 8).is_err());
+        assert!(config("max_players = 7\ncomputer_races = ['Zerg', 'Zerg']")
+            .lobby_players(2, 8)
+            .is_err());
+        assert!(config("human_host = true\nmax_players = 4")
+            .lobby_players(2, 8)
+            .is_err());
+        assert_eq!(
+            config("allow_human_observer = true")
+                .lobby_players(2, 8)
+                .unwrap(),
+            (2, 3)
+        );
+        assert!(config("human_host = true\nallow_human_observer = true")
+            .lobby_players(2, 8)
+            .is_err());
+    }
+
+    #[test]
+    fn test_validate_computer_races() {
+        let config = |bots: &str, extra: &str| {
+            toml::from_str::<GameConfig<GameType>>(&format!(
+                "map = 'maps/map.scx'\ncomputer_races = ['Zerg']\ngame_type = {{ Melee = [{bots}] }}\n{extra}"
+            ))
+            .unwrap()
+        };
+        assert!(config("{ name = 'A' }", "").validate().is_ok());
+        assert!(config("{ name = 'A' }, { name = 'B' }", "")
+            .validate()
+            .is_err());
+        assert!(config("{ name = 'A' }", "max_players = 2")
+            .validate()
+            .is_err());
+    }
+
+    #[test]
+    fn test_speed_preset() {
+        let config: GameConfig<GameType> = toml::from_str("game_type = { Melee = [] }").unwrap();
+        assert_eq!(config.effective_latency_frames(), 3);
+
+        let config: GameConfig<GameType> = toml::from_str(
+            "game_type = { Melee = [] }
+latency_frames = 5
+speed_preset = 'Normal'",
+        )
+        .unwrap();
+        assert_eq!(config.effective_latency_frames(), 2);
+
+        let config: GameConfig<GameType> = toml::from_str(
+            "game_type = { Melee = [] }
+speed_preset = { Custom = 7 }",
+        )
+        .unwrap();
+        assert_eq!(config.effective_latency_frames(), 7);
+    }
+
+    #[test]
+    fn test_speed_preset_latency_frames() {
+        let latency_frames = [
+            GameSpeedPreset::Slowest,
+            GameSpeedPreset::Slower,
+            GameSpeedPreset::Slow,
+            GameSpeedPreset::Normal,
+            GameSpeedPreset::Fast,
+            GameSpeedPreset::Faster,
+            GameSpeedPreset::Fastest,
+        ]
+        .map(GameSpeedPreset::to_latency_frames);
+        // Always rounded up, ie. 'Fast' would be 2.25 frames
+        assert_eq!(latency_frames, [1, 2, 2, 2, 3, 3, 3]);
+    }
+
+    #[test]
+    fn test_bot_args() {
+        let definition: BotDefinition =
+            toml::from_str("race = 'Terran'\nargs = ['--config', 'bot.json']").unwrap();
+        let config = BotLaunchConfig {
+            name: "Bot".to_string(),
+            args: vec!["--debug".to_string()],
+            ..Default::default()
+        };
+        let mut warnings = vec![];
+        let args = bot_args(
+            &definition,
+            &config,
+            &Binary::Exe(PathBuf::from("Bot.exe")),
+            &mut warnings,
+        );
+        assert_eq!(args, ["--config", "bot.json", "--debug"]);
+        assert!(warnings.is_empty());
+
+        let args = bot_args(
+            &definition,
+            &config,
+            &Binary::Dll(PathBuf::from("Bot.dll")),
+            &mut warnings,
+        );
+        assert!(args.is_empty());
+        assert_eq!(
+            warnings,
+            ["'Bot' is a DLL bot, which is loaded by BWAPI - ignoring its command line arguments"]
+        );
+    }
+
+    #[test]
+    fn test_time_limit_exceeded() {
+        let exceeded = |last_frame| TimeoutAction::time_limit_exceeded(Some(1000), last_frame);
+        // The TM left in frame 1000 without logging it
+        assert!(exceeded(Some(999)));
+        assert!(exceeded(Some(1000)));
+        assert!(exceeded(Some(1200)));
+        assert!(!exceeded(Some(998)));
+        assert!(!exceeded(None));
+        assert!(!TimeoutAction::time_limit_exceeded(None, Some(1200)));
+    }
+}
@@ -0,0 +1,86 @@
+//! Lists of games to run one after the other, ie. all games of a tournament round. Each game is
+//! configured like 'game.toml'.
+use std::fs::read_to_string;
+use std::path::Path;
+
+use anyhow::{ensure, Context};
+use serde::Deserialize;
+
+use crate::{GameConfig, GameType};
+
+#[derive(Deserialize)]
+struct TomlManifest {
+    games: Vec<GameConfig<GameType>>,
+}
+
+/// Reads a '.jsonl' manifest (one game per line) or a '.toml' manifest (a `[[games]]` table per
+/// game)
+pub fn load_manifest(path: &Path) -> anyhow::Result<Vec<GameConfig<GameType>>> {
+    let content = read_to_string(path)
+        .with_context(|| format!("Could not read manifest '{}'", path.display()))?;
+    let games = parse_manifest(&content, path.extension().is_some_and(|ext| ext == "jsonl"))
+        .with_context(|| format!("Manifest '{}' is invalid", path.display()))?;
+    ensure!(
+        !games.is_empty(),
+        "Manifest '{}' contains no games",
+        path.display()
+    );
+    Ok(games)
+}
+
+fn parse_manifest(content: &str, json_lines: bool) -> anyhow::Result<Vec<GameConfig<GameType>>> {
+    let games = if json_lines {
+        content
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(index, line)| {
+                serde_json::from_str(line).with_context(|| format!("Line {} is invalid", index + 1))
+            })
+            .collect::<anyhow::Result<Vec<GameConfig<GameType>>>>()?
+    } else {
+        toml::from_str::<TomlManifest>(content)?.games
+    };
+    for (index, game) in games.iter().enumerate() {
+        game.validate()
+            .with_context(|| format!("Game {} is invalid", index + 1))?;
+    }
+    Ok(games)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::manifest::parse_manifest;
+
+    #[test]
+    fn test_parse_manifest() {
+        let games = parse_manifest(
+            "[[games]]
+map = 'maps/(2)Destination.scx'
+game_type = { Melee = [{ name = 'NitekatT' }, { name = 'ZergHell' }] }
+
+[[games]]
+map = 'maps/(4)Python.scx'
+game_type = { Melee = [{ name = 'MarineHell' }, { name = 'ZergHell' }] }
+",
+            false,
+        )
+        .unwrap();
+        assert_eq!(games.len(), 2);
+        assert_eq!(games[1].map.as_deref(), Some("maps/(4)Python.scx"));
+
+        let games = parse_manifest(
+            r#"{"map": "maps/(2)Destination.scx", "game_type": {"Melee": [{"name": "NitekatT"}]}}
+
+{"human_host": true, "game_type": {"Melee": [{"name": "ZergHell"}]}}
+"#,
+            true,
+        )
+        .unwrap();
+        assert_eq!(games.len(), 2);
+        assert!(games[1].human_host);
+
+        // Bot hosted games need a map
+        assert!(parse_manifest(r#"{"game_type": {"Melee": []}}"#, true).is_err());
+    }
+}
@@ -0,0 +1,253 @@
+//! Finds maps by a part of their name, ie. "(2)Destination" instead of
+//! "maps/(2)Destination 1.0.scx", and makes sure BWAPI can handle their file name.
+use std::fs::{copy, create_dir_all, read_dir};
+use std::path::{Component, Path, PathBuf};
+
+use anyhow::{ensure, Context};
+use log::info;
+
+use crate::botsetup::matches_pattern;
+
+/// Maps with invalid file names are copied into this folder (relative to StarCraft), see
+/// `auto_rename_maps`
+pub const RENAMED_MAPS_FOLDER: &str = "maps/BWAIShotgun";
+
+/// Longest map path (absolute, as BWAPI 4.x gets it) the map selection of auto_menu can open
+pub const MAX_MAP_PATH_LEN: usize = 259;
+
+/// Finds problems of a map hosted by a headful bot: auto_menu silently stops at the map selection,
+/// if the map is not in the 'maps' folder of StarCraft or its path is too long
+pub fn auto_menu_map_problem(map: &str, starcraft_path: &Path) -> Option<String> {
+    let map_path = Path::new(map);
+    let relative = map_path.strip_prefix(starcraft_path).unwrap_or(map_path);
+    let mut components = relative.components();
+    let in_maps_folder = matches!(
+        components.next(),
+        Some(Component::Normal(folder)) if folder.eq_ignore_ascii_case("maps")
+    ) && components.all(|it| matches!(it, Component::Normal(_)));
+    if !in_maps_folder {
+        return Some(format!(
+            "is not in the 'maps' folder of '{}'",
+            starcraft_path.display()
+        ));
+    }
+    let length = starcraft_path.join(relative).as_os_str().len();
+    (length > MAX_MAP_PATH_LEN).then(|| {
+        format!("has a path of {length} characters, but at most {MAX_MAP_PATH_LEN} are supported")
+    })
+}
+
+/// Checks the file name of the map for characters breaking BWAPI's ini parsing or auto_menu. The
+/// host would wait in the menu forever otherwise.
+pub fn check_map_file_name(map: &str) -> anyhow::Result<()> {
+    let file_name = Path::new(map)
+        .file_name()
+        .map(|it| it.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let problem = if file_name.contains([',', ';', '[', ']']) {
+        Some("contains one of ',', ';', '[' or ']'")
+    } else if file_name.trim() != file_name {
+        Some("starts or ends with spaces")
+    } else if !file_name.is_ascii() {
+        Some("contains non-ASCII characters")
+    } else {
+        None
+    };
+    if let Some(problem) = problem {
+        anyhow::bail!(
+            "The file name of map '{map}' {problem}, which BWAPI can't handle. Please rename the map or set 'auto_rename_maps = true'"
+        );
+    }
+    Ok(())
+}
+
+/// Replaces all characters rejected by `check_map_file_name` with '_'
+pub fn sanitized_map_file_name(file_name: &str) -> String {
+    file_name
+        .trim()
+        .chars()
+        .map(|c| {
+            if c.is_ascii() && !matches!(c, ',' | ';' | '[' | ']') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Copies the map (relative to StarCraft) to `RENAMED_MAPS_FOLDER` with a sanitized file name,
+/// returns the new map path relative to StarCraft. `reason` is logged.
+pub fn copy_sanitized_map(
+    map: &str,
+    starcraft_path: &Path,
+    reason: &str,
+) -> anyhow::Result<String> {
+    let source = starcraft_path.join(map);
+    let file_name = source
+        .file_name()
+        .with_context(|| format!("Map '{map}' has no file name"))?
+        .to_string_lossy();
+    let renamed = format!(
+        "{RENAMED_MAPS_FOLDER}/{}",
+        sanitized_map_file_name(&file_name)
+    );
+    let target = starcraft_path.join(&renamed);
+    create_dir_all(target.parent().expect("Renamed map without folder"))?;
+    copy(&source, &target).with_context(|| format!("Could not copy map '{}'", source.display()))?;
+    info!("Using '{renamed}' instead of '{map}', because {reason}");
+    Ok(renamed)
+}
+
+/// Recursively searches the 'maps' folder of StarCraft for maps with a file name containing
+/// `partial_name` (case-insensitive). Names with `*` or `?` are matched as glob pattern against
+/// the whole file name instead.
+pub fn find_map(partial_name: &str, starcraft_path: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let maps_folder = starcraft_path.join("maps");
+    ensure!(
+        maps_folder.is_dir(),
+        "Could not find map '{partial_name}', '{}' does not exist",
+        maps_folder.display()
+    );
+    let pattern = partial_name.to_lowercase();
+    let mut found = vec![];
+    search(&maps_folder, &pattern, &mut found);
+    found.sort();
+    Ok(found)
+}
+
+/// Symlinked folders are not followed, they might link back to a parent folder
+fn search(folder: &Path, pattern: &str, found: &mut Vec<PathBuf>) {
+    let Ok(entries) = read_dir(folder) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if entry.file_type().is_ok_and(|it| it.is_dir()) {
+            search(&path, pattern, found);
+            continue;
+        }
+        let is_map = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("scx") || ext.eq_ignore_ascii_case("scm"));
+        let name = entry.file_name().to_string_lossy().to_lowercase();
+        if is_map && name_matches(&name, pattern) {
+            found.push(path);
+        }
+    }
+}
+
+fn name_matches(name: &str, pattern: &str) -> bool {
+    if pattern.contains(['*', '?']) {
+        matches_pattern(pattern, name)
+    } else {
+        name.contains(pattern)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::maps::{
+        auto_menu_map_problem, check_map_file_name, copy_sanitized_map, find_map, name_matches,
+        sanitized_map_file_name,
+    };
+    use crate::test_util::TempDir;
+    use std::fs::{create_dir_all, write};
+    use std::path::Path;
+
+    #[test]
+    fn test_auto_menu_map_problem() {
+        let starcraft = Path::new("/games/StarCraft");
+        assert_eq!(
+            auto_menu_map_problem("maps/(2)Destination.scx", starcraft),
+            None
+        );
+        assert_eq!(
+            auto_menu_map_problem("/games/StarCraft/Maps/sscai/(2)Destination.scx", starcraft),
+            None
+        );
+        for map in [
+            "(2)Destination.scx",
+            "custom/(2)Destination.scx",
+            "maps/../(2)Destination.scx",
+            "/downloads/maps/(2)Destination.scx",
+        ] {
+            assert!(auto_menu_map_problem(map, starcraft).is_some(), "{map}");
+        }
+        let long_map = format!("maps/{}.scx", "x".repeat(250));
+        assert!(auto_menu_map_problem(&long_map, starcraft).is_some());
+    }
+
+    #[test]
+    fn test_check_map_file_name() {
+        check_map_file_name("maps/sscai/(2)Destination 1.0.scx").unwrap();
+        check_map_file_name("maps/(4)Fighting Spirit 1.3.scx").unwrap();
+        // Names which made hosts wait in the menu forever
+        for map in [
+            "maps/(2)Heartbreak Ridge, 2.1.scx",
+            "maps/(4)Python;1.3.scx",
+            "maps/[AIIDE](4)Andromeda.scx",
+            "maps/ (2)Eclipse.scx",
+            "maps/(2)Eclipse.scx ",
+            "maps/(4)투혼 1.3.scx",
+        ] {
+            assert!(check_map_file_name(map).is_err(), "{map}");
+        }
+    }
+
+    #[test]
+    fn test_copy_sanitized_map() {
+        assert_eq!(
+            sanitized_map_file_name(" [AIIDE](2)Heartbreak Ridge, 2.1.scx"),
+            "_AIIDE_(2)Heartbreak Ridge_ 2.1.scx"
+        );
+        assert_eq!(sanitized_map_file_name("(4)투혼.scx"), "(4)__.scx");
+
+        let starcraft = TempDir::new("sanitize-map");
+        create_dir_all(starcraft.join("maps")).unwrap();
+        write(starcraft.join("maps").join("(2)Ridge, 2.1.scx"), "map").unwrap();
+        let renamed = copy_sanitized_map("maps/(2)Ridge, 2.1.scx", &starcraft, "test").unwrap();
+        assert_eq!(renamed, "maps/BWAIShotgun/(2)Ridge_ 2.1.scx");
+        assert!(starcraft.join(renamed).exists());
+    }
+
+    #[test]
+    fn test_name_matches() {
+        assert!(name_matches("(2)destination 1.0.scx", "(2)destination"));
+        assert!(name_matches("(2)destination 1.0.scx", "(2)*.scx"));
+        assert!(name_matches("(2)destination 1.0.scx", "(?)destination*"));
+        assert!(!name_matches("(4)python.scx", "(2)*"));
+    }
+
+    #[test]
+    fn test_find_map() {
+        let starcraft = TempDir::new("find-map");
+        let ladder = starcraft.join("maps").join("sscai");
+        create_dir_all(&ladder).unwrap();
+        write(ladder.join("(2)Destination 1.0.scx"), "").unwrap();
+        write(ladder.join("(4)Python 1.3.scx"), "").unwrap();
+        write(ladder.join("readme.txt"), "").unwrap();
+
+        assert_eq!(
+            find_map("(2)destination", &starcraft).unwrap(),
+            [ladder.join("(2)Destination 1.0.scx")]
+        );
+        assert_eq!(find_map("*.scx", &starcraft).unwrap().len(), 2);
+        assert!(find_map("Fighting Spirit", &starcraft).unwrap().is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_find_map_symlink_loop() {
+        let starcraft = TempDir::new("find-map-symlink-loop");
+        let ladder = starcraft.join("maps").join("sscai");
+        create_dir_all(&ladder).unwrap();
+        write(ladder.join("(2)Destination 1.0.scx"), "").unwrap();
+        std::os::unix::fs::symlink(starcraft.join("maps"), ladder.join("maps")).unwrap();
+
+        assert_eq!(
+            find_map("(2)destination", &starcraft).unwrap(),
+            [ladder.join("(2)Destination 1.0.scx")]
+        );
+    }
+}
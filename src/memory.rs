@@ -0,0 +1,46 @@
+use std::process::Child;
+
+/// Returns the resident memory (working set) of the given process in bytes, if it can be queried.
+///
+/// Under Wine (or any other wrapper) the process is the launched wrapper. Wine executes the bot in
+/// the same process, so the value is usually correct. Wrappers starting the bot in a separate
+/// process (ie. Sandboxie) can't be monitored.
+pub fn resident_memory(child: &Child) -> Option<u64> {
+    #[cfg(target_os = "windows")]
+    {
+        use std::os::windows::io::AsRawHandle;
+        use windows_sys::Win32::System::ProcessStatus::{
+            K32GetProcessMemoryInfo, PROCESS_MEMORY_COUNTERS,
+        };
+
+        let mut counters: PROCESS_MEMORY_COUNTERS = unsafe { std::mem::zeroed() };
+        let ok = unsafe {
+            K32GetProcessMemoryInfo(
+                child.as_raw_handle() as _,
+                &mut counters,
+                std::mem::size_of::<PROCESS_MEMORY_COUNTERS>() as u32,
+            )
+        };
+        if ok != 0 {
+            Some(counters.WorkingSetSize as u64)
+        } else {
+            None
+        }
+    }
+    #[cfg(not(target_os = "windows"))]
+    {
+        let status = std::fs::read_to_string(format!("/proc/{}/status", child.id())).ok()?;
+        status
+            .lines()
+            .find_map(|line| line.strip_prefix("VmRSS:"))
+            .and_then(|value| {
+                value
+                    .trim()
+                    .trim_end_matches("kB")
+                    .trim()
+                    .parse::<u64>()
+                    .ok()
+            })
+            .map(|kb| kb * 1024)
+    }
+}
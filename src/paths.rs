@@ -0,0 +1,61 @@
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Context};
+
+/// Environment variable overriding the BWAIShotgun home folder, `--home` takes precedence
+pub const HOME_ENV_VAR: &str = "BWAISHOTGUN_HOME";
+
+/// Folders used by BWAIShotgun, resolved once at startup
+#[derive(Debug, Clone)]
+pub struct Paths {
+    /// Relative paths in the configuration are resolved against this folder
+    pub home: PathBuf,
+    /// 'shotgun.toml' and 'game.toml'
+    pub config_dir: PathBuf,
+    pub bots_dir: PathBuf,
+    pub tools_dir: PathBuf,
+    /// Tournament modules
+    pub tm_dir: PathBuf,
+    pub downloads_dir: PathBuf,
+}
+
+impl Paths {
+    /// Uses the given home folder (ie. `--home`), `BWAISHOTGUN_HOME` or the folder of the
+    /// executable - in that order
+    pub fn resolve(home: Option<PathBuf>) -> anyhow::Result<Self> {
+        let home = match home.or_else(|| std::env::var_os(HOME_ENV_VAR).map(PathBuf::from)) {
+            Some(home) => home,
+            None => std::env::current_exe()
+                .context("Could not find executable")?
+                .parent()
+                .ok_or_else(|| anyhow!("BWAIShotgun folder does not exist"))?
+                .to_path_buf(),
+        };
+        Ok(Self::with_home(home))
+    }
+
+    pub fn with_home(home: PathBuf) -> Self {
+        Self {
+            config_dir: home.clone(),
+            bots_dir: home.join("bots"),
+            tools_dir: home.join("tools"),
+            tm_dir: home.join("tm"),
+            downloads_dir: home.join("download"),
+            home,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::paths::Paths;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_resolve_home() {
+        let paths = Paths::resolve(Some(PathBuf::from("shotgun"))).unwrap();
+        assert_eq!(paths.config_dir, PathBuf::from("shotgun"));
+        assert_eq!(paths.bots_dir, PathBuf::from("shotgun").join("bots"));
+        assert_eq!(paths.tools_dir, PathBuf::from("shotgun").join("tools"));
+    }
+}
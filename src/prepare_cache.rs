@@ -0,0 +1,159 @@
+//! Caches checksums of files used to prepare a bot (BWAPI.dll, tournament modules), so batches of
+//! games don't read them again and again. A checksum is reused as long as size and modification
+//! time of the file are unchanged.
+use std::collections::BTreeMap;
+use std::fs::{read, read_to_string, write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, UNIX_EPOCH};
+
+use anyhow::Context;
+use crc::{Crc, CRC_32_ISO_HDLC};
+use log::debug;
+use serde::{Deserialize, Serialize};
+
+/// File name of the cache in the bot folder
+const CACHE_FILE: &str = ".bwaishotgun_cache.json";
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+struct CachedCrc {
+    size: u64,
+    modified_nanos: u128,
+    crc: u32,
+}
+
+#[derive(Default)]
+pub struct PrepareCache {
+    path: PathBuf,
+    entries: BTreeMap<String, CachedCrc>,
+    /// Bytes which did not need to be read, because their checksum was cached
+    pub bytes_saved: u64,
+    changed: bool,
+}
+
+impl PrepareCache {
+    /// Loads the cache of the bot, an unreadable cache is treated as empty
+    pub fn load(bot_folder: &Path) -> Self {
+        let path = bot_folder.join(CACHE_FILE);
+        let entries = read_to_string(&path)
+            .ok()
+            .and_then(|it| serde_json::from_str(&it).ok())
+            .unwrap_or_default();
+        Self {
+            path,
+            entries,
+            ..Default::default()
+        }
+    }
+
+    /// CRC32 of the file, read from the cache if the file didn't change
+    pub fn crc(&mut self, file: &Path) -> anyhow::Result<u32> {
+        let metadata = file
+            .metadata()
+            .with_context(|| format!("Could not check '{}'", file.to_string_lossy()))?;
+        let size = metadata.len();
+        let modified_nanos = metadata
+            .modified()
+            .ok()
+            .and_then(|it| it.duration_since(UNIX_EPOCH).ok())
+            .unwrap_or(Duration::ZERO)
+            .as_nanos();
+        let key = file.to_string_lossy().to_string();
+        if let Some(cached) = self.entries.get(&key) {
+            if cached.size == size && cached.modified_nanos == modified_nanos {
+                self.bytes_saved += size;
+                return Ok(cached.crc);
+            }
+        }
+        let crc = Crc::<u32>::new(&CRC_32_ISO_HDLC).checksum(
+            read(file)
+                .with_context(|| format!("Could not check '{}'", file.to_string_lossy()))?
+                .as_slice(),
+        );
+        self.entries.insert(
+            key,
+            CachedCrc {
+                size,
+                modified_nanos,
+                crc,
+            },
+        );
+        self.changed = true;
+        Ok(crc)
+    }
+
+    /// Copies `source` to `target`, unless both are equal already. Returns whether it copied.
+    pub fn copy_if_changed(&mut self, source: &Path, target: &Path) -> anyhow::Result<bool> {
+        if target.exists()
+            && source.metadata()?.len() == target.metadata()?.len()
+            && self.crc(source)? == self.crc(target)?
+        {
+            debug!("'{}' is up to date", target.display());
+            return Ok(false);
+        }
+        std::fs::copy(source, target)?;
+        Ok(true)
+    }
+
+    /// Writes the cache, if it changed. Failing to write it only costs time in the next game.
+    pub fn save(&self) {
+        if !self.changed {
+            return;
+        }
+        let result = serde_json::to_string(&self.entries)
+            .map_err(anyhow::Error::from)
+            .and_then(|it| Ok(write(&self.path, it)?));
+        if let Err(err) = result {
+            debug!("Could not write '{}': {err}", self.path.display());
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::prepare_cache::PrepareCache;
+    use crate::test_util::TempDir;
+    use std::fs::{read, write, File};
+    use std::time::{Duration, SystemTime};
+
+    #[test]
+    fn test_crc_cache() {
+        let bot = TempDir::new("prepare-cache");
+        let dll = bot.join("BWAPI.dll");
+        write(&dll, "BWAPI 4.4.0").unwrap();
+
+        let mut cache = PrepareCache::load(&bot);
+        let crc = cache.crc(&dll).unwrap();
+        assert_eq!(cache.bytes_saved, 0);
+        cache.save();
+
+        let mut cache = PrepareCache::load(&bot);
+        assert_eq!(cache.crc(&dll).unwrap(), crc);
+        assert_eq!(cache.bytes_saved, 11);
+
+        // Swapped by the user
+        write(&dll, "BWAPI 4.1.2!").unwrap();
+        assert_ne!(cache.crc(&dll).unwrap(), crc);
+    }
+
+    #[test]
+    fn test_copy_if_changed() {
+        let bot = TempDir::new("prepare-cache-copy");
+        let source = bot.join("TM.dll");
+        let target = bot.join("TM_copy.dll");
+        write(&source, "TM 1").unwrap();
+
+        let mut cache = PrepareCache::load(&bot);
+        assert!(cache.copy_if_changed(&source, &target).unwrap());
+        assert!(!cache.copy_if_changed(&source, &target).unwrap());
+        // Updated, same size
+        write(&source, "TM 2").unwrap();
+        File::options()
+            .write(true)
+            .open(&source)
+            .unwrap()
+            .set_modified(SystemTime::now() + Duration::from_secs(10))
+            .unwrap();
+        assert!(cache.copy_if_changed(&source, &target).unwrap());
+        assert_eq!(read(&target).unwrap(), b"TM 2");
+    }
+}
@@ -0,0 +1,193 @@
+//! Starts client bots outside of a game, to find bots which can't even start (ie. because of a
+//! missing DLL, the wrong architecture or Java version). In a game, these only show up as bots
+//! not connecting to BWAPI.
+use std::io::{BufRead, BufReader, Read};
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{channel, Sender};
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, ensure, Context};
+use log::{error, info, warn};
+
+use crate::botsetup::Binary;
+use crate::paths::Paths;
+use crate::setup::ComponentInstallation;
+use crate::wrapper::ExecutionWrapper;
+use crate::{client_bot_command, BotDefinition};
+
+/// Bots still running after this are considered started, and killed
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+/// Lines of output shown per probe
+const OUTPUT_LINES: usize = 5;
+/// Argument for jar bots without `probe_command`
+const DEFAULT_JAR_PROBE_ARG: &str = "--shotgun-probe";
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ProbeOutcome {
+    /// Still running at the timeout
+    Running,
+    /// Exited with the code, if there is one
+    Exited(Option<i32>),
+}
+
+#[derive(Debug)]
+pub struct ProbeResult {
+    pub outcome: ProbeOutcome,
+    /// The first lines of stdout and stderr
+    pub output: Vec<String>,
+}
+
+/// Runs the command until it exits or the timeout passed. Fails, if it can't be started at all.
+/// Keeps at most `max_lines` of output.
+pub fn probe(mut cmd: Command, timeout: Duration, max_lines: usize) -> anyhow::Result<ProbeResult> {
+    let mut child = cmd
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Could not start {cmd:?}"))?;
+    let (sender, receiver) = channel();
+    if let Some(stdout) = child.stdout.take() {
+        forward_lines(stdout, sender.clone());
+    }
+    if let Some(stderr) = child.stderr.take() {
+        forward_lines(stderr, sender);
+    }
+    let started = Instant::now();
+    let outcome = loop {
+        if let Some(status) = child.try_wait()? {
+            break ProbeOutcome::Exited(status.code());
+        }
+        if started.elapsed() >= timeout {
+            child.kill().ok();
+            child.wait().ok();
+            break ProbeOutcome::Running;
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    };
+    // Processes started by the bot might keep the pipes open, don't wait for them
+    let mut output = vec![];
+    while let Ok(line) = receiver.recv_timeout(Duration::from_millis(200)) {
+        if output.len() < max_lines {
+            output.push(line);
+        }
+    }
+    Ok(ProbeResult { outcome, output })
+}
+
+fn forward_lines(pipe: impl Read + Send + 'static, sender: Sender<String>) {
+    std::thread::spawn(move || {
+        for line in BufReader::new(pipe).lines().map_while(Result::ok) {
+            if sender.send(line).is_err() {
+                break;
+            }
+        }
+    });
+}
+
+/// Probes all given bots, fails if a probe failed
+pub fn probe_bots(
+    paths: &Paths,
+    bots: &[String],
+    wrapper: &ExecutionWrapper,
+    java: &ComponentInstallation,
+    bot_binary_scan_depth: usize,
+) -> anyhow::Result<()> {
+    let mut failed = vec![];
+    for name in bots {
+        if let Err(err) = probe_bot(paths, name, wrapper, java, bot_binary_scan_depth) {
+            error!("Probing '{name}' failed: {err:?}");
+            failed.push(format!("'{name}'"));
+        }
+    }
+    ensure!(failed.is_empty(), "Probing {} failed", failed.join(", "));
+    Ok(())
+}
+
+fn probe_bot(
+    paths: &Paths,
+    name: &str,
+    wrapper: &ExecutionWrapper,
+    java: &ComponentInstallation,
+    bot_binary_scan_depth: usize,
+) -> anyhow::Result<()> {
+    let bot_folder = paths.bots_dir.join(name);
+    let definition = BotDefinition::load(name, &bot_folder)?;
+    let (binary, args, explicit) = match &definition.probe_command {
+        Some(probe_command) => {
+            let Some((program, args)) = probe_command.split_first() else {
+                bail!("'probe_command' is empty");
+            };
+            let binary = Binary::from_path(&bot_folder.join(program))
+                .with_context(|| format!("'{program}' is not an exe or jar"))?;
+            (binary, args.to_vec(), true)
+        }
+        None => match definition.find_binary(paths, &bot_folder, bot_binary_scan_depth)? {
+            binary @ Binary::Jar(_) => (binary, vec![DEFAULT_JAR_PROBE_ARG.to_string()], false),
+            Binary::Exe(_) => {
+                info!("'{name}' has no 'probe_command' in 'bot.toml', skipping it");
+                return Ok(());
+            }
+            Binary::Dll(_) => {
+                info!("'{name}' is a DLL bot, it can only be started by BWAPI");
+                return Ok(());
+            }
+        },
+    };
+    let mut cmd = client_bot_command(&binary, &args, wrapper, java)?
+        .context("DLL bots can't be probed, 'probe_command' must be an exe or jar")?;
+    cmd.current_dir(&bot_folder);
+    info!("Probing '{name}': {cmd:?}");
+    let result = probe(cmd, PROBE_TIMEOUT, OUTPUT_LINES)?;
+    for line in result.output.iter() {
+        info!("  {line}");
+    }
+    match result.outcome {
+        ProbeOutcome::Running => info!(
+            "'{name}' started and was still running after {}s",
+            PROBE_TIMEOUT.as_secs()
+        ),
+        ProbeOutcome::Exited(Some(0)) => info!("'{name}' started and exited"),
+        ProbeOutcome::Exited(code) => {
+            let code = code.map_or("none".to_string(), |it| it.to_string());
+            // Bots don't know the default argument, exiting because of it is fine
+            ensure!(!explicit, "'{name}' exited with code {code}");
+            warn!("'{name}' exited with code {code}, it might not be able to start");
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::probe::probe;
+    #[cfg(unix)]
+    use crate::probe::ProbeOutcome;
+    use std::process::Command;
+    use std::time::Duration;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_probe() {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "echo started; echo missing.dll >&2; exit 3"]);
+        let result = probe(cmd, Duration::from_secs(5), 5).unwrap();
+        assert_eq!(result.outcome, ProbeOutcome::Exited(Some(3)));
+        assert!(result.output.contains(&"missing.dll".to_string()));
+
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "sleep 5"]);
+        let result = probe(cmd, Duration::from_millis(200), 5).unwrap();
+        assert_eq!(result.outcome, ProbeOutcome::Running);
+    }
+
+    #[test]
+    fn test_probe_missing_executable() {
+        assert!(probe(
+            Command::new("bwaishotgun-missing"),
+            Duration::from_secs(1),
+            5
+        )
+        .is_err());
+    }
+}
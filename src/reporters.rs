@@ -0,0 +1,324 @@
+//! Reporters write the result of every game in the format other tools ingest. They are enabled by
+//! name with `reporters` in 'game.toml', options are set in `[reporter.<name>]`. The `result_file`
+//! of 'shotgun.toml' and `--results-csv` are reported the same way.
+use std::fs::{copy, create_dir_all, read_dir, write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use anyhow::Context;
+use log::{error, info};
+use serde::Deserialize;
+
+use crate::results::{GameResult, ResultFileWriter, ResultFormat};
+
+pub trait Reporter {
+    fn name(&self) -> &'static str;
+    fn report(&mut self, result: &GameResult) -> anyhow::Result<()>;
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ReporterKind {
+    /// One JSON object per game, appended to a file
+    Json,
+    /// One row per game, appended to a file (see `CSV_HEADER`)
+    Csv,
+    /// A folder per game with the result, replays and logs, like BASIL publishes them
+    Basil,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct ReporterOptions {
+    pub json: FileReporterOptions,
+    pub csv: FileReporterOptions,
+    pub basil: BasilOptions,
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub struct FileReporterOptions {
+    /// Relative to BWAIShotgun, defaults to 'results/results.<json|csv>'
+    pub file: Option<PathBuf>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub struct BasilOptions {
+    /// Relative to BWAIShotgun, defaults to 'results/basil'
+    pub folder: Option<PathBuf>,
+}
+
+/// Opens all enabled reporters, so an unusable file doesn't waste a whole game
+pub fn create_reporters(
+    kinds: &[ReporterKind],
+    options: &ReporterOptions,
+    home: &Path,
+) -> anyhow::Result<Vec<Box<dyn Reporter>>> {
+    let file = |options: &FileReporterOptions, default: &str| {
+        home.join(options.file.as_deref().unwrap_or(Path::new(default)))
+    };
+    kinds
+        .iter()
+        .map(|kind| -> anyhow::Result<Box<dyn Reporter>> {
+            Ok(match kind {
+                ReporterKind::Json => file_reporter(
+                    &file(&options.json, "results/results.jsonl"),
+                    ResultFormat::Json,
+                )?,
+                ReporterKind::Csv => file_reporter(
+                    &file(&options.csv, "results/results.csv"),
+                    ResultFormat::Csv,
+                )?,
+                ReporterKind::Basil => Box::new(BasilReporter {
+                    folder: home.join(
+                        options
+                            .basil
+                            .folder
+                            .as_deref()
+                            .unwrap_or(Path::new("results/basil")),
+                    ),
+                }),
+            })
+        })
+        .collect()
+}
+
+/// Runs all reporters, a failing reporter doesn't stop the others
+pub fn report_all(reporters: &mut [Box<dyn Reporter>], result: &GameResult) {
+    for reporter in reporters.iter_mut() {
+        if let Err(err) = reporter.report(result) {
+            error!("Reporter '{}' failed: {err:?}", reporter.name());
+        }
+    }
+}
+
+/// Appends the result of every game to the file, opened right away
+pub fn file_reporter(path: &Path, format: ResultFormat) -> anyhow::Result<Box<dyn Reporter>> {
+    Ok(Box::new(FileReporter {
+        writer: ResultFileWriter::open(path, format)?,
+        format,
+    }))
+}
+
+struct FileReporter {
+    writer: ResultFileWriter,
+    format: ResultFormat,
+}
+
+impl Reporter for FileReporter {
+    fn name(&self) -> &'static str {
+        match self.format {
+            ResultFormat::Json => "json",
+            ResultFormat::Csv => "csv",
+            ResultFormat::Aiide => "aiide",
+            ResultFormat::Sscait => "sscait",
+        }
+    }
+
+    fn report(&mut self, result: &GameResult) -> anyhow::Result<()> {
+        self.writer.write(result)
+    }
+}
+
+/// Writes the game the way BASIL (and sc-docker) lay it out: `<folder>/GAME_<game id>/` with
+/// `result.json`, the replay of each player as `player_<n>.rep` and its logs in `logs_<n>`, with
+/// `n` counting from 0 in launch order
+struct BasilReporter {
+    folder: PathBuf,
+}
+
+impl Reporter for BasilReporter {
+    fn name(&self) -> &'static str {
+        "basil"
+    }
+
+    fn report(&mut self, result: &GameResult) -> anyhow::Result<()> {
+        let game_folder = self.folder.join(format!("GAME_{}", result.game_id));
+        create_dir_all(&game_folder)
+            .with_context(|| format!("Could not create '{}'", game_folder.display()))?;
+        write(
+            game_folder.join("result.json"),
+            serde_json::to_string_pretty(result)?,
+        )?;
+        for (player, bot) in result.bot_logs.iter().enumerate() {
+            for (replay_index, replay) in find_replays(&bot.replay_dir, result.started)
+                .iter()
+                .enumerate()
+            {
+                // Only bots restarted during the game write more than one replay
+                let file_name = if replay_index == 0 {
+                    format!("player_{player}.rep")
+                } else {
+                    format!("player_{player}_{replay_index}.rep")
+                };
+                copy(replay, game_folder.join(file_name))?;
+            }
+            copy_folder(&bot.log_dir, &game_folder.join(format!("logs_{player}")))?;
+        }
+        info!("Reported the game to '{}'", game_folder.display());
+        Ok(())
+    }
+}
+
+/// The folder replays are written to: The part of `replay_path` before the first placeholder,
+/// relative to the bot folder (StarCraft runs in it)
+pub fn replay_folder(bot_folder: &Path, replay_path: &str) -> PathBuf {
+    let fixed: PathBuf = Path::new(replay_path)
+        .parent()
+        .unwrap_or(Path::new(""))
+        .components()
+        .take_while(|it| !it.as_os_str().to_string_lossy().contains(['$', '%']))
+        .collect();
+    bot_folder.join(fixed)
+}
+
+/// Replays in `folder` (and below) modified after `since`, oldest first
+fn find_replays(folder: &Path, since: SystemTime) -> Vec<PathBuf> {
+    let mut replays = vec![];
+    let mut folders = vec![folder.to_path_buf()];
+    while let Some(folder) = folders.pop() {
+        for entry in read_dir(folder).into_iter().flatten().flatten() {
+            let path = entry.path();
+            let modified = entry.metadata().and_then(|it| it.modified()).ok();
+            if path.is_dir() {
+                folders.push(path);
+            } else if path
+                .extension()
+                .is_some_and(|it| it.eq_ignore_ascii_case("rep"))
+            {
+                if let Some(modified) = modified.filter(|it| *it >= since) {
+                    replays.push((modified, path));
+                }
+            }
+        }
+    }
+    replays.sort();
+    replays.into_iter().map(|(_, path)| path).collect()
+}
+
+fn copy_folder(source: &Path, target: &Path) -> anyhow::Result<()> {
+    let Ok(entries) = read_dir(source) else {
+        return Ok(());
+    };
+    create_dir_all(target)?;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            copy_folder(&path, &target.join(entry.file_name()))?;
+        } else {
+            copy(&path, target.join(entry.file_name()))?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::environment::Environment;
+    use crate::log_archive::BotLogs;
+    use crate::reporters::{
+        replay_folder, report_all, BasilReporter, Reporter, ReporterKind, ReporterOptions,
+    };
+    use crate::results::GameResult;
+    use crate::test_util::TempDir;
+    use std::fs::{create_dir_all, read_to_string, write};
+    use std::path::Path;
+    use std::time::{Duration, SystemTime};
+
+    struct FailingReporter;
+
+    impl Reporter for FailingReporter {
+        fn name(&self) -> &'static str {
+            "failing"
+        }
+
+        fn report(&mut self, _: &GameResult) -> anyhow::Result<()> {
+            anyhow::bail!("Ladder is down")
+        }
+    }
+
+    #[test]
+    fn test_replay_folder() {
+        assert_eq!(
+            replay_folder(
+                Path::new("bots/NitekatT"),
+                "replays/$Y $b $d/%MAP%_%BOTRACE%.rep"
+            ),
+            Path::new("bots/NitekatT/replays")
+        );
+        assert_eq!(
+            replay_folder(Path::new("bots/NitekatT"), "game.rep"),
+            Path::new("bots/NitekatT")
+        );
+    }
+
+    #[test]
+    fn test_reporter_config() {
+        #[derive(serde::Deserialize)]
+        struct Config {
+            reporters: Vec<ReporterKind>,
+            reporter: ReporterOptions,
+        }
+        let config: Config = toml::from_str(
+            "reporters = [\"json\", \"basil\"]\n[reporter.basil]\nfolder = 'ladder'\n",
+        )
+        .unwrap();
+        assert_eq!(config.reporters, [ReporterKind::Json, ReporterKind::Basil]);
+        assert_eq!(
+            config.reporter.basil.folder.as_deref(),
+            Some(Path::new("ladder"))
+        );
+        assert!(config.reporter.json.file.is_none());
+    }
+
+    #[test]
+    fn test_basil_reporter() {
+        let folder = TempDir::new("basil-reporter");
+        let bot = folder.join("bots").join("NitekatT");
+        let log_dir = bot.join("logs");
+        create_dir_all(&log_dir).unwrap();
+        write(log_dir.join("bot_out.log"), "gg").unwrap();
+        let replay_dir = bot.join("replays");
+        create_dir_all(replay_dir.join("2024 Jan 01")).unwrap();
+        write(replay_dir.join("2024 Jan 01").join("game.rep"), "rep").unwrap();
+
+        let result = GameResult {
+            game_id: "1700000000".to_string(),
+            game_name: "shotgun".to_string(),
+            started: SystemTime::now() - Duration::from_secs(60),
+            map: None,
+            replay_path: String::new(),
+            duration: Duration::from_secs(60),
+            duration_frames: None,
+            lobby_failure: None,
+            time_limit_exceeded: false,
+            launch_duration: Duration::ZERO,
+            total_duration: Duration::ZERO,
+            bots: vec![],
+            timings: vec![],
+            environment: Environment::default(),
+            bot_logs: vec![BotLogs {
+                name: "NitekatT".to_string(),
+                log_dir,
+                tm_dir: bot.join("tm"),
+                replay_dir,
+            }],
+        };
+        let mut reporters: Vec<Box<dyn Reporter>> = vec![
+            Box::new(FailingReporter),
+            Box::new(BasilReporter {
+                folder: folder.join("basil"),
+            }),
+        ];
+        report_all(&mut reporters, &result);
+
+        let game_folder = folder.join("basil").join("GAME_1700000000");
+        assert!(read_to_string(game_folder.join("result.json"))
+            .unwrap()
+            .contains("\"game_name\": \"shotgun\""));
+        assert_eq!(
+            read_to_string(game_folder.join("player_0.rep")).unwrap(),
+            "rep"
+        );
+        assert!(game_folder.join("logs_0").join("bot_out.log").exists());
+    }
+}
@@ -0,0 +1,957 @@
+use std::fmt::{Display, Formatter};
+use std::fs::{create_dir_all, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use anyhow::Context;
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::environment::Environment;
+use crate::log_archive::BotLogs;
+use crate::tm_results::{GameResultCsvWriter, StartLocation};
+use crate::Race;
+
+/// Outcome of a single game, printed as summary table or JSON at the end of a run
+#[derive(Serialize, Debug)]
+pub struct GameResult {
+    pub game_id: String,
+    pub game_name: String,
+    /// When the first bot was launched, as UTC timestamp (ie. '2023-11-14T22:13:20Z')
+    #[serde(serialize_with = "serialize_timestamp")]
+    pub started: SystemTime,
+    pub map: Option<String>,
+    pub replay_path: String,
+    /// From the start of the game until all bots exited. Without TM, the start of the game is
+    /// unknown and launching the first bot is used instead.
+    #[serde(serialize_with = "serialize_secs")]
+    pub duration: Duration,
+    /// The last frame logged by the TM of any bot, `None` without TM
+    pub duration_frames: Option<u32>,
+    /// Set, if the game never started. Only the TM reports the start of a game.
+    pub lobby_failure: Option<LobbyFailure>,
+    /// The game reached `time_out_at_frame`, see `timeout_action`
+    pub time_limit_exceeded: bool,
+    /// From launching the first bot until all bots were launched and client bots connected
+    #[serde(serialize_with = "serialize_secs")]
+    pub launch_duration: Duration,
+    /// From reading the game configuration until all bots exited
+    #[serde(serialize_with = "serialize_secs")]
+    pub total_duration: Duration,
+    pub bots: Vec<BotResult>,
+    /// Consecutive phases from preparing the bots until all of them exited
+    pub timings: Vec<PhaseTiming>,
+    pub environment: Environment,
+    /// Where the logs, TM files and replays of the bots are, in launch order
+    #[serde(skip)]
+    pub bot_logs: Vec<BotLogs>,
+}
+
+/// Why a game never left the lobby
+#[derive(Serialize, Debug)]
+pub struct LobbyFailure {
+    pub kind: LobbyFailureKind,
+    /// Changes of the game table until the failure, see `GameTableHistory`
+    pub game_table_history: String,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub enum LobbyFailureKind {
+    /// No TM reported the start of the game within `lobby_timeout_secs`
+    Timeout { secs: u64 },
+    /// The StarCraft of this bot exited first, before any TM reported the start of the game
+    Exited { bot: String },
+}
+
+impl Display for LobbyFailureKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LobbyFailureKind::Timeout { secs } => write!(
+                f,
+                "The game did not start within {secs}s ('lobby_timeout_secs')"
+            ),
+            LobbyFailureKind::Exited { bot } => {
+                write!(f, "StarCraft of '{bot}' exited before the game started")
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct PhaseTiming {
+    pub phase: String,
+    #[serde(serialize_with = "serialize_secs")]
+    pub duration: Duration,
+}
+
+/// Records consecutive phases of a run. If the run fails before `finish` is called, the phases
+/// recorded so far are logged.
+pub struct PhaseTimer {
+    phase_started: Instant,
+    timings: Vec<PhaseTiming>,
+    finished: bool,
+}
+
+impl PhaseTimer {
+    pub fn new() -> Self {
+        Self {
+            phase_started: Instant::now(),
+            timings: vec![],
+            finished: false,
+        }
+    }
+
+    /// Ends the current phase, the next one starts now
+    pub fn end_phase(&mut self, phase: impl Into<String>) {
+        self.timings.push(PhaseTiming {
+            phase: phase.into(),
+            duration: self.phase_started.elapsed(),
+        });
+        self.phase_started = Instant::now();
+    }
+
+    /// Starts the next phase now, without recording the current one (ie. waiting for the user)
+    pub fn skip_phase(&mut self) {
+        self.phase_started = Instant::now();
+    }
+
+    pub fn finish(mut self) -> Vec<PhaseTiming> {
+        self.finished = true;
+        std::mem::take(&mut self.timings)
+    }
+}
+
+impl Drop for PhaseTimer {
+    fn drop(&mut self) {
+        if !self.finished {
+            warn!(
+                "Timings until failure: {}{}failed after {:.1}s",
+                format_timings(&self.timings),
+                if self.timings.is_empty() { "" } else { ", " },
+                self.phase_started.elapsed().as_secs_f64()
+            );
+        }
+    }
+}
+
+fn format_timings(timings: &[PhaseTiming]) -> String {
+    timings
+        .iter()
+        .map(|it| format!("{} {:.1}s", it.phase, it.duration.as_secs_f64()))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[derive(Serialize, Debug)]
+pub struct BotResult {
+    pub name: String,
+    pub race: Race,
+    pub binary_type: &'static str,
+    pub bwapi_version: Option<&'static str>,
+    /// Position in the final launch order, the first launched bot is 0
+    pub launch_order: usize,
+    /// Set, if this bot hosted the game
+    pub host: bool,
+    #[serde(serialize_with = "serialize_secs")]
+    pub prepare_time: Duration,
+    #[serde(serialize_with = "serialize_opt_secs")]
+    pub connect_time: Option<Duration>,
+    pub exit_code: Option<i32>,
+    /// Set, if BWAIShotgun detected a problem with this bot (ie. it was killed)
+    pub failure: Option<String>,
+    /// As reported by the TM, `None` without TM. Overridden if the bot crashed or exceeded the
+    /// `frame_limits`.
+    pub outcome: Option<Outcome>,
+    /// Problems that didn't decide the game, ie. the TM's verdict on slow frames differing from
+    /// BWAIShotgun's
+    pub diagnostics: Vec<String>,
+    /// File name of the TM dll, if the bot ran with one
+    pub tournament_module: Option<String>,
+    /// File name of the TM dll, if the bot ran without it because it was missing (see `tm_missing`)
+    pub missing_tournament_module: Option<String>,
+    /// Only known, if the bot ran with a TM
+    pub start_location: Option<StartLocation>,
+    /// Crash dumps (or Wine backtraces) of StarCraft, if `collect_crash_dumps` is enabled
+    pub crash_dumps: Vec<PathBuf>,
+}
+
+impl BotResult {
+    /// Set if StarCraft or the bot crashed, or BWAIShotgun detected another problem
+    pub fn crashed(&self) -> bool {
+        self.failure.is_some()
+            || !self.crash_dumps.is_empty()
+            || self.outcome == Some(Outcome::Crash)
+    }
+
+    /// The race the bot played, only known with TM if it was configured as `Random`
+    pub fn resolved_race(&self) -> Option<Race> {
+        match self.race {
+            Race::Random => self.start_location.as_ref().map(|it| it.race),
+            race => Some(race),
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    Win,
+    Loss,
+    /// Nobody won before the game timed out
+    Draw,
+    /// The TM detected a crash of the bot, or StarCraft crashed
+    Crash,
+    /// Lost by exceeding the `frame_limits`, no matter what the TM reported
+    Disqualified {
+        rule: String,
+    },
+}
+
+impl Display for Outcome {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Outcome::Win => "win",
+            Outcome::Loss => "loss",
+            Outcome::Draw => "draw",
+            Outcome::Crash => "crash",
+            Outcome::Disqualified { rule } => return write!(f, "disqualified ({rule})"),
+        })
+    }
+}
+
+/// A disqualified bot loses: If all other bots were disqualified, the remaining one wins
+pub fn apply_disqualifications(bots: &mut [BotResult]) {
+    let disqualified = |bot: &BotResult| matches!(bot.outcome, Some(Outcome::Disqualified { .. }));
+    if !bots.iter().any(disqualified) {
+        return;
+    }
+    if let [winner] = bots
+        .iter_mut()
+        .filter(|it| !disqualified(it))
+        .collect::<Vec<_>>()
+        .as_mut_slice()
+    {
+        winner.outcome = Some(Outcome::Win);
+    }
+}
+
+fn serialize_secs<S: serde::Serializer>(duration: &Duration, s: S) -> Result<S::Ok, S::Error> {
+    s.serialize_f64(duration.as_secs_f64())
+}
+
+fn serialize_opt_secs<S: serde::Serializer>(
+    duration: &Option<Duration>,
+    s: S,
+) -> Result<S::Ok, S::Error> {
+    match duration {
+        Some(duration) => serialize_secs(duration, s),
+        None => s.serialize_none(),
+    }
+}
+
+fn serialize_timestamp<S: serde::Serializer>(time: &SystemTime, s: S) -> Result<S::Ok, S::Error> {
+    s.collect_str(&format_timestamp(*time))
+}
+
+/// Formats the time as UTC in ISO 8601, ie. '2023-11-14T22:13:20Z'
+pub fn format_timestamp(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map(|it| it.as_secs())
+        .unwrap_or_default();
+    // Civil date from days since 1970-01-01, see http://howardhinnant.github.io/date_algorithms.html
+    let days = secs / 86400 + 719468;
+    let era = days / 146097;
+    let day_of_era = days % 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        secs % 86400 / 3600,
+        secs % 3600 / 60,
+        secs % 60
+    )
+}
+
+/// Startup times of a game, see `metrics_file`
+#[derive(Serialize, Debug)]
+pub struct LaunchMetrics<'a> {
+    pub game_id: &'a str,
+    pub bots: Vec<BotMetrics<'a>>,
+    #[serde(serialize_with = "serialize_secs")]
+    pub launch_duration: Duration,
+    #[serde(serialize_with = "serialize_secs")]
+    pub total_duration: Duration,
+}
+
+#[derive(Serialize, Debug)]
+pub struct BotMetrics<'a> {
+    pub name: &'a str,
+    #[serde(serialize_with = "serialize_secs")]
+    pub prepare_time: Duration,
+    /// Only known for client bots
+    #[serde(serialize_with = "serialize_opt_secs")]
+    pub connect_time: Option<Duration>,
+}
+
+impl<'a> From<&'a GameResult> for LaunchMetrics<'a> {
+    fn from(game_result: &'a GameResult) -> Self {
+        Self {
+            game_id: &game_result.game_id,
+            bots: game_result
+                .bots
+                .iter()
+                .map(|bot| BotMetrics {
+                    name: &bot.name,
+                    prepare_time: bot.prepare_time,
+                    connect_time: bot.connect_time,
+                })
+                .collect(),
+            launch_duration: game_result.launch_duration,
+            total_duration: game_result.total_duration,
+        }
+    }
+}
+
+impl LaunchMetrics<'_> {
+    /// Appends the metrics as JSON line to the file
+    pub fn append_to(&self, path: &Path) -> anyhow::Result<()> {
+        if let Some(folder) = path.parent().filter(|it| !it.as_os_str().is_empty()) {
+            create_dir_all(folder)?;
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Could not open '{}'", path.display()))?;
+        writeln!(file, "{}", serde_json::to_string(self)?)?;
+        Ok(())
+    }
+}
+
+/// Layout of the `result_file`
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ResultFormat {
+    /// One `GameResult` as JSON object per line
+    #[default]
+    Json,
+    /// See `GameResult::to_csv_row`
+    Csv,
+    /// See `GameResult::to_aiide_line`
+    Aiide,
+    /// See `SscaitResult`
+    Sscait,
+}
+
+/// Appends the result of each game to a file in the configured format
+pub enum ResultFileWriter {
+    Json(File),
+    Csv(GameResultCsvWriter),
+    Aiide(File),
+    Sscait(File),
+}
+
+impl ResultFileWriter {
+    pub fn open(path: &Path, format: ResultFormat) -> anyhow::Result<Self> {
+        let append = || -> anyhow::Result<File> {
+            if let Some(folder) = path.parent().filter(|it| !it.as_os_str().is_empty()) {
+                create_dir_all(folder)?;
+            }
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .with_context(|| format!("Could not open '{}'", path.display()))
+        };
+        Ok(match format {
+            ResultFormat::Json => ResultFileWriter::Json(append()?),
+            ResultFormat::Csv => ResultFileWriter::Csv(GameResultCsvWriter::open(path)?),
+            ResultFormat::Aiide => ResultFileWriter::Aiide(append()?),
+            ResultFormat::Sscait => ResultFileWriter::Sscait(append()?),
+        })
+    }
+
+    pub fn write(&mut self, game_result: &GameResult) -> anyhow::Result<()> {
+        match self {
+            ResultFileWriter::Json(file) => {
+                writeln!(file, "{}", serde_json::to_string(game_result)?)?;
+            }
+            ResultFileWriter::Csv(writer) => writer.write(game_result)?,
+            ResultFileWriter::Aiide(file) => writeln!(file, "{}", game_result.to_aiide_line())?,
+            ResultFileWriter::Sscait(file) => {
+                writeln!(
+                    file,
+                    "{}",
+                    serde_json::to_string(&SscaitResult::from(game_result))?
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A 1on1 game as JSON object in the layout of the SSCAIT match results, the bots are named
+/// after their role in the lobby
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SscaitResult<'a> {
+    pub host: &'a str,
+    pub guest: &'a str,
+    pub map: &'a str,
+    /// "host", "guest" or "draw", "unknown" without TM
+    pub result: &'static str,
+    pub frame_count: Option<u32>,
+    pub host_crashed: bool,
+    pub guest_crashed: bool,
+    pub timestamp: String,
+}
+
+impl<'a> From<&'a GameResult> for SscaitResult<'a> {
+    fn from(game_result: &'a GameResult) -> Self {
+        // Without a hosting bot (ie. a human host), the first bot takes its place
+        let host = game_result
+            .bots
+            .iter()
+            .position(|it| it.host)
+            .unwrap_or_default();
+        let host_bot = game_result.bots.get(host);
+        let guest_bot = game_result
+            .bots
+            .iter()
+            .enumerate()
+            .find(|(index, _)| *index != host)
+            .map(|(_, bot)| bot);
+        let outcome = |bot: Option<&'a BotResult>| bot.and_then(|it| it.outcome.as_ref());
+        Self {
+            host: host_bot.map_or("", |it| &it.name),
+            guest: guest_bot.map_or("", |it| &it.name),
+            map: game_result.map.as_deref().unwrap_or_default(),
+            result: match (outcome(host_bot), outcome(guest_bot)) {
+                (Some(Outcome::Win), _) => "host",
+                (_, Some(Outcome::Win)) => "guest",
+                (None, None) => "unknown",
+                _ => "draw",
+            },
+            frame_count: game_result.duration_frames,
+            host_crashed: host_bot.is_some_and(BotResult::crashed),
+            guest_crashed: guest_bot.is_some_and(BotResult::crashed),
+            timestamp: format_timestamp(game_result.started),
+        }
+    }
+}
+
+/// Columns of `GameResult::to_csv_row`
+pub const CSV_HEADER: &str = "timestamp,map,bot1_name,bot1_race,bot2_name,bot2_race,winner,duration_frames,bot1_crash,bot2_crash";
+
+impl GameResult {
+    /// One line (without line break) for a CSV of 1on1 results, see `CSV_HEADER`. The winner is
+    /// `winner`, it is left empty without TM or if nobody won.
+    pub fn to_csv_row(&self) -> String {
+        let bot = |index: usize| self.bots.get(index);
+        let name = |index| bot(index).map(|it| it.name.clone()).unwrap_or_default();
+        let race = |index| bot(index).map(|it| it.race.to_string()).unwrap_or_default();
+        let crash = |index| {
+            bot(index)
+                .map(|it| it.crashed().to_string())
+                .unwrap_or_default()
+        };
+        let winner = self.winner().map(|it| it.name.clone()).unwrap_or_default();
+        [
+            format_timestamp(self.started),
+            self.map.clone().unwrap_or_default(),
+            name(0),
+            race(0),
+            name(1),
+            race(1),
+            winner,
+            self.duration_frames
+                .map(|it| it.to_string())
+                .unwrap_or_default(),
+            crash(0),
+            crash(1),
+        ]
+        .iter()
+        .map(|it| csv_field(it))
+        .collect::<Vec<_>>()
+        .join(",")
+    }
+
+    /// The bot its TM reported as winner, or the opponent of a disqualified bot
+    pub fn winner(&self) -> Option<&BotResult> {
+        self.bots.iter().find(|it| it.outcome == Some(Outcome::Win))
+    }
+
+    /// One line (without line break) in the layout of the AIIDE tournament manager's results,
+    /// separated by spaces: game id, bot 1, bot 2, winner ('-' if unknown), crash flags of bot 1
+    /// and 2, whether the game timed out, frame count (0 if unknown) and map. Spaces in bot names
+    /// are replaced by '_', the map comes last as its name might contain spaces.
+    pub fn to_aiide_line(&self) -> String {
+        let name =
+            |bot: Option<&BotResult>| bot.map_or("-".to_string(), |it| it.name.replace(' ', "_"));
+        let crash = |index: usize| self.bots.get(index).is_some_and(BotResult::crashed);
+        format!(
+            "{} {} {} {} {} {} {} {} {}",
+            self.game_id,
+            name(self.bots.first()),
+            name(self.bots.get(1)),
+            name(self.winner()),
+            crash(0),
+            crash(1),
+            self.time_limit_exceeded,
+            self.duration_frames.unwrap_or_default(),
+            self.map.as_deref().unwrap_or("-")
+        )
+    }
+
+    pub fn summary_table(&self) -> String {
+        let rows: Vec<_> = self
+            .bots
+            .iter()
+            .map(|bot| {
+                vec![
+                    bot.name.clone(),
+                    match bot.resolved_race() {
+                        Some(race) if race == bot.race => race.to_string(),
+                        Some(race) => format!("{}/{race}", bot.race),
+                        None => format!("{}/?", bot.race),
+                    },
+                    bot.binary_type.to_string(),
+                    bot.bwapi_version.unwrap_or("?").to_string(),
+                    bot.connect_time
+                        .map(|it| format!("{:.1}s", it.as_secs_f64()))
+                        .unwrap_or_else(|| "-".to_string()),
+                    bot.exit_code
+                        .map(|it| it.to_string())
+                        .unwrap_or_else(|| "-".to_string()),
+                    match (&bot.start_location, &bot.tournament_module) {
+                        (Some(start_location), _) => start_location.to_string(),
+                        (None, Some(_)) => "unknown".to_string(),
+                        (None, None) if bot.missing_tournament_module.is_some() => {
+                            "unknown (TM missing)".to_string()
+                        }
+                        (None, None) => "unknown (no TM)".to_string(),
+                    },
+                    bot.failure.clone().unwrap_or_else(|| "-".to_string()),
+                    bot.outcome
+                        .as_ref()
+                        .map(|it| it.to_string())
+                        .unwrap_or_else(|| "-".to_string()),
+                ]
+            })
+            .collect();
+        let mut table = render_table(
+            &[
+                "Bot", "Race", "Type", "BWAPI", "Connect", "Exit", "Start", "Failure", "Result",
+            ],
+            &rows,
+        );
+        table.push_str(&format!(
+            "Game '{}' ({}) on {} took {:.0}s ({} frames), replays: {}\n",
+            self.game_name,
+            self.game_id,
+            self.map.as_deref().unwrap_or("<human selected map>"),
+            self.duration.as_secs_f64(),
+            self.duration_frames
+                .map_or("?".to_string(), |it| it.to_string()),
+            self.replay_path
+        ));
+        if let Some(lobby_failure) = &self.lobby_failure {
+            table.push_str(&format!("Lobby failure: {}\n", lobby_failure.kind));
+        }
+        if self.time_limit_exceeded {
+            table.push_str("The game exceeded 'time_out_at_frame'\n");
+        }
+        table.push_str(&format!("Timings: {}\n", format_timings(&self.timings)));
+        table
+    }
+}
+
+/// One row per game of a series, printed after the summaries of the single games
+pub fn aggregate_table(results: &[GameResult]) -> String {
+    let rows: Vec<_> = results
+        .iter()
+        .map(|result| {
+            vec![
+                result.game_id.clone(),
+                result
+                    .map
+                    .clone()
+                    .unwrap_or_else(|| "<human selected map>".to_string()),
+                format!("{:.0}s", result.duration.as_secs_f64()),
+                result
+                    .duration_frames
+                    .map_or("?".to_string(), |it| it.to_string()),
+                result
+                    .bots
+                    .iter()
+                    .map(|bot| {
+                        format!(
+                            "{} {}",
+                            bot.name,
+                            bot.outcome
+                                .as_ref()
+                                .map_or("-".to_string(), |it| it.to_string())
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            ]
+        })
+        .collect();
+    let mut table = render_table(&["Game", "Map", "Duration", "Frames", "Results"], &rows);
+    let wins = |name: &str| {
+        results
+            .iter()
+            .flat_map(|it| it.bots.iter())
+            .filter(|bot| bot.name == name && bot.outcome == Some(Outcome::Win))
+            .count()
+    };
+    let mut names: Vec<_> = results
+        .iter()
+        .flat_map(|it| it.bots.iter().map(|bot| bot.name.as_str()))
+        .collect();
+    names.sort();
+    names.dedup();
+    table.push_str(&format!(
+        "{} games, wins: {}\n",
+        results.len(),
+        names
+            .iter()
+            .map(|name| format!("{name} {}", wins(name)))
+            .collect::<Vec<_>>()
+            .join(", ")
+    ));
+    table
+}
+
+/// Quotes the field if necessary
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Renders a fixed-width table, columns are as wide as their widest cell
+fn render_table(headers: &[&str], rows: &[Vec<String>]) -> String {
+    let mut widths: Vec<_> = headers.iter().map(|it| it.chars().count()).collect();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let render_row = |cells: &mut dyn Iterator<Item = &str>| {
+        let line = cells
+            .zip(&widths)
+            .map(|(cell, width)| format!("{cell:width$}"))
+            .collect::<Vec<_>>()
+            .join(" | ");
+        format!("{}\n", line.trim_end())
+    };
+    let mut table = render_row(&mut headers.iter().copied());
+    table.push_str(&format!(
+        "{}\n",
+        widths
+            .iter()
+            .map(|width| "-".repeat(*width))
+            .collect::<Vec<_>>()
+            .join("-+-")
+    ));
+    for row in rows {
+        table.push_str(&render_row(&mut row.iter().map(|it| it.as_str())));
+    }
+    table
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use crate::environment::Environment;
+    use crate::results::{
+        aggregate_table, apply_disqualifications, format_timestamp, render_table, BotResult,
+        GameResult, LaunchMetrics, LobbyFailure, LobbyFailureKind, Outcome, ResultFileWriter,
+        ResultFormat, SscaitResult,
+    };
+    use crate::test_util::TempDir;
+    use crate::tm_results::StartLocation;
+    use crate::Race;
+    use std::fs::read_to_string;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    fn bot(name: &str, race: Race) -> BotResult {
+        BotResult {
+            name: name.to_string(),
+            race,
+            binary_type: "DLL",
+            bwapi_version: None,
+            launch_order: 0,
+            host: false,
+            prepare_time: Duration::from_millis(500),
+            connect_time: None,
+            exit_code: Some(0),
+            failure: None,
+            outcome: None,
+            diagnostics: vec![],
+            tournament_module: None,
+            missing_tournament_module: None,
+            start_location: None,
+            crash_dumps: vec![],
+        }
+    }
+
+    /// A 1on1 without TM, also used by the tests of other modules
+    pub(crate) fn game_result() -> GameResult {
+        GameResult {
+            game_id: "1700000000".to_string(),
+            game_name: "shotgun".to_string(),
+            started: UNIX_EPOCH + Duration::from_secs(1700000000),
+            map: Some("maps/(2)Heartbreak Ridge, 2.1.scx".to_string()),
+            replay_path: String::new(),
+            duration: Duration::from_secs(60),
+            duration_frames: Some(1440),
+            lobby_failure: None,
+            time_limit_exceeded: false,
+            launch_duration: Duration::from_secs(5),
+            total_duration: Duration::from_secs(70),
+            bots: vec![bot("NitekatT", Race::Terran), bot("ZergHell", Race::Zerg)],
+            timings: vec![],
+            environment: Environment::default(),
+            bot_logs: vec![],
+        }
+    }
+
+    #[test]
+    fn test_result_file() {
+        let temp_dir = TempDir::new("result-file");
+        let folder = temp_dir.join("results");
+        for (format, file_name) in [
+            (ResultFormat::Json, "results.jsonl"),
+            (ResultFormat::Csv, "results.csv"),
+            (ResultFormat::Aiide, "results.txt"),
+            (ResultFormat::Sscait, "sscait.jsonl"),
+        ] {
+            let path = folder.join(file_name);
+            for _ in 0..2 {
+                ResultFileWriter::open(&path, format)
+                    .unwrap()
+                    .write(&game_result())
+                    .unwrap();
+            }
+            let content = read_to_string(path).unwrap();
+            let lines: Vec<_> = content.lines().collect();
+            match format {
+                ResultFormat::Json => {
+                    assert_eq!(lines.len(), 2);
+                    let json: serde_json::Value = serde_json::from_str(lines[1]).unwrap();
+                    assert_eq!(json["bots"][1]["name"], "ZergHell");
+                    assert_eq!(json["bots"][1]["launch_order"], 0);
+                    assert_eq!(json["bots"][1]["host"], false);
+                }
+                ResultFormat::Csv => {
+                    assert_eq!(lines.len(), 3);
+                    assert!(lines[0].starts_with("timestamp,"));
+                }
+                ResultFormat::Aiide => {
+                    let line = game_result().to_aiide_line();
+                    assert_eq!(lines, [line.as_str(), line.as_str()]);
+                }
+                ResultFormat::Sscait => {
+                    assert_eq!(lines.len(), 2);
+                    let json: serde_json::Value = serde_json::from_str(lines[1]).unwrap();
+                    assert_eq!(json["guest"], "ZergHell");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_launch_metrics() {
+        let folder = TempDir::new("launch-metrics");
+        let path = folder.join("results").join("metrics.jsonl");
+        let mut game_result = game_result();
+        game_result.bots[0].connect_time = Some(Duration::from_secs(2));
+        LaunchMetrics::from(&game_result).append_to(&path).unwrap();
+
+        let json: serde_json::Value =
+            serde_json::from_str(read_to_string(&path).unwrap().trim()).unwrap();
+        assert_eq!(json["launch_duration"], 5.0);
+        assert_eq!(json["bots"][0]["connect_time"], 2.0);
+        assert_eq!(json["bots"][1]["prepare_time"], 0.5);
+        assert!(json["bots"][1]["connect_time"].is_null());
+    }
+
+    #[test]
+    fn test_to_csv_row() {
+        let mut game_result = game_result();
+        game_result.bots[1].failure = Some("StarCraft crashed".to_string());
+        assert_eq!(
+            game_result.to_csv_row(),
+            "2023-11-14T22:13:20Z,\"maps/(2)Heartbreak Ridge, 2.1.scx\",NitekatT,Terran,ZergHell,Zerg,,1440,false,true"
+        );
+        game_result.bots[0].outcome = Some(Outcome::Win);
+        game_result.bots[1].outcome = Some(Outcome::Loss);
+        game_result.bots[1].failure = None;
+        assert_eq!(
+            game_result.to_csv_row(),
+            "2023-11-14T22:13:20Z,\"maps/(2)Heartbreak Ridge, 2.1.scx\",NitekatT,Terran,ZergHell,Zerg,NitekatT,1440,false,false"
+        );
+    }
+
+    #[test]
+    fn test_apply_disqualifications() {
+        let mut game_result = game_result();
+        // The TM saw the disqualified bot winning
+        game_result.bots[0].outcome = Some(Outcome::Disqualified {
+            rule: "at most 320 frames over 55ms, but 400 were".to_string(),
+        });
+        game_result.bots[1].outcome = Some(Outcome::Loss);
+        apply_disqualifications(&mut game_result.bots);
+        assert_eq!(game_result.bots[1].outcome, Some(Outcome::Win));
+        assert_eq!(
+            game_result.winner().map(|it| it.name.as_str()),
+            Some("ZergHell")
+        );
+        assert_eq!(
+            game_result.bots[0].outcome.as_ref().unwrap().to_string(),
+            "disqualified (at most 320 frames over 55ms, but 400 were)"
+        );
+
+        game_result.bots[1].outcome = game_result.bots[0].outcome.clone();
+        apply_disqualifications(&mut game_result.bots);
+        assert!(game_result
+            .bots
+            .iter()
+            .all(|it| matches!(it.outcome, Some(Outcome::Disqualified { .. }))));
+    }
+
+    #[test]
+    fn test_aiide_line() {
+        let mut game_result = game_result();
+        assert_eq!(
+            game_result.to_aiide_line(),
+            "1700000000 NitekatT ZergHell - false false false 1440 maps/(2)Heartbreak Ridge, 2.1.scx"
+        );
+        game_result.bots[0].name = "Nite katT".to_string();
+        game_result.bots[1].outcome = Some(Outcome::Win);
+        game_result.bots[0].outcome = Some(Outcome::Crash);
+        game_result.time_limit_exceeded = true;
+        assert_eq!(
+            game_result.to_aiide_line(),
+            "1700000000 Nite_katT ZergHell ZergHell true false true 1440 maps/(2)Heartbreak Ridge, 2.1.scx"
+        );
+    }
+
+    #[test]
+    fn test_sscait_result() {
+        let mut game_result = game_result();
+        let json = |game_result: &GameResult| {
+            serde_json::to_string(&SscaitResult::from(game_result)).unwrap()
+        };
+        assert_eq!(
+            json(&game_result),
+            r#"{"host":"NitekatT","guest":"ZergHell","map":"maps/(2)Heartbreak Ridge, 2.1.scx","result":"unknown","frameCount":1440,"hostCrashed":false,"guestCrashed":false,"timestamp":"2023-11-14T22:13:20Z"}"#
+        );
+        game_result.bots[1].host = true;
+        game_result.bots[0].outcome = Some(Outcome::Win);
+        game_result.bots[1].outcome = Some(Outcome::Loss);
+        game_result.bots[1].failure = Some("StarCraft crashed".to_string());
+        assert_eq!(
+            json(&game_result),
+            r#"{"host":"ZergHell","guest":"NitekatT","map":"maps/(2)Heartbreak Ridge, 2.1.scx","result":"guest","frameCount":1440,"hostCrashed":true,"guestCrashed":false,"timestamp":"2023-11-14T22:13:20Z"}"#
+        );
+    }
+
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp(UNIX_EPOCH), "1970-01-01T00:00:00Z");
+        assert_eq!(
+            format_timestamp(UNIX_EPOCH + Duration::from_secs(951827696)),
+            "2000-02-29T12:34:56Z"
+        );
+        assert_eq!(
+            format_timestamp(UNIX_EPOCH + Duration::from_secs(1735689599)),
+            "2024-12-31T23:59:59Z"
+        );
+    }
+
+    #[test]
+    fn test_lobby_failure() {
+        let mut game_result = game_result();
+        game_result.lobby_failure = Some(LobbyFailure {
+            kind: LobbyFailureKind::Timeout { secs: 60 },
+            game_table_history: "+0.1s: no game table\n".to_string(),
+        });
+        assert!(game_result
+            .summary_table()
+            .contains("Lobby failure: The game did not start within 60s ('lobby_timeout_secs')\n"));
+        assert!(!game_result.summary_table().contains("no game table"));
+        let json = serde_json::to_value(&game_result).unwrap();
+        assert_eq!(json["lobby_failure"]["kind"]["Timeout"]["secs"], 60);
+        assert_eq!(
+            json["lobby_failure"]["game_table_history"],
+            "+0.1s: no game table\n"
+        );
+    }
+
+    #[test]
+    fn test_summary_table() {
+        let mut game_result = game_result();
+        game_result.bots[0].race = Race::Random;
+        game_result.bots[0].start_location = Some(StartLocation {
+            player: "NitekatT".to_string(),
+            x: 192,
+            y: 3880,
+            race: Race::Terran,
+        });
+        game_result.bots[0].outcome = Some(Outcome::Win);
+        game_result.bots[1].outcome = Some(Outcome::Loss);
+        let table = game_result.summary_table();
+        let lines: Vec<_> = table.lines().collect();
+        assert!(lines[0].ends_with("| Failure | Result"));
+        assert!(lines[2].starts_with("NitekatT | Random/Terran | "));
+        assert!(lines[2].ends_with("| win"));
+        assert!(lines[3].starts_with("ZergHell | Zerg          | "));
+        assert!(lines[3].ends_with("| loss"));
+        assert!(table.contains("took 60s (1440 frames)"));
+
+        let mut second_game = self::game_result();
+        second_game.game_id = "1700000100".to_string();
+        second_game.duration_frames = None;
+        second_game.bots[1].outcome = Some(Outcome::Win);
+        assert_eq!(
+            aggregate_table(&[game_result, second_game]),
+            "Game       | Map                               | Duration | Frames | Results\n\
+             -----------+-----------------------------------+----------+--------+----------------------------\n\
+             1700000000 | maps/(2)Heartbreak Ridge, 2.1.scx | 60s      | 1440   | NitekatT win, ZergHell loss\n\
+             1700000100 | maps/(2)Heartbreak Ridge, 2.1.scx | 60s      | ?      | NitekatT -, ZergHell win\n\
+             2 games, wins: NitekatT 1, ZergHell 1\n"
+        );
+    }
+
+    #[test]
+    fn test_render_table() {
+        let table = render_table(
+            &["Bot", "Race"],
+            &[
+                vec!["NitekatT".to_string(), "Terran".to_string()],
+                vec!["Z".to_string(), "Zerg".to_string()],
+            ],
+        );
+        assert_eq!(
+            table,
+            "Bot      | Race\n\
+             ---------+-------\n\
+             NitekatT | Terran\n\
+             Z        | Zerg\n"
+        );
+    }
+}
@@ -0,0 +1,150 @@
+use anyhow::ensure;
+
+use crate::{GameConfig, HeadfulMode, PreparedBot};
+
+/// Who hosts the game, decided once before launching any bot
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameRole {
+    /// The bot at this position in launch order hosts, all others join its game
+    BotHost { bot: usize },
+    /// A human hosts, all bots join the first game they find
+    HumanHost,
+}
+
+/// What a single bot does in the lobby
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BotRole {
+    Host {
+        game_name: String,
+        map: Option<String>,
+        min_players: usize,
+        max_players: usize,
+    },
+    /// Join the game with this name, or the first one found if `None`
+    Join { game_name: Option<String> },
+}
+
+impl GameRole {
+    /// Decides the role for bots in launch order (see `plan_launch_order`), problems which are not fatal
+    /// are added to `warnings`. `lobby_players` are the minimum and maximum players of the game.
+    pub fn plan(
+        bots: &[PreparedBot],
+        game_config: &GameConfig,
+        (min_players, max_players): (usize, usize),
+        warnings: &mut Vec<String>,
+    ) -> anyhow::Result<Self> {
+        let computers = game_config.computer_races.len();
+        if computers > 0 {
+            ensure!(
+                !game_config.human_host,
+                "'computer_races' can't be used in games hosted by a human, add them in the lobby instead"
+            );
+            if let Some(host) = bots.first() {
+                ensure!(
+                    !matches!(host.headful, HeadfulMode::Off),
+                    "'{}' hosts headless, but only headful hosts can add 'computer_races'",
+                    host.name
+                );
+            }
+        }
+        if let Some((top, bottom)) = game_config.game_type.force_sizes() {
+            ensure!(
+                bots.len() == top + bottom,
+                "Only {} of {} bots could be prepared, the forces would not be as configured",
+                bots.len(),
+                top + bottom
+            );
+            if let (Some(host), false) = (bots.first(), game_config.human_host) {
+                ensure!(
+                    !matches!(host.headful, HeadfulMode::Off),
+                    "'{}' hosts headless, but only headful hosts can create 'TopVsBottom' games",
+                    host.name
+                );
+            }
+        }
+        if let (Some(host), false) = (bots.first(), game_config.human_host) {
+            // bwheadless' '-h' waits for an exact number of players, it would wait forever
+            ensure!(
+                !matches!(host.headful, HeadfulMode::Off) || min_players == max_players,
+                "'{}' hosts headless, but only headful hosts can wait for {min_players} to {max_players} players ('min_players', 'max_players' or 'allow_human_observer')",
+                host.name
+            );
+        }
+        if !game_config.human_host {
+            // `plan_launch_order` put the bot configured to host first
+            ensure!(!bots.is_empty(), "No bot available to host the game");
+            return Ok(GameRole::BotHost { bot: 0 });
+        }
+        if let Some(map) = &game_config.map {
+            warnings.push(format!(
+                "The game is hosted by a human, the map selected by the human wins over '{map}'"
+            ));
+        }
+        for bot in bots
+            .iter()
+            .filter(|bot| !matches!(bot.headful, HeadfulMode::Off) && !bot.supports_character_name)
+        {
+            warnings.push(format!(
+                "'{}' is headful and uses a very old BWAPI version, it might not join the human hosted game automatically",
+                bot.name
+            ));
+        }
+        Ok(GameRole::HumanHost)
+    }
+
+    pub fn is_host(&self, bot: usize) -> bool {
+        matches!(self, GameRole::BotHost { bot: host } if *host == bot)
+    }
+
+    /// The role of the bot at the given position in launch order
+    pub fn bot_role(
+        &self,
+        bot: usize,
+        game_name: &str,
+        map: Option<String>,
+        min_players: usize,
+        max_players: usize,
+    ) -> BotRole {
+        match self {
+            GameRole::BotHost { .. } if self.is_host(bot) => BotRole::Host {
+                game_name: game_name.to_string(),
+                map,
+                min_players,
+                max_players,
+            },
+            GameRole::BotHost { .. } => BotRole::Join {
+                game_name: Some(game_name.to_string()),
+            },
+            GameRole::HumanHost => BotRole::Join { game_name: None },
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::role::{BotRole, GameRole};
+
+    #[test]
+    fn test_bot_role() {
+        let role = GameRole::BotHost { bot: 0 };
+        assert_eq!(
+            role.bot_role(0, "shotgun", Some("map.scx".to_string()), 2, 4),
+            BotRole::Host {
+                game_name: "shotgun".to_string(),
+                map: Some("map.scx".to_string()),
+                min_players: 2,
+                max_players: 4
+            }
+        );
+        assert_eq!(
+            role.bot_role(1, "shotgun", Some("map.scx".to_string()), 2, 2),
+            BotRole::Join {
+                game_name: Some("shotgun".to_string())
+            }
+        );
+        assert_eq!(
+            GameRole::HumanHost.bot_role(0, "shotgun", None, 2, 2),
+            BotRole::Join { game_name: None }
+        );
+    }
+}
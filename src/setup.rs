@@ -1,17 +1,18 @@
 use log::debug;
+use reqwest::blocking::Client;
+use reqwest::header::{ACCEPT_RANGES, CONTENT_LENGTH, RANGE};
+use reqwest::StatusCode;
 use serde::Deserialize;
 use sha2::{Digest, Sha256};
 use std::fs::{create_dir_all, File, OpenOptions};
-use std::io::copy;
+use std::io::{copy, Read, Seek, SeekFrom};
 use std::path::{Path, PathBuf};
 
-use anyhow::ensure;
-use log::info;
+use anyhow::{bail, ensure};
+use log::{info, warn};
 use zip::ZipArchive;
 
-use crate::download_folder;
-
-#[derive(Deserialize, Debug, Default)]
+#[derive(Deserialize, Debug, Default, Clone)]
 pub enum ComponentConfig {
     #[default]
     Locate,
@@ -19,15 +20,20 @@ pub enum ComponentConfig {
     Path(PathBuf),
 }
 
+#[derive(Clone)]
 pub struct ComponentInstallation {
     pub name: &'static str,
     pub download_name: &'static str,
     pub locator: fn() -> anyhow::Result<PathBuf>,
     pub provider: fn(&Self) -> anyhow::Result<PathBuf>,
     pub internal_folder: PathBuf,
+    /// Created on demand, if the component needs to be downloaded
+    pub downloads_dir: PathBuf,
     pub download_url: &'static str,
     pub hashes: &'static [[u8; 32]],
     pub config: ComponentConfig,
+    /// Parallel connections used to download the component, see `download`
+    pub download_connections: usize,
 }
 
 impl ComponentInstallation {
@@ -36,8 +42,9 @@ impl ComponentInstallation {
             debug!("Using internal {}", self.name);
             return Ok(false);
         }
-        let path = download_folder()?.join(self.download_name);
-        let file = if !verify_hashes(&path, self.hashes)? {
+        create_dir_all(&self.downloads_dir)?;
+        let path = self.downloads_dir.join(self.download_name);
+        let file = if !verify_hashes_streaming(&path, self.hashes)? {
             info!(
                 "Downloading {} from '{}' to '{}'",
                 self.name,
@@ -45,12 +52,11 @@ impl ComponentInstallation {
                 path.to_string_lossy()
             );
             // File not there or hash failed -> redownload
-            let mut file = OpenOptions::new().write(true).read(true).open(&path)?;
-            let dl_bytes = reqwest::blocking::get(self.download_url)?.copy_to(&mut file)?;
+            let dl_bytes = download(self.download_url, &path, self.download_connections)?;
             debug!("Downloaded {} distribution: {dl_bytes} bytes", self.name);
-            file.sync_data()?;
+            let file = File::open(&path)?;
             ensure!(
-                verify_hashes(&path, self.hashes)?,
+                verify_hashes_streaming(&path, self.hashes)?,
                 "Hash check of downloaded {} failed, aborting!",
                 self.name
             );
@@ -88,6 +94,19 @@ impl ComponentInstallation {
         Ok(true)
     }
 
+    /// Like `to_path`, but will never download or install the component
+    pub fn installed_path(&self) -> Option<PathBuf> {
+        match &self.config {
+            ComponentConfig::Locate => (self.locator)()
+                .ok()
+                .or_else(|| Some(self.internal_folder.clone()).filter(|it| it.exists())),
+            ComponentConfig::Path(path) => Some(path.clone()),
+            ComponentConfig::Internal => {
+                Some(self.internal_folder.clone()).filter(|it| it.exists())
+            }
+        }
+    }
+
     pub fn to_path(&self) -> anyhow::Result<PathBuf> {
         match &self.config {
             ComponentConfig::Locate => (self.locator)().or_else(|_| {
@@ -106,7 +125,107 @@ impl ComponentInstallation {
     }
 }
 
-fn verify_hashes(file: &Path, hashes: &[[u8; 32]]) -> anyhow::Result<bool> {
+/// Downloads `url` to `target` using up to `connections` parallel range requests, or a single
+/// stream if the server doesn't support ranges. Returns the number of bytes downloaded.
+fn download(url: &str, target: &Path, connections: usize) -> anyhow::Result<u64> {
+    let client = Client::new();
+    if connections > 1 {
+        match range_length(&client, url) {
+            Some(length) => match download_segments(&client, url, target, length, connections) {
+                Ok(()) => return Ok(length),
+                Err(err) => {
+                    warn!("Segmented download failed, retrying with a single connection: {err}")
+                }
+            },
+            None => debug!("'{url}' doesn't support range requests, using a single connection"),
+        }
+    }
+    let mut file = File::create(target)?;
+    let bytes = client
+        .get(url)
+        .send()?
+        .error_for_status()?
+        .copy_to(&mut file)?;
+    file.sync_data()?;
+    Ok(bytes)
+}
+
+/// The length of the download, if the server supports range requests. Some servers don't answer
+/// HEAD requests (properly), the download then falls back to a single GET request.
+fn range_length(client: &Client, url: &str) -> Option<u64> {
+    let head = match client.head(url).send().and_then(|it| it.error_for_status()) {
+        Ok(head) => head,
+        Err(err) => {
+            debug!("HEAD request to '{url}' failed: {err}");
+            return None;
+        }
+    };
+    let supports_ranges = head
+        .headers()
+        .get(ACCEPT_RANGES)
+        .is_some_and(|it| it.as_bytes() == b"bytes");
+    head.headers()
+        .get(CONTENT_LENGTH)
+        .and_then(|it| it.to_str().ok())
+        .and_then(|it| it.parse::<u64>().ok())
+        .filter(|_| supports_ranges)
+}
+
+/// Every connection writes its segment directly into the pre-sized target file
+fn download_segments(
+    client: &Client,
+    url: &str,
+    target: &Path,
+    length: u64,
+    connections: usize,
+) -> anyhow::Result<()> {
+    File::create(target)?.set_len(length)?;
+    std::thread::scope(|scope| {
+        let downloads: Vec<_> = segments(length, connections)
+            .into_iter()
+            .map(|(start, end)| {
+                scope.spawn(move || -> anyhow::Result<()> {
+                    let response = client
+                        .get(url)
+                        .header(RANGE, format!("bytes={start}-{end}"))
+                        .send()?;
+                    if response.status() != StatusCode::PARTIAL_CONTENT {
+                        bail!("Range request answered with {}", response.status());
+                    }
+                    let mut file = OpenOptions::new().write(true).open(target)?;
+                    file.seek(SeekFrom::Start(start))?;
+                    let expected = end - start + 1;
+                    let written = copy(&mut response.take(expected), &mut file)?;
+                    ensure!(
+                        written == expected,
+                        "Segment {start}-{end} ended after {written} bytes"
+                    );
+                    file.sync_data()?;
+                    Ok(())
+                })
+            })
+            .collect();
+        downloads.into_iter().try_for_each(|download| {
+            download
+                .join()
+                .unwrap_or_else(|_| bail!("Download thread panicked"))
+        })
+    })
+}
+
+/// Splits `length` bytes into (at most) `connections` inclusive byte ranges
+fn segments(length: u64, connections: usize) -> Vec<(u64, u64)> {
+    let size = length.div_ceil(connections.max(1) as u64).max(1);
+    (0..length)
+        .step_by(size as usize)
+        .map(|start| (start, (start + size).min(length) - 1))
+        .collect()
+}
+
+/// Checks if the SHA-256 of the file matches any of the given hashes. The file is streamed through
+/// the hasher in small chunks (no buffer of the file size is allocated) and hashed only once,
+/// regardless of the number of hashes. A missing file doesn't match.
+fn verify_hashes_streaming(file: &Path, hashes: &[[u8; 32]]) -> anyhow::Result<bool> {
     let mut file = if let Ok(file) = File::open(file) {
         file
     } else {
@@ -117,3 +236,30 @@ fn verify_hashes(file: &Path, hashes: &[[u8; 32]]) -> anyhow::Result<bool> {
     let hash = hasher.finalize();
     Ok(hashes.contains(hash.as_ref()))
 }
+
+#[cfg(test)]
+mod test {
+    use crate::setup::{segments, verify_hashes_streaming};
+    use std::path::Path;
+
+    #[test]
+    fn test_segments() {
+        assert_eq!(segments(10, 1), [(0, 9)]);
+        assert_eq!(segments(10, 3), [(0, 3), (4, 7), (8, 9)]);
+        assert_eq!(segments(2, 4), [(0, 0), (1, 1)]);
+        assert!(segments(0, 4).is_empty());
+    }
+
+    #[test]
+    fn test_verify_hashes_streaming() {
+        let file = Path::new("test-resources/BWAPI440.dll");
+        let hash = [
+            0xf2, 0xe0, 0xf9, 0x37, 0xe9, 0x59, 0x21, 0x57, 0x65, 0x61, 0x18, 0xfa, 0x7e, 0x5f,
+            0xf3, 0x0c, 0x23, 0x27, 0x69, 0x4e, 0xd5, 0x6c, 0x1d, 0x8f, 0x55, 0x68, 0x79, 0x72,
+            0xad, 0x97, 0xd3, 0x08,
+        ];
+        assert!(verify_hashes_streaming(file, &[[0; 32], hash]).unwrap());
+        assert!(!verify_hashes_streaming(file, &[[0; 32]]).unwrap());
+        assert!(!verify_hashes_streaming(Path::new("missing.zip"), &[hash]).unwrap());
+    }
+}
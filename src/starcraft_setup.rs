@@ -1,18 +1,22 @@
 #[cfg(target_os = "windows")]
 use anyhow::Context;
 #[cfg(target_os = "windows")]
-use registry::{Hive, Security};
-use std::fs::File;
+use registry::{Data, Hive, Security};
+use std::fs::{metadata, File};
 use std::io::copy;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use hex_literal::hex;
 use log::info;
 
-use crate::base_folder;
+use crate::paths::Paths;
 use crate::setup::{ComponentConfig, ComponentInstallation};
 
-pub fn starcraft_component(config: ComponentConfig) -> ComponentInstallation {
+pub fn starcraft_component(
+    paths: &Paths,
+    config: ComponentConfig,
+    download_connections: usize,
+) -> ComponentInstallation {
     ComponentInstallation {
         name: "Starcraft 1.16.1",
         download_name: "scbw_bwapi440.zip",
@@ -27,7 +31,9 @@ pub fn starcraft_component(config: ComponentConfig) -> ComponentInstallation {
             // TODO what changed here
             hex!("1B9F0BF5B719C59EC44563BA0DF834BFC7087332315D7A9E67B0731268AE90DC"),
         ],
-        internal_folder: base_folder().join("scbw"),
+        internal_folder: paths.home.join("scbw"),
+        downloads_dir: paths.downloads_dir.clone(),
+        download_connections,
         provider: provide_starcraft,
     }
 }
@@ -37,8 +43,9 @@ pub fn provide_starcraft(component: &ComponentInstallation) -> anyhow::Result<Pa
         return Ok(component.internal_folder.clone());
     }
     info!("Installing SNP_DirectIP.snp");
+    // Shipped next to the internal installation, in the BWAIShotgun folder
     copy(
-        &mut File::open(base_folder().join("SNP_DirectIP.snp"))?,
+        &mut File::open(component.internal_folder.with_file_name("SNP_DirectIP.snp"))?,
         &mut File::create(component.internal_folder.join("SNP_DirectIP.snp"))?,
     )?;
     Ok(component.internal_folder.clone())
@@ -47,12 +54,15 @@ pub fn provide_starcraft(component: &ComponentInstallation) -> anyhow::Result<Pa
 fn locate_starcraft() -> anyhow::Result<PathBuf> {
     #[cfg(target_os = "windows")]
     {
-        Ok(Hive::LocalMachine
+        match Hive::LocalMachine
             .open(r"SOFTWARE\Blizzard Entertainment\Starcraft", Security::Read)
             .context("Could not find Starcraft installation")?
             .value("InstallPath")?
-            .to_string()
-            .into())
+        {
+            // Not converted to a String, the path might not be valid unicode
+            Data::String(path) | Data::ExpandString(path) => Ok(path.to_os_string().into()),
+            _ => anyhow::bail!("The StarCraft 'InstallPath' in the registry is not a string"),
+        }
     }
     #[cfg(not(target_os = "windows"))]
     anyhow::bail!("Only supported in Windows")
@@ -65,6 +75,24 @@ pub fn starcraft_default_config() -> ComponentConfig {
     return ComponentConfig::Internal;
 }
 
+/// Capabilities of the 'SNP_DirectIP.snp' (Local PC network) of a StarCraft installation
+#[derive(Debug, Eq, PartialEq)]
+pub enum SnpSupport {
+    Missing,
+    /// Might not support more than ~6 bots per game
+    Limited,
+    /// The modified version supporting 8 bots per game
+    Full,
+}
+
+pub fn snp_support(starcraft_path: &Path) -> SnpSupport {
+    match metadata(starcraft_path.join("SNP_DirectIP.snp")) {
+        Ok(metadata) if metadata.len() == 46100 => SnpSupport::Full,
+        Ok(_) => SnpSupport::Limited,
+        Err(_) => SnpSupport::Missing,
+    }
+}
+
 // impl StarCraftInstallation {
 //     pub fn ensure_path(&self) -> anyhow::Result<PathBuf> {
 //         match self {
@@ -0,0 +1,367 @@
+use std::fmt::{Display, Formatter};
+use std::fs::{create_dir_all, read_to_string, File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+use crate::results::{GameResult, Outcome, CSV_HEADER};
+use crate::Race;
+
+/// Where a bot started, inferred from the position of its first resource depot
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct StartLocation {
+    /// The owner of the depot, as reported by the TM
+    pub player: String,
+    /// Position in pixels
+    pub x: i32,
+    pub y: i32,
+    /// Race of the depot, ie. the race a bot playing `Random` ended up with
+    pub race: Race,
+}
+
+impl Display for StartLocation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "({}, {})", self.x, self.y)
+    }
+}
+
+/// Reads the start location from the TM's 'unit_events.csv' of a bot, `None` if there is no
+/// (usable) file - ie. the bot ran without TM.
+pub fn read_start_location(tm_dir: &Path, player_name: &str) -> Option<StartLocation> {
+    resolve_start_location(
+        &read_to_string(tm_dir.join("unit_events.csv")).ok()?,
+        player_name,
+    )
+}
+
+/// The last frame the TM logged in 'frames.csv', `None` if there is no (usable) file
+pub fn read_last_frame(tm_dir: &Path) -> Option<u32> {
+    last_frame(&read_to_string(tm_dir.join("frames.csv")).ok()?)
+}
+
+/// The TM's verdict on the game of a bot, from its 'result.csv'
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TmResult {
+    pub is_winner: bool,
+    pub is_crashed: bool,
+    /// The game reached `TM_TIME_OUT_AT_FRAME`
+    pub timed_out: bool,
+    /// The TM's verdict on `TM_FRAME_LIMITS`, `None` if it doesn't support them
+    pub disqualified: Option<bool>,
+}
+
+impl TmResult {
+    pub fn outcome(&self) -> Outcome {
+        if self.is_crashed {
+            Outcome::Crash
+        } else if self.is_winner {
+            Outcome::Win
+        } else if self.timed_out {
+            Outcome::Draw
+        } else {
+            Outcome::Loss
+        }
+    }
+}
+
+/// Reads the 'result.csv' the TM writes at the end of the game, `None` if there is no file - ie.
+/// the bot ran without TM or StarCraft crashed.
+pub fn read_tm_result(tm_dir: &Path) -> Option<TmResult> {
+    Some(parse_tm_result(
+        &read_to_string(tm_dir.join("result.csv")).ok()?,
+    ))
+}
+
+/// Whether the TM disqualified the bot for slow frames, `None` if there is no 'result.csv' or the
+/// TM doesn't support `TM_FRAME_LIMITS`
+pub fn read_tm_disqualification(tm_dir: &Path) -> Option<bool> {
+    read_tm_result(tm_dir)?.disqualified
+}
+
+/// Describes how the TM's verdict on slow frames differs from BWAIShotgun's `violation`, `None`
+/// if they agree or the TM has no verdict
+pub fn frame_limits_mismatch(
+    tm_disqualified: Option<bool>,
+    violation: Option<FrameLimit>,
+) -> Option<String> {
+    match (tm_disqualified?, violation) {
+        (true, None) => Some(
+            "The tournament module disqualified the bot for slow frames, but BWAIShotgun found no violation".to_string(),
+        ),
+        (false, Some(rule)) => Some(format!(
+            "BWAIShotgun disqualified the bot ({rule}), but the tournament module did not"
+        )),
+        _ => None,
+    }
+}
+
+/// Despite its name, 'result.csv' holds '"key": value' pairs (ie. '"is_winner": true'), one per
+/// line. Unknown keys (the scores) are skipped.
+fn parse_tm_result(result: &str) -> TmResult {
+    let mut tm_result = TmResult::default();
+    for (key, value) in result
+        .split([',', '\n', '{', '}'])
+        .filter_map(|it| it.split_once(':'))
+    {
+        let value = value.trim() == "true";
+        match key.trim().trim_matches('"') {
+            "is_winner" => tm_result.is_winner = value,
+            "is_crashed" => tm_result.is_crashed = value,
+            "timed_out" => tm_result.timed_out = value,
+            "disqualified" => tm_result.disqualified = Some(value),
+            _ => (),
+        }
+    }
+    tm_result
+}
+
+/// The TM writes frame times as soon as the game started
+pub fn game_started(tm_dir: &Path) -> bool {
+    tm_dir
+        .join("frames.csv")
+        .metadata()
+        .is_ok_and(|it| it.len() > 0)
+}
+
+/// Lines start with the frame number, anything else (ie. a header) is skipped
+fn last_frame(frames: &str) -> Option<u32> {
+    frames
+        .lines()
+        .filter_map(|line| line.split(',').next()?.trim().parse().ok())
+        .max()
+}
+
+/// Tournament rules for slow frames, ie. "no more than 320 frames over 55ms"
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct FrameLimits {
+    pub rules: Vec<FrameLimit>,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct FrameLimit {
+    /// Frames taking longer than this count against the limit
+    pub ms: f64,
+    /// Allowed number of frames over `ms`
+    pub frames: u32,
+}
+
+impl Display for FrameLimit {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "at most {} frames over {}ms", self.frames, self.ms)
+    }
+}
+
+impl FrameLimits {
+    /// Passed to the TM as `TM_FRAME_LIMITS`, ie. `55:320,1000:10`
+    pub fn to_env(&self) -> String {
+        self.rules
+            .iter()
+            .map(|rule| format!("{}:{}", rule.ms, rule.frames))
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    /// The first rule violated by the frame times in 'frames.csv' of a bot, with the number of
+    /// frames over its limit. `None` if there is no (usable) file.
+    pub fn check(&self, tm_dir: &Path) -> Option<Option<(FrameLimit, usize)>> {
+        Some(self.violation(&read_to_string(tm_dir.join("frames.csv")).ok()?))
+    }
+
+    fn violation(&self, frames: &str) -> Option<(FrameLimit, usize)> {
+        let frame_times: Vec<f64> = frames
+            .lines()
+            .filter_map(|line| line.split(',').nth(1)?.trim().parse().ok())
+            .collect();
+        self.rules.iter().find_map(|rule| {
+            let slow_frames = frame_times.iter().filter(|time| **time > rule.ms).count();
+            (slow_frames > rule.frames as usize).then_some((*rule, slow_frames))
+        })
+    }
+}
+
+/// Finds the first resource depot of the given player. Depots of other players are never used,
+/// they would report the wrong start location.
+fn resolve_start_location(unit_events: &str, player_name: &str) -> Option<StartLocation> {
+    unit_events
+        .lines()
+        .skip(1)
+        .filter_map(parse_depot_event)
+        .find(|(_, location)| location.player == player_name)
+        .map(|(_, location)| location)
+}
+
+/// Parses a line of 'frame_number,event_type,player_owned,unit_id,unit_type,unit_position' if it
+/// is about a resource depot. The position is written as '(x,y)', and therefore spans 2 columns.
+fn parse_depot_event(line: &str) -> Option<(u32, StartLocation)> {
+    let columns: Vec<_> = line.split(',').map(str::trim).collect();
+    let [frame, _event_type, player, _unit_id, unit_type, position @ ..] = columns.as_slice()
+    else {
+        return None;
+    };
+    let unit_type = unit_type.replace(['_', ' '], "").to_lowercase();
+    let (_, race) = [
+        ("commandcenter", Race::Terran),
+        ("nexus", Race::Protoss),
+        ("hatchery", Race::Zerg),
+    ]
+    .into_iter()
+    .find(|(depot, _)| unit_type.ends_with(depot))?;
+    let coordinates: Vec<i32> = position
+        .join(",")
+        .split(|c: char| !c.is_ascii_digit() && c != '-')
+        .filter(|it| !it.is_empty())
+        .map(str::parse)
+        .collect::<Result<_, _>>()
+        .ok()?;
+    let [x, y] = coordinates.as_slice() else {
+        return None;
+    };
+    Some((
+        frame.parse().ok()?,
+        StartLocation {
+            player: player.to_string(),
+            x: *x,
+            y: *y,
+            race,
+        },
+    ))
+}
+
+/// Appends game results to a CSV file, the header is only written when the file is created
+pub struct GameResultCsvWriter {
+    file: File,
+}
+
+impl GameResultCsvWriter {
+    pub fn open(path: &Path) -> anyhow::Result<Self> {
+        if let Some(folder) = path.parent().filter(|it| !it.as_os_str().is_empty()) {
+            create_dir_all(folder)?;
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Could not open '{}'", path.display()))?;
+        if file.metadata()?.len() == 0 {
+            writeln!(file, "{CSV_HEADER}")?;
+        }
+        Ok(Self { file })
+    }
+
+    pub fn write(&mut self, game_result: &GameResult) -> anyhow::Result<()> {
+        writeln!(self.file, "{}", game_result.to_csv_row())?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::results::Outcome;
+    use crate::tm_results::{
+        frame_limits_mismatch, last_frame, parse_tm_result, resolve_start_location, FrameLimit,
+        FrameLimits, StartLocation, TmResult,
+    };
+    use crate::Race;
+
+    #[test]
+    fn test_last_frame() {
+        assert_eq!(
+            last_frame("frame,time\n0,1.5\n1,0.8\n85714,1.1\n"),
+            Some(85714)
+        );
+        assert_eq!(last_frame("frame,time\n"), None);
+    }
+
+    #[test]
+    fn test_frame_limits() {
+        let limits = FrameLimits {
+            rules: vec![
+                FrameLimit {
+                    ms: 55.0,
+                    frames: 2,
+                },
+                FrameLimit {
+                    ms: 1000.0,
+                    frames: 0,
+                },
+            ],
+        };
+        assert_eq!(limits.to_env(), "55:2,1000:0");
+        assert_eq!(limits.violation("frame,time\n0,60\n1,56\n2,12.5\n"), None);
+        assert_eq!(
+            limits.violation("frame,time\n0,60\n1,56\n2,55.5\n"),
+            Some((limits.rules[0], 3))
+        );
+        assert_eq!(
+            limits.violation("frame,time\n0,1000.5\n"),
+            Some((limits.rules[1], 1))
+        );
+    }
+
+    #[test]
+    fn test_resolve_start_location() {
+        let unit_events = "frame_number,event_type,player_owned,unit_id,unit_type,unit_position\n\
+            0,unitShow,ZergHell,1,Zerg_Drone,(3504,240)\n\
+            0,unitShow,ZergHell,2,Zerg_Hatchery,(3520,176)\n\
+            0,unitShow,MarineHell,3,Terran_Command_Center,(192,3880)\n\
+            1500,unitCreate,ZergHell,4,Zerg_Hatchery,(3200,400)\n";
+        assert_eq!(
+            resolve_start_location(unit_events, "MarineHell"),
+            Some(StartLocation {
+                player: "MarineHell".to_string(),
+                x: 192,
+                y: 3880,
+                race: Race::Terran
+            })
+        );
+        assert_eq!(
+            resolve_start_location(unit_events, "ZergHell").map(|it| it.race),
+            Some(Race::Zerg)
+        );
+        assert_eq!(resolve_start_location(unit_events, "Unknown"), None);
+        assert_eq!(resolve_start_location("frame_number\n", "ZergHell"), None);
+    }
+
+    #[test]
+    fn test_parse_tm_result() {
+        let tm_result = parse_tm_result(
+            "{\n\"is_winner\": true,\n\"is_crashed\": false,\n\"timed_out\": false,\n\"kill_score\": 1200\n}\n",
+        );
+        assert_eq!(
+            tm_result,
+            TmResult {
+                is_winner: true,
+                is_crashed: false,
+                timed_out: false,
+                disqualified: None
+            }
+        );
+        assert_eq!(
+            parse_tm_result("\"is_winner\": false\n\"disqualified\": true\n").disqualified,
+            Some(true)
+        );
+        assert_eq!(tm_result.outcome(), Outcome::Win);
+        let timed_out = parse_tm_result("\"is_winner\": false\n\"timed_out\": true\n");
+        assert_eq!(timed_out.outcome(), Outcome::Draw);
+        assert_eq!(parse_tm_result("").outcome(), Outcome::Loss);
+    }
+
+    #[test]
+    fn test_frame_limits_mismatch() {
+        let rule = FrameLimit {
+            ms: 55.0,
+            frames: 320,
+        };
+        assert_eq!(frame_limits_mismatch(None, Some(rule)), None);
+        assert_eq!(frame_limits_mismatch(Some(true), Some(rule)), None);
+        assert_eq!(frame_limits_mismatch(Some(false), None), None);
+        assert_eq!(
+            frame_limits_mismatch(Some(false), Some(rule)).as_deref(),
+            Some("BWAIShotgun disqualified the bot (at most 320 frames over 55ms), but the tournament module did not")
+        );
+        assert!(frame_limits_mismatch(Some(true), None)
+            .is_some_and(|it| it.contains("found no violation")));
+    }
+}
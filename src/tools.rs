@@ -0,0 +1,76 @@
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+
+use crc::{Crc, CRC_32_ISO_HDLC};
+use sha2::{Digest, Sha256};
+
+/// Tools shipped with BWAIShotgun and the CRC-32 of the released files
+const TOOLS: [(&str, u32); 4] = [
+    ("bwheadless.exe", 0x76b3891c),
+    ("injectory_x86.exe", 0x029f837a),
+    ("oldbwapi.dll", 0x60facff3),
+    ("WMode.dll", 0x73799c46),
+];
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ToolProblem {
+    Missing(PathBuf),
+    /// Not readable or not an executable/dll at all
+    Corrupted(PathBuf),
+    UnexpectedHash {
+        path: PathBuf,
+        crc: u32,
+    },
+}
+
+impl Display for ToolProblem {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ToolProblem::Missing(path) => write!(f, "'{}' is missing", path.display()),
+            ToolProblem::Corrupted(path) => write!(f, "'{}' is corrupted", path.display()),
+            ToolProblem::UnexpectedHash { path, crc } => write!(
+                f,
+                "'{}' has an unexpected checksum ({crc:08x})",
+                path.display()
+            ),
+        }
+    }
+}
+
+/// Verifies the tools, which are sometimes quarantined or modified by antivirus software
+pub fn check_tools_integrity(tools_dir: &Path) -> Vec<ToolProblem> {
+    let crc = Crc::<u32>::new(&CRC_32_ISO_HDLC);
+    TOOLS
+        .iter()
+        .filter_map(|(name, expected_crc)| {
+            let path = tools_dir.join(name);
+            if !path.exists() {
+                return Some(ToolProblem::Missing(path));
+            }
+            let Ok(content) = std::fs::read(&path) else {
+                return Some(ToolProblem::Corrupted(path));
+            };
+            if !content.starts_with(b"MZ") {
+                return Some(ToolProblem::Corrupted(path));
+            }
+            let actual_crc = crc.checksum(&content);
+            (actual_crc != *expected_crc).then_some(ToolProblem::UnexpectedHash {
+                path,
+                crc: actual_crc,
+            })
+        })
+        .collect()
+}
+
+/// SHA-256 over the names and contents of all tools, missing tools are hashed by name only
+pub fn tools_sha256(tools_dir: &Path) -> String {
+    let mut hasher = Sha256::new();
+    for (name, _) in TOOLS {
+        hasher.update(name);
+        match std::fs::read(tools_dir.join(name)) {
+            Ok(content) => hasher.update(Sha256::digest(content)),
+            Err(_) => hasher.update(b"missing"),
+        }
+    }
+    format!("{:x}", hasher.finalize())
+}
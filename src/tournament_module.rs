@@ -0,0 +1,198 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use anyhow::{bail, Context};
+use serde::Deserialize;
+
+use crate::bwapi::BwapiVersion;
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum TournamentModule {
+    None,
+    Default,
+    Custom { prefix: String },
+}
+
+impl Default for TournamentModule {
+    fn default() -> Self {
+        Self::Default
+    }
+}
+
+/// What to do, if the tournament module of a bot is not in the 'tm' folder
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum TmMissing {
+    /// Abort the game
+    #[default]
+    Error,
+    /// Run the bot without tournament module and log a warning
+    Warn,
+    /// Run the bot without tournament module
+    Skip,
+}
+
+impl TournamentModule {
+    /// Parses "none", "default" or a custom prefix (ie. given on the command line)
+    pub fn from_name(name: &str) -> Self {
+        match name.to_ascii_lowercase().as_str() {
+            "none" => TournamentModule::None,
+            "default" => TournamentModule::Default,
+            _ => TournamentModule::Custom {
+                prefix: name.to_string(),
+            },
+        }
+    }
+
+    /// File name of the tournament module dll for the given BWAPI version, ie. `TM_440.dll`
+    pub fn file_name(&self, bwapi_version: &BwapiVersion) -> Option<String> {
+        let prefix = match self {
+            TournamentModule::None => return None,
+            TournamentModule::Default => "TM",
+            TournamentModule::Custom { prefix } => prefix,
+        };
+        Some(format!("{prefix}_{}.dll", bwapi_version.version_short()))
+    }
+
+    /// Checks that the tournament module required by the bot in `bot_path` is available in the
+    /// `tm_dir` folder.
+    pub fn validate_presence(
+        &self,
+        tm_dir: &Path,
+        bot_path: &Path,
+        bwapi_version: &BwapiVersion,
+    ) -> anyhow::Result<()> {
+        if let Some(tm_name) = self.file_name(bwapi_version) {
+            validate_tm_file(tm_dir, &tm_name).with_context(|| {
+                format!(
+                    "Tournament module of bot in '{}' is not available. Get '{tm_name}' from \
+                    https://github.com/basil-ladder/sc-tm, or set 'tm_missing' in 'shotgun.toml' \
+                    to run without it",
+                    bot_path.display()
+                )
+            })?;
+        }
+        Ok(())
+    }
+}
+
+/// Splits a tournament module file name into prefix and BWAPI version: `TM_440.dll` => `("TM", "440")`
+fn split_module_name(file_name: &str) -> Option<(&str, &str)> {
+    let (name, extension) = file_name.rsplit_once('.')?;
+    if !extension.eq_ignore_ascii_case("dll") {
+        return None;
+    }
+    name.rsplit_once('_')
+}
+
+/// Tournament modules in `tm_folder`, grouped by prefix with the BWAPI versions available for each
+pub fn available_modules(tm_folder: &Path) -> BTreeMap<String, Vec<String>> {
+    let mut modules: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for entry in tm_folder.read_dir().into_iter().flatten().flatten() {
+        let file_name = entry.file_name().to_string_lossy().to_string();
+        if let Some((prefix, version)) = split_module_name(&file_name) {
+            modules
+                .entry(prefix.to_string())
+                .or_default()
+                .push(version.to_string());
+        }
+    }
+    for versions in modules.values_mut() {
+        versions.sort();
+    }
+    modules
+}
+
+fn validate_tm_file(tm_folder: &Path, tm_name: &str) -> anyhow::Result<()> {
+    let tm_file = tm_folder.join(tm_name);
+    if !tm_file.exists() {
+        let available = available_modules(tm_folder);
+        let version = split_module_name(tm_name).map(|(_, version)| version);
+        let prefixes: Vec<_> = available
+            .iter()
+            .filter(|(_, versions)| versions.iter().any(|it| Some(it.as_str()) == version))
+            .map(|(prefix, _)| prefix.as_str())
+            .collect();
+        let all: Vec<_> = available
+            .iter()
+            .flat_map(|(prefix, versions)| {
+                versions
+                    .iter()
+                    .map(move |version| format!("{prefix}_{version}.dll"))
+            })
+            .collect();
+        bail!(
+            "Could not find '{}' in '{}'. Prefixes available for this BWAPI version: {} (all tournament modules: {})",
+            tm_name,
+            tm_folder.display(),
+            if prefixes.is_empty() {
+                "none".to_string()
+            } else {
+                prefixes.join(", ")
+            },
+            if all.is_empty() {
+                "none".to_string()
+            } else {
+                all.join(", ")
+            }
+        );
+    }
+    let mut header = [0u8; 2];
+    File::open(&tm_file)
+        .and_then(|mut file| file.read_exact(&mut header))
+        .with_context(|| format!("Could not read '{}'", tm_file.display()))?;
+    if &header != b"MZ" {
+        bail!("'{}' is not a valid DLL", tm_file.display());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::bwapi::BwapiVersion;
+    use crate::tournament_module::{available_modules, validate_tm_file, TournamentModule};
+    use std::path::Path;
+
+    #[test]
+    fn test_available_modules() {
+        let modules = available_modules(Path::new("tm"));
+        assert_eq!(modules["TM"], ["375", "412", "420", "440"]);
+    }
+
+    #[test]
+    fn test_validate_tm_file() {
+        assert!(validate_tm_file(Path::new("tm"), "TM_440.dll").is_ok());
+
+        let err = validate_tm_file(Path::new("tm"), "aa_440.dll").unwrap_err();
+        let msg = err.to_string();
+        assert!(msg.contains("aa_440.dll"));
+        assert!(msg.contains("Prefixes available for this BWAPI version: TM "));
+        for tm in ["TM_375.dll", "TM_412.dll", "TM_420.dll", "TM_440.dll"] {
+            assert!(msg.contains(tm), "'{msg}' does not list '{tm}'");
+        }
+    }
+
+    #[test]
+    fn test_validate_presence() {
+        let tm = TournamentModule::Custom {
+            prefix: "aa".to_string(),
+        };
+        let msg = tm
+            .validate_presence(
+                Path::new("tm"),
+                Path::new("bots/aa"),
+                &BwapiVersion::Bwapi440,
+            )
+            .unwrap_err()
+            .to_string();
+        assert!(msg.contains("Get 'aa_440.dll' from"), "{msg}");
+        assert!(TournamentModule::Default
+            .validate_presence(
+                Path::new("tm"),
+                Path::new("bots/aa"),
+                &BwapiVersion::Bwapi440
+            )
+            .is_ok());
+    }
+}
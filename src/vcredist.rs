@@ -0,0 +1,106 @@
+//! BWAPI dlls loaded by StarCraft or bwheadless are linked against the 32-bit Visual C++
+//! runtime. If it is missing, loading fails with an unhelpful "could not load library".
+use std::path::Path;
+
+#[cfg(target_os = "windows")]
+use registry::{Data, Hive, Security};
+
+use crate::bwapi::BwapiVersion;
+
+/// A Visual C++ redistributable required by BWAPI
+#[derive(Debug, PartialEq, Eq)]
+pub struct VcRuntime {
+    pub name: &'static str,
+    /// Dlls which must be resolvable for 32-bit processes
+    dlls: &'static [&'static str],
+    /// Key below HKLM, containing an `Installed` value if the redistributable is installed
+    #[cfg_attr(not(target_os = "windows"), allow(dead_code))]
+    registry_key: &'static str,
+    pub download_url: &'static str,
+}
+
+pub const VC_2013: VcRuntime = VcRuntime {
+    name: "Visual C++ 2013 Redistributable (x86)",
+    dlls: &["msvcr120.dll", "msvcp120.dll"],
+    registry_key: r"SOFTWARE\Microsoft\VisualStudio\12.0\VC\Runtimes\x86",
+    download_url: "https://aka.ms/highdpimfc2013x86enu",
+};
+
+pub const VC_2015: VcRuntime = VcRuntime {
+    name: "Visual C++ 2015-2022 Redistributable (x86)",
+    dlls: &["vcruntime140.dll", "msvcp140.dll"],
+    registry_key: r"SOFTWARE\Microsoft\VisualStudio\14.0\VC\Runtimes\x86",
+    download_url: "https://aka.ms/vs/17/release/vc_redist.x86.exe",
+};
+
+impl VcRuntime {
+    /// BWAPI 3.7.x has no dependency on a redistributable
+    pub fn required_by(version: BwapiVersion) -> Option<&'static VcRuntime> {
+        match version {
+            BwapiVersion::Bwapi375 => None,
+            BwapiVersion::Bwapi412 | BwapiVersion::Bwapi420 => Some(&VC_2013),
+            BwapiVersion::Bwapi440 => Some(&VC_2015),
+        }
+    }
+
+    /// Checks if the dlls are next to StarCraft.exe, in the 32-bit system folder or if the
+    /// registry lists the redistributable as installed. Always true outside of Windows, Wine
+    /// resolves the dlls differently and should not be checked.
+    pub fn is_installed(&self, starcraft_path: &Path) -> bool {
+        #[cfg(target_os = "windows")]
+        {
+            let windows = std::env::var_os("WINDIR")
+                .map(std::path::PathBuf::from)
+                .unwrap_or_else(|| r"C:\Windows".into());
+            // On 64-bit Windows, 32-bit system dlls live in SysWOW64
+            let system_folder = [windows.join("SysWOW64"), windows.join("System32")]
+                .into_iter()
+                .find(|it| it.exists());
+            let dlls_found = self.dlls.iter().all(|dll| {
+                starcraft_path.join(dll).exists()
+                    || system_folder
+                        .as_ref()
+                        .is_some_and(|folder| folder.join(dll).exists())
+            });
+            dlls_found
+                || matches!(
+                    Hive::LocalMachine
+                        .open(self.registry_key, Security::Read | Security::Wow6432Key)
+                        .ok()
+                        .and_then(|key| key.value("Installed").ok()),
+                    Some(Data::U32(1))
+                )
+        }
+        #[cfg(not(target_os = "windows"))]
+        {
+            let _ = starcraft_path;
+            true
+        }
+    }
+
+    pub fn missing_message(&self, bot_name: &str) -> String {
+        format!(
+            "'{bot_name}' requires the {}, which could not be found. Please install it from {}",
+            self.name, self.download_url
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::bwapi::BwapiVersion;
+    use crate::vcredist::{VcRuntime, VC_2013, VC_2015};
+
+    #[test]
+    fn test_required_by() {
+        assert_eq!(VcRuntime::required_by(BwapiVersion::Bwapi375), None);
+        assert_eq!(
+            VcRuntime::required_by(BwapiVersion::Bwapi420),
+            Some(&VC_2013)
+        );
+        assert_eq!(
+            VcRuntime::required_by(BwapiVersion::Bwapi440),
+            Some(&VC_2015)
+        );
+    }
+}
@@ -0,0 +1,81 @@
+//! Multiple headful StarCraft windows all show "Brood War", renaming them after the bot makes them
+//! distinguishable.
+
+/// Sets the title of the visible windows of the given launcher process (ie. injectory) and its
+/// direct child processes (StarCraft). Returns false if no window exists yet, StarCraft takes a
+/// while to create it. Outside of Windows, nothing can be done and true is returned.
+pub fn set_window_title(launcher_process_id: u32, title: &str) -> bool {
+    #[cfg(target_os = "windows")]
+    {
+        use windows_sys::Win32::Foundation::{BOOL, HWND, LPARAM};
+        use windows_sys::Win32::UI::WindowsAndMessaging::{
+            EnumWindows, GetWindowThreadProcessId, IsWindowVisible, SetWindowTextW,
+        };
+
+        struct Search {
+            process_ids: Vec<u32>,
+            title: Vec<u16>,
+            renamed: bool,
+        }
+
+        unsafe extern "system" fn rename_window(window: HWND, search: LPARAM) -> BOOL {
+            let search = &mut *(search as *mut Search);
+            let mut process_id = 0;
+            GetWindowThreadProcessId(window, &mut process_id);
+            if search.process_ids.contains(&process_id)
+                && IsWindowVisible(window) != 0
+                && SetWindowTextW(window, search.title.as_ptr()) != 0
+            {
+                search.renamed = true;
+            }
+            1
+        }
+
+        let Some(process_ids) = launched_process_ids(launcher_process_id) else {
+            return false;
+        };
+        let mut search = Search {
+            process_ids,
+            title: title.encode_utf16().chain(Some(0)).collect(),
+            renamed: false,
+        };
+        unsafe {
+            EnumWindows(Some(rename_window), &mut search as *mut Search as LPARAM);
+        }
+        search.renamed
+    }
+    #[cfg(not(target_os = "windows"))]
+    {
+        let _ = (launcher_process_id, title);
+        true
+    }
+}
+
+/// The launcher process and its direct child processes, `None` if the processes can't be listed
+#[cfg(target_os = "windows")]
+pub fn launched_process_ids(launcher_process_id: u32) -> Option<Vec<u32>> {
+    use windows_sys::Win32::Foundation::{CloseHandle, INVALID_HANDLE_VALUE};
+    use windows_sys::Win32::System::Diagnostics::ToolHelp::{
+        CreateToolhelp32Snapshot, Process32FirstW, Process32NextW, PROCESSENTRY32W,
+        TH32CS_SNAPPROCESS,
+    };
+
+    let mut process_ids = vec![launcher_process_id];
+    unsafe {
+        let snapshot = CreateToolhelp32Snapshot(TH32CS_SNAPPROCESS, 0);
+        if snapshot == INVALID_HANDLE_VALUE {
+            return None;
+        }
+        let mut entry: PROCESSENTRY32W = std::mem::zeroed();
+        entry.dwSize = std::mem::size_of::<PROCESSENTRY32W>() as u32;
+        let mut has_entry = Process32FirstW(snapshot, &mut entry) != 0;
+        while has_entry {
+            if entry.th32ParentProcessID == launcher_process_id {
+                process_ids.push(entry.th32ProcessID);
+            }
+            has_entry = Process32NextW(snapshot, &mut entry) != 0;
+        }
+        CloseHandle(snapshot);
+    }
+    Some(process_ids)
+}
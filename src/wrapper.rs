@@ -2,6 +2,7 @@ use std::ffi::OsStr;
 use std::path::PathBuf;
 use std::process::Command;
 
+use anyhow::ensure;
 use serde::Deserialize;
 
 #[derive(Deserialize, Clone, Debug)]
@@ -30,6 +31,23 @@ impl Default for ExecutionWrapper {
 }
 
 impl ExecutionWrapper {
+    /// Ensures the wrapper executable can be found, before any process is launched with it
+    pub fn validate(&self) -> anyhow::Result<()> {
+        match self {
+            ExecutionWrapper::Sandboxie { executable, .. } => ensure!(
+                executable.is_file(),
+                "Could not find the Sandboxie executable '{}', please check 'shotgun.toml'",
+                executable.display()
+            ),
+            ExecutionWrapper::Wine => ensure!(
+                find_in_path("wine").is_some(),
+                "Could not find 'wine' in PATH, please install wine or configure another wrapper in 'shotgun.toml'"
+            ),
+            ExecutionWrapper::Unconfigured | ExecutionWrapper::NoWrapper => (),
+        }
+        Ok(())
+    }
+
     pub fn wrap_executable(&self, exe: impl AsRef<OsStr>) -> Command {
         match self {
             ExecutionWrapper::Sandboxie {
@@ -52,3 +70,9 @@ impl ExecutionWrapper {
         }
     }
 }
+
+fn find_in_path(executable: &str) -> Option<PathBuf> {
+    std::env::split_paths(&std::env::var_os("PATH")?)
+        .map(|dir| dir.join(executable))
+        .find(|candidate| candidate.is_file())
+}